@@ -0,0 +1,99 @@
+//! Which probe-rs binary is used, for sessions as well as for the commands run outside of them.
+//!
+//! The slash commands and the CLI commands run before a session use the same binary as the
+//! session, so a configured or pinned probe-rs isn't mixed with another one on the `PATH`.
+
+use zed_extension_api::{Worktree, serde_json::Value};
+
+use crate::settings::Settings;
+
+/// The probe-rs binary asked for by the scenario, the settings and the project.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Adapter {
+    /// The configured binary, together with where it was configured.
+    pub configured: Option<(String, String)>,
+    /// The name to look for on the `PATH`, from `adapterName`.
+    pub name: String,
+    /// The pinned version, from `adapterVersion` or the `.probe-rs-version` file.
+    pub pinned: Option<String>,
+}
+
+impl Adapter {
+    /// The binary for a scenario. Settings and scenarios both configure it, and the scenario
+    /// takes precedence.
+    pub fn new(
+        config: &Value,
+        settings: &Settings,
+        user_provided_path: Option<String>,
+        worktree: &Worktree,
+    ) -> Self {
+        let pinned = config
+            .get("adapterVersion")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .or(settings.adapter_version.clone())
+            .or_else(|| crate::version::pinned_by_file(worktree));
+
+        // A path for the project takes precedence over the one in the settings of Zed.
+        let configured = crate::config::adapter_path(
+            config,
+            settings.adapter_path.as_deref(),
+            &worktree.root_path(),
+        )
+        .or_else(|| {
+            user_provided_path.map(|path| (path, "the debugger settings of Zed".to_string()))
+        });
+
+        let name = config
+            .get("adapterName")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .or(settings.adapter_name.clone())
+            .unwrap_or_else(|| "probe-rs".to_string());
+
+        Adapter {
+            configured,
+            name,
+            pinned,
+        }
+    }
+
+    /// Find the binary without downloading it, together with where it was found.
+    ///
+    /// A pinned version is preferably taken from the cache, since checking the version of the
+    /// binary on the `PATH` needs to run it. That one is only used if it has the pinned version.
+    pub fn find(&self, worktree: &Worktree) -> Option<(String, &'static str)> {
+        let pinned = self.pinned.as_deref();
+
+        self.configured
+            .as_ref()
+            .map(|(binary, _)| (binary.clone(), "configured"))
+            .or_else(|| {
+                pinned
+                    .and_then(crate::install::cached)
+                    .map(|binary| (binary, "downloaded"))
+            })
+            .or_else(|| {
+                worktree
+                    .which(&self.name)
+                    .filter(|binary| {
+                        pinned.is_none_or(|version| crate::install::has_version(binary, version))
+                    })
+                    .map(|binary| (binary, "PATH"))
+            })
+            .or_else(|| {
+                crate::cargo_home::find(worktree, &self.name, pinned)
+                    .map(|binary| (binary, "cargo"))
+            })
+            .or_else(|| crate::vscode::find(worktree, pinned).map(|binary| (binary, "vscode")))
+    }
+
+    /// The binary for commands run outside of a session, falling back to the newest download
+    /// and else to `probe-rs` on the `PATH` of Zed.
+    pub fn binary(&self, worktree: &Worktree) -> String {
+        self.find(worktree)
+            .map(|(binary, _)| binary)
+            .or_else(crate::install::installed)
+            .unwrap_or_else(|| "probe-rs".to_string())
+    }
+}
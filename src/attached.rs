@@ -0,0 +1,155 @@
+//! Detection of other debuggers which use the probe, for `noFlashIfAttachedDebugger`.
+//!
+//! Boards in a lab are often shared. Flashing a board while a colleague debugs it replaces
+//! their firmware, so with the option set, a session doesn't flash while another debugger
+//! runs on the host.
+
+use zed_extension_api::{self as zed, Os, serde_json::Value};
+
+/// Tools which keep a probe open while they run.
+const DEBUGGERS: &[&str] = &[
+    "probe-rs",
+    "probe-run",
+    "cargo-embed",
+    "cargo-flash",
+    "openocd",
+    "pyocd",
+    "JLinkGDBServer",
+    "JLinkGDBServerCL",
+    "JLinkGDBServerCLExe",
+    "st-util",
+];
+
+/// Whether the option is set in the configuration.
+fn is_enabled(config: &Value) -> bool {
+    config
+        .get("noFlashIfAttachedDebugger")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// Fail if the option is set and another debugger seems to use the probe.
+pub fn guard(config: &Value) -> Result<(), String> {
+    if !is_enabled(config) {
+        return Ok(());
+    }
+
+    let serial = config
+        .pointer("/probe/serial_number")
+        .and_then(Value::as_str);
+
+    let processes = list_processes()
+        .map_err(|err| format!("Not flashing, other debuggers can't be detected: {err}"))?;
+
+    match processes
+        .lines()
+        .find(|process| is_conflicting(process, serial))
+    {
+        Some(process) => Err(format!(
+            "Not flashing, another debugger seems to use the probe: {}",
+            process.trim()
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The command lines of the processes on the host, one per line.
+///
+/// On Windows, only the names of the processes are available.
+fn list_processes() -> Result<String, String> {
+    let (os, _) = zed::current_platform();
+
+    match os {
+        Os::Windows => crate::process::run("tasklist", &["/fo", "csv", "/nh"].map(str::to_string)),
+        Os::Mac | Os::Linux => {
+            crate::process::run("ps", &["-A", "-o", "args="].map(str::to_string))
+        }
+    }
+}
+
+/// Whether a process is a debugger which may use the probe with the given serial number.
+///
+/// A debugger which selects another probe is not in the way. The dap-server started by Zed for
+/// the extension is not counted, since it only uses the probe while a session is running.
+fn is_conflicting(process: &str, serial: Option<&str>) -> bool {
+    let mut args = process
+        .split([' ', ','])
+        .map(|arg| arg.trim_matches('"'))
+        .filter(|arg| !arg.is_empty());
+
+    let Some(program) = args.next() else {
+        return false;
+    };
+    let program = program.rsplit(['/', '\\']).next().unwrap_or(program);
+    let program = program.strip_suffix(".exe").unwrap_or(program);
+
+    if !DEBUGGERS.contains(&program) {
+        return false;
+    }
+
+    let args: Vec<&str> = args.collect();
+
+    if program == "probe-rs"
+        && args.first() == Some(&"dap-server")
+        && args.windows(2).any(|pair| {
+            pair[0] == "--port" && pair[1].parse().is_ok_and(crate::server::is_extension_port)
+        })
+    {
+        return false;
+    }
+
+    let selects_probe = args
+        .iter()
+        .any(|arg| *arg == "--probe" || arg.starts_with("--probe="));
+
+    match serial {
+        Some(serial) if selects_probe => process.contains(serial),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn is_conflicting() {
+        assert!(super::is_conflicting(
+            "/usr/bin/openocd -f interface/stlink.cfg",
+            None
+        ));
+        assert!(super::is_conflicting(
+            "probe-rs run --chip nRF52840_xxAA app",
+            Some("0001")
+        ));
+        assert!(!super::is_conflicting("/usr/bin/zsh", None));
+
+        // Other probes are not in the way.
+        assert!(!super::is_conflicting(
+            "probe-rs attach --probe 1366:1015:0002 app",
+            Some("0001")
+        ));
+        assert!(super::is_conflicting(
+            "probe-rs attach --probe=1366:1015:0001 app",
+            Some("0001")
+        ));
+
+        // The server started by Zed for this extension.
+        assert!(!super::is_conflicting(
+            "/home/user/.cargo/bin/probe-rs dap-server --port 50000",
+            None
+        ));
+        assert!(!super::is_conflicting(
+            "probe-rs dap-server --port 50003 --single-session",
+            None
+        ));
+
+        // Output of tasklist on Windows.
+        assert!(super::is_conflicting(
+            "\"JLinkGDBServerCL.exe\",\"4242\",\"Console\",\"1\",\"20,480 K\"",
+            None
+        ));
+        assert!(!super::is_conflicting(
+            "\"chrome.exe\",\"4343\",\"Console\",\"1\",\"80,120 K\"",
+            None
+        ));
+    }
+}
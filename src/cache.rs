@@ -0,0 +1,49 @@
+//! The directory for downloads, which can be downloaded again at any time.
+//!
+//! Downloaded probe-rs versions and SVD files are kept in `cache/` in the working directory
+//! of the extension. The extension can only write to its working directory, so the cache
+//! can't be moved to `XDG_CACHE_HOME` or the cache directory of the platform. Instead, the
+//! directory is marked with a `CACHEDIR.TAG` file, which backup tools like borg, restic and
+//! `tar --exclude-caches` recognize, see <https://bford.info/cachedir/>.
+
+use std::fs;
+
+/// Directory in the working directory of the extension containing the caches.
+pub const DIRECTORY: &str = "cache";
+
+const TAG_FILE: &str = "CACHEDIR.TAG";
+
+const TAG: &str = "Signature: 8a477f597d28d172789f06886806bc55\n\
+                   # This file is a cache directory tag created by the probe-rs extension of Zed.\n\
+                   # For information about cache directory tags, see https://bford.info/cachedir/\n";
+
+/// Directories of earlier versions of the extension, and where they are now.
+const MOVED: &[(&str, &str)] = &[
+    ("probe-rs", crate::install::CACHE_DIRECTORY),
+    ("svd", crate::svd::DOWNLOAD_DIRECTORY),
+];
+
+/// Create the cache directory with its tag, and move the downloads of earlier versions of the
+/// extension into it, so they are not downloaded again.
+pub fn prepare() {
+    if let Err(err) = fs::create_dir_all(DIRECTORY) {
+        log!("Failed to create the cache directory '{DIRECTORY}': {err}");
+        return;
+    }
+
+    let tag = format!("{DIRECTORY}/{TAG_FILE}");
+    if fs::metadata(&tag).is_err()
+        && let Err(err) = fs::write(&tag, TAG)
+    {
+        log!("Failed to write '{tag}': {err}");
+    }
+
+    for (old, new) in MOVED {
+        if fs::metadata(old).is_ok_and(|metadata| metadata.is_dir())
+            && fs::metadata(new).is_err()
+            && let Err(err) = fs::rename(old, new)
+        {
+            log!("Failed to move '{old}' to '{new}': {err}");
+        }
+    }
+}
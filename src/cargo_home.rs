@@ -0,0 +1,101 @@
+//! Finding a probe-rs installed with `cargo install` or `cargo binstall`.
+//!
+//! Cargo installs binaries into `$CARGO_HOME/bin`, which defaults to `.cargo/bin` in the home
+//! directory, where rustup also puts its proxies for cargo and rustc. That directory is added
+//! to the `PATH` by the shell profile, which the environment of Zed often misses on macOS and
+//! Windows, so it is checked explicitly after the `PATH`.
+
+use zed_extension_api::{self as zed, Os, Worktree};
+
+/// The places of the binary with the given name, from the environment of the worktree.
+fn binary_candidates(os: Os, env: &[(String, String)], name: &str) -> Vec<String> {
+    let variable = |name: &str| {
+        env.iter()
+            .find_map(|(key, value)| (key == name && !value.is_empty()).then_some(value.as_str()))
+    };
+
+    let home_variable = if os == Os::Windows {
+        "USERPROFILE"
+    } else {
+        "HOME"
+    };
+    let binary = if os == Os::Windows && !name.ends_with(".exe") {
+        format!("{name}.exe")
+    } else {
+        name.to_string()
+    };
+
+    let mut directories = Vec::new();
+    if let Some(cargo_home) = variable("CARGO_HOME") {
+        directories.push(cargo_home.to_string());
+    }
+    // Also checked with CARGO_HOME set, since the variable may only be set for some shells.
+    if let Some(home) = variable(home_variable) {
+        let default = crate::paths::join(Some(home), ".cargo");
+        if !directories.contains(&default) {
+            directories.push(default);
+        }
+    }
+
+    directories
+        .iter()
+        .map(|directory| format!("{}/bin/{binary}", directory.trim_end_matches(['/', '\\'])))
+        .collect()
+}
+
+/// The binary with the given name installed by cargo, with exactly the `pinned` version if
+/// given.
+///
+/// The binaries are checked by running them, since the directories are outside of the sandbox
+/// of the extension.
+pub fn find(worktree: &Worktree, name: &str, pinned: Option<&str>) -> Option<String> {
+    let (os, _) = zed::current_platform();
+
+    binary_candidates(os, &worktree.shell_env(), name)
+        .into_iter()
+        .find(|binary| match pinned {
+            Some(version) => crate::install::has_version(binary, version),
+            None => crate::install::version(binary).is_some(),
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::Os;
+
+    fn env(variables: &[(&str, &str)]) -> Vec<(String, String)> {
+        variables
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn binary_candidates() {
+        assert_eq!(
+            super::binary_candidates(
+                Os::Mac,
+                &env(&[("HOME", "/Users/me"), ("CARGO_HOME", "/opt/cargo/")]),
+                "probe-rs"
+            ),
+            ["/opt/cargo/bin/probe-rs", "/Users/me/.cargo/bin/probe-rs"]
+        );
+        assert_eq!(
+            super::binary_candidates(
+                Os::Windows,
+                &env(&[("USERPROFILE", "C:\\Users\\me")]),
+                "probe-rs"
+            ),
+            ["C:\\Users\\me/.cargo/bin/probe-rs.exe"]
+        );
+        assert_eq!(
+            super::binary_candidates(
+                Os::Linux,
+                &env(&[("HOME", "/home/me"), ("CARGO_HOME", "/home/me/.cargo")]),
+                "probe-rs-debugger"
+            ),
+            ["/home/me/.cargo/bin/probe-rs-debugger"]
+        );
+        assert!(super::binary_candidates(Os::Linux, &[], "probe-rs").is_empty());
+    }
+}
@@ -0,0 +1,152 @@
+//! The chips supported by probe-rs, from `probe-rs chip list`.
+//!
+//! Listing the chips takes a while, so the list is cached in `cache/chips.json` together with
+//! the `--version` output of the binary it came from. A different probe-rs, like after an
+//! update, lists the chips again, and `/probe-rs-refresh-chips` does so on request, for example
+//! after replacing a binary built from a branch without changing its version.
+
+use std::fs;
+
+use zed_extension_api::serde_json::{self, Value, json};
+
+/// File in the working directory of the extension with the cached chip list.
+pub const CACHE_FILE: &str = "cache/chips.json";
+
+/// The chip names in the output of `probe-rs chip list`, which lists them indented below
+/// `Variants:` of their family.
+fn parse_list(output: &str) -> Vec<String> {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let mut variants_indent = None;
+    let mut chips = Vec::new();
+
+    for line in output.lines().filter(|line| !line.trim().is_empty()) {
+        if line.trim() == "Variants:" {
+            variants_indent = Some(indent(line));
+        } else if variants_indent.is_some_and(|variants| indent(line) > variants) {
+            chips.push(line.trim().to_string());
+        } else {
+            variants_indent = None;
+        }
+    }
+
+    chips
+}
+
+/// The cached chips, if they were listed by the binary with this `--version` output.
+fn cached(contents: &str, version: &str) -> Option<Vec<String>> {
+    let cache: Value = serde_json::from_str(contents).ok()?;
+    if cache.get("version").and_then(Value::as_str) != Some(version) {
+        return None;
+    }
+
+    cache
+        .get("chips")?
+        .as_array()?
+        .iter()
+        .map(|chip| chip.as_str().map(str::to_string))
+        .collect()
+}
+
+/// List the chips with the binary and cache them.
+fn list(binary: &str, version: &str) -> Result<Vec<String>, String> {
+    let output = crate::process::run(binary, &["chip".to_string(), "list".to_string()])?;
+    let chips = parse_list(&output);
+    if chips.is_empty() {
+        return Err(format!("'{binary} chip list' didn't list any chips"));
+    }
+
+    let contents = json!({ "version": version, "chips": chips }).to_string();
+    if let Err(err) = fs::write(CACHE_FILE, contents) {
+        log!("Failed to write '{CACHE_FILE}': {err}");
+    }
+
+    Ok(chips)
+}
+
+/// The chips supported by the binary with this `--version` output, from the cache if possible.
+pub fn load(binary: &str, version: &str) -> Option<Vec<String>> {
+    fs::read_to_string(CACHE_FILE)
+        .ok()
+        .and_then(|contents| cached(&contents, version))
+        .or_else(|| {
+            list(binary, version)
+                .map_err(|err| log!("Chips are not checked: {err}"))
+                .ok()
+        })
+}
+
+/// List the chips again, regardless of the cache.
+///
+/// Returns all chips, and the ones which were not in the cache before.
+pub fn refresh(binary: &str) -> Result<(Vec<String>, Vec<String>), String> {
+    let version = crate::install::version_output(binary)
+        .ok_or_else(|| format!("'{binary} --version' failed, is probe-rs installed?"))?;
+
+    let previous: Vec<String> = fs::read_to_string(CACHE_FILE)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<Value>(&contents).ok())
+        .and_then(|cache| serde_json::from_value(cache.get("chips")?.clone()).ok())
+        .unwrap_or_default();
+
+    let chips = list(binary, &version)?;
+    let added = if previous.is_empty() {
+        Vec::new()
+    } else {
+        chips
+            .iter()
+            .filter(|chip| !previous.contains(chip))
+            .cloned()
+            .collect()
+    };
+
+    Ok((chips, added))
+}
+
+/// Whether probe-rs accepts the chip name, which it compares ignoring case, and also accepts
+/// the start of a name.
+pub fn is_known(chips: &[String], chip: &str) -> bool {
+    let chip = chip.to_ascii_lowercase();
+
+    chips
+        .iter()
+        .any(|known| known.to_ascii_lowercase().starts_with(&chip))
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    const OUTPUT: &str = "Available chips:\n\
+                          nRF52\n    Variants:\n        nRF52805_xxAA\n        nRF52840_xxAA\n\
+                          STM32F1 Series\n    Variants:\n        STM32F103C8\n";
+
+    #[test]
+    fn parse_list() {
+        assert_eq!(
+            super::parse_list(OUTPUT),
+            ["nRF52805_xxAA", "nRF52840_xxAA", "STM32F103C8"]
+        );
+        assert!(super::parse_list("error: no such command").is_empty());
+    }
+
+    #[test]
+    fn cached() {
+        let contents = json!({ "version": "probe-rs 0.27.0", "chips": ["RP2040"] }).to_string();
+
+        assert_eq!(
+            super::cached(&contents, "probe-rs 0.27.0"),
+            Some(vec!["RP2040".to_string()])
+        );
+        assert_eq!(super::cached(&contents, "probe-rs 0.28.0"), None);
+        assert_eq!(super::cached("{", "probe-rs 0.27.0"), None);
+    }
+
+    #[test]
+    fn is_known() {
+        let chips = super::parse_list(OUTPUT);
+
+        assert!(super::is_known(&chips, "nrf52840_xxaa"));
+        assert!(super::is_known(&chips, "STM32F103"));
+        assert!(!super::is_known(&chips, "nRF54L15"));
+    }
+}
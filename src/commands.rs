@@ -0,0 +1,88 @@
+//! Slash commands which run one-off probe-rs operations outside of a debug session.
+
+mod attach_under_reset;
+mod doctor;
+pub mod dump;
+mod duplicate;
+mod effective_config;
+mod flash_all;
+mod last_session;
+pub mod log;
+mod migrate;
+mod refresh_chips;
+mod reset;
+mod run;
+mod statistics;
+mod target_gen;
+mod validate;
+mod verify;
+
+use zed_extension_api::{
+    SlashCommand, SlashCommandOutput, SlashCommandOutputSection, Worktree, serde_json::Value,
+};
+
+pub fn run(
+    command: SlashCommand,
+    args: Vec<String>,
+    worktree: Option<&Worktree>,
+) -> Result<SlashCommandOutput, String> {
+    if let Some(worktree) = worktree {
+        crate::process::set_environment(worktree.shell_env());
+    }
+
+    match command.name.as_str() {
+        attach_under_reset::COMMAND_NAME => attach_under_reset::run(&args, worktree),
+        doctor::COMMAND_NAME => doctor::run(&args, worktree),
+        dump::COMMAND_NAME => dump::run(&args, worktree),
+        duplicate::COMMAND_NAME => duplicate::run(&args, worktree),
+        effective_config::COMMAND_NAME => effective_config::run(&args, worktree),
+        flash_all::COMMAND_NAME => flash_all::run(&args, worktree),
+        last_session::COMMAND_NAME => last_session::run(&args, worktree),
+        log::COMMAND_NAME => log::run(&args, worktree),
+        migrate::COMMAND_NAME => migrate::run(&args, worktree),
+        refresh_chips::COMMAND_NAME => refresh_chips::run(&args, worktree),
+        reset::COMMAND_NAME => reset::run(&args, worktree),
+        run::COMMAND_NAME => run::run(&args, worktree),
+        statistics::COMMAND_NAME => statistics::run(&args, worktree),
+        target_gen::COMMAND_NAME => target_gen::run(&args, worktree),
+        validate::COMMAND_NAME => validate::run(&args, worktree),
+        verify::COMMAND_NAME => verify::run(&args, worktree),
+        name => Err(format!("Unknown slash command '{name}'")),
+    }
+}
+
+/// Find the probe-rs binary which should be used to run a command.
+///
+/// This is the binary a session without `adapterPath`, `adapterName` and `adapterVersion` in
+/// its scenario would use, so the settings and a pinned version apply. The `PATH` of the
+/// worktree comes from the login shell of the user, so binaries installed by cargo or rustup
+/// are found even if Zed itself was started with a different `PATH`, like when launched from
+/// the macOS dock.
+pub(crate) fn probe_rs_binary(worktree: Option<&Worktree>) -> String {
+    match worktree {
+        Some(worktree) => {
+            let settings = crate::settings::Settings::load(worktree);
+            crate::adapter::Adapter::new(&Value::Null, &settings, None, worktree).binary(worktree)
+        }
+        None => crate::install::installed().unwrap_or_else(|| "probe-rs".to_string()),
+    }
+}
+
+/// Resolve a path given as a command argument relative to the worktree root.
+fn resolve_path(path: &str, worktree: Option<&Worktree>) -> String {
+    crate::paths::join(
+        worktree.map(|worktree| worktree.root_path()).as_deref(),
+        path,
+    )
+}
+
+/// Wrap text in a slash command output with a single section covering all of it.
+fn output(label: impl Into<String>, text: String) -> SlashCommandOutput {
+    SlashCommandOutput {
+        sections: vec![SlashCommandOutputSection {
+            range: (0..text.len()).into(),
+            label: label.into(),
+        }],
+        text,
+    }
+}
@@ -0,0 +1,97 @@
+//! `/probe-rs-attach-under-reset`: suggest a scenario for targets which can't be attached to
+//! normally.
+//!
+//! Targets which are asleep, or run firmware which disables the debug pins, can only be
+//! attached to while they are held in reset. This is a frequent question for locked nRF and
+//! low-power STM32L parts, and the right combination of options is easy to get wrong.
+
+use zed_extension_api::{
+    SlashCommandOutput, Worktree,
+    serde_json::{self, Value, json},
+};
+
+use crate::{
+    project::Project,
+    settings::Settings,
+    targets::{self, ResetConnection},
+};
+
+pub const COMMAND_NAME: &str = "probe-rs-attach-under-reset";
+
+const USAGE: &str = "Usage: /probe-rs-attach-under-reset [chip] [program]";
+
+fn scenario(chip: Option<&str>, program: Option<&str>) -> Value {
+    let mut core = json!({ "coreIndex": 0 });
+
+    if let Some(program) = program {
+        core["programBinary"] = program.into();
+    }
+
+    json!({
+        "label": "Attach under reset",
+        "adapter": crate::ADAPTER_NAME,
+        "request": "attach",
+        "cwd": "$ZED_WORKTREE_ROOT",
+        "chip": chip.unwrap_or("<chip>"),
+        "connectUnderReset": true,
+        "coreConfigs": [core],
+    })
+}
+
+pub fn run(args: &[String], worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let (chip, program) = match args {
+        [] => (None, None),
+        [chip] => (Some(chip.clone()), None),
+        [chip, program] => (Some(chip.clone()), Some(program.as_str())),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let settings = match worktree {
+        Some(worktree) => Settings::load(worktree),
+        None => Settings::last_loaded(),
+    };
+
+    // Without a chip argument, use the chip of the project, if there is one.
+    let chip = chip.or_else(|| {
+        settings
+            .chip
+            .clone()
+            .or_else(|| Project::last_loaded().chip)
+    });
+
+    if let Some(chip) = &chip
+        && let Some((ResetConnection::Forbidden, reason)) =
+            targets::reset_connection(chip, &settings.connect_under_reset)
+    {
+        return Err(format!(
+            "'{chip}' can't be connected to under reset: {reason}"
+        ));
+    }
+
+    let scenario = serde_json::to_string_pretty(&scenario(chip.as_deref(), program))
+        .map_err(|err| format!("Failed to format the scenario: {err}"))?;
+
+    Ok(super::output(
+        "Attach under reset",
+        format!(
+            "This scenario holds the target in reset while connecting, and then attaches to it without flashing. Add it to `.zed/debug.json`:\n\n```json\n{scenario}\n```\n\nThe reset pin of the probe has to be connected to the target. Locked nRF chips can't be attached to at all, they have to be unlocked first with `probe-rs erase --allow-erase-all`, which erases the flash.\n"
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn scenario() {
+        let scenario = super::scenario(Some("STM32L071KBTx"), Some("target/app"));
+
+        assert_eq!(scenario["request"], "attach");
+        assert_eq!(scenario["connectUnderReset"], true);
+        assert_eq!(scenario["chip"], "STM32L071KBTx");
+        assert_eq!(scenario["coreConfigs"][0]["programBinary"], "target/app");
+
+        let scenario = super::scenario(None, None);
+        assert_eq!(scenario["chip"], "<chip>");
+        assert!(scenario["coreConfigs"][0].get("programBinary").is_none());
+    }
+}
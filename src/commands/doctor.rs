@@ -0,0 +1,264 @@
+//! `/probe-rs-doctor`: check the most common causes of failing sessions.
+//!
+//! Most sessions which fail to start do so because probe-rs is missing, the USB permissions
+//! are wrong, no probe is connected, or the port of the debug adapter is taken. Each of these
+//! is checked, and the results are shown as a checklist.
+
+use std::fmt::Write;
+
+use zed_extension_api::{self as zed, Os, SlashCommandOutput, Worktree};
+
+use crate::{enumeration, server, version::Version};
+
+pub const COMMAND_NAME: &str = "probe-rs-doctor";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Pass,
+    Fail,
+    Skipped,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Pass => "Pass",
+            Status::Fail => "Fail",
+            Status::Skipped => "Skipped",
+        }
+    }
+}
+
+struct Check {
+    name: &'static str,
+    status: Status,
+    details: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: Status, details: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            details: details.into(),
+        }
+    }
+}
+
+fn check_adapter(probe_rs: Option<&str>) -> Check {
+    const NAME: &str = "probe-rs";
+
+    let Some(probe_rs) = probe_rs else {
+        return Check::new(
+            NAME,
+            Status::Fail,
+            "probe-rs was not found in the PATH, see <https://probe.rs/docs/getting-started/installation/>",
+        );
+    };
+
+    match crate::process::run(probe_rs, &["--version".to_string()]) {
+        Ok(output) => match Version::from_version_output(&output) {
+            Some(version) => match crate::version::check_minimum(version) {
+                Ok(()) => Check::new(NAME, Status::Pass, format!("{version} at {probe_rs}")),
+                Err(err) => Check::new(NAME, Status::Fail, format!("{err} Found at {probe_rs}")),
+            },
+            None => Check::new(NAME, Status::Pass, format!("Unknown version at {probe_rs}")),
+        },
+        Err(err) => Check::new(NAME, Status::Fail, err),
+    }
+}
+
+/// The udev rules are needed on Linux to access probes without root.
+fn check_usb_permissions(os: Os) -> Check {
+    const NAME: &str = "USB permissions";
+
+    if os != Os::Linux {
+        return Check::new(NAME, Status::Skipped, "Only needed on Linux");
+    }
+
+    // Each directory is searched separately, since `find` fails for missing directories.
+    let found: String = [
+        "/etc/udev/rules.d",
+        "/usr/lib/udev/rules.d",
+        "/lib/udev/rules.d",
+    ]
+    .iter()
+    .filter_map(|directory| {
+        let args = [directory, "-name", "*probe-rs*"].map(str::to_string);
+        crate::process::run("find", &args).ok()
+    })
+    .collect();
+
+    match found.lines().find(|line| line.ends_with(".rules")) {
+        Some(rules) => Check::new(NAME, Status::Pass, format!("udev rules found at {rules}")),
+        None => Check::new(
+            NAME,
+            Status::Fail,
+            "No probe-rs udev rules found, see <https://probe.rs/docs/getting-started/probe-setup/>",
+        ),
+    }
+}
+
+fn check_probes(probe_rs: Option<&str>) -> Check {
+    const NAME: &str = "Probes";
+
+    let Some(probe_rs) = probe_rs else {
+        return Check::new(
+            NAME,
+            Status::Skipped,
+            "probe-rs is needed to list the probes",
+        );
+    };
+
+    match enumeration::try_list(probe_rs) {
+        Ok(probes) if probes.is_empty() => {
+            Check::new(NAME, Status::Fail, "No debug probes are connected")
+        }
+        Ok(probes) => {
+            let probes: Vec<_> = probes
+                .iter()
+                .map(|probe| format!("{:04x}:{:04x}", probe.vendor_id, probe.product_id))
+                .collect();
+
+            Check::new(
+                NAME,
+                Status::Pass,
+                format!("Connected: {}", probes.join(", ")),
+            )
+        }
+        Err(err) => Check::new(NAME, Status::Fail, err),
+    }
+}
+
+/// The command which lists listening sockets on the port, printing nothing if it's free.
+fn port_command(os: Os, port: u16) -> (&'static str, Vec<String>) {
+    match os {
+        Os::Linux => ("ss", vec!["-Htln".to_string(), format!("sport = :{port}")]),
+        Os::Mac => (
+            "lsof",
+            vec![format!("-iTCP:{port}"), "-sTCP:LISTEN".to_string()],
+        ),
+        Os::Windows => (
+            "netstat",
+            vec!["-an".to_string(), "-p".to_string(), "TCP".to_string()],
+        ),
+    }
+}
+
+/// Whether the output of the port command shows a listening socket on the port.
+fn port_in_use(os: Os, output: &str, port: u16) -> bool {
+    match os {
+        Os::Windows => output
+            .lines()
+            .any(|line| line.contains("LISTENING") && line.contains(&format!(":{port} "))),
+        Os::Linux | Os::Mac => output.lines().any(|line| !line.trim().is_empty()),
+    }
+}
+
+fn check_port(os: Os, port: u16) -> Check {
+    const NAME: &str = "Debug adapter port";
+
+    let (command, args) = port_command(os, port);
+
+    match crate::process::run(command, &args) {
+        Ok(output) if port_in_use(os, &output, port) => Check::new(
+            NAME,
+            Status::Fail,
+            format!(
+                "Port {port} is already in use, e.g. by a probe-rs server which is still running"
+            ),
+        ),
+        Ok(_) => Check::new(NAME, Status::Pass, format!("Port {port} is free")),
+        // lsof exits with an error if nothing uses the port.
+        Err(_) if os == Os::Mac => Check::new(NAME, Status::Pass, format!("Port {port} is free")),
+        Err(err) => Check::new(
+            NAME,
+            Status::Skipped,
+            format!("Couldn't check the port: {err}"),
+        ),
+    }
+}
+
+fn format_checks(checks: &[Check]) -> String {
+    let mut text = String::new();
+
+    for check in checks {
+        let _ = writeln!(
+            text,
+            "- **{}** {}: {}",
+            check.status.name(),
+            check.name,
+            check.details
+        );
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == Status::Fail)
+        .count();
+
+    match failed {
+        0 => text.push_str("\nAll checks passed.\n"),
+        failed => {
+            let _ = writeln!(text, "\n{failed} of {} checks failed.", checks.len());
+        }
+    }
+
+    text
+}
+
+pub fn run(_args: &[String], worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let (os, _) = zed::current_platform();
+
+    let probe_rs = worktree.and_then(|worktree| worktree.which("probe-rs"));
+
+    let checks = [
+        check_adapter(probe_rs.as_deref()),
+        check_usb_permissions(os),
+        check_probes(probe_rs.as_deref()),
+        check_port(os, server::DEFAULT_PORT),
+    ];
+
+    let mut text = format_checks(&checks);
+    if let Some(timings) = crate::timing::last() {
+        let _ = writeln!(text, "\nTimings of the last session: {timings}");
+    }
+
+    Ok(super::output("probe-rs doctor", text))
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::Os;
+
+    use super::{Check, Status};
+
+    #[test]
+    fn port_in_use() {
+        let netstat = "  TCP    0.0.0.0:50000          0.0.0.0:0              LISTENING\n";
+
+        assert!(super::port_in_use(Os::Windows, netstat, 50000));
+        assert!(!super::port_in_use(Os::Windows, netstat, 5000));
+        assert!(super::port_in_use(
+            Os::Linux,
+            "LISTEN 0 128 127.0.0.1:50000 0.0.0.0:*\n",
+            50000
+        ));
+        assert!(!super::port_in_use(Os::Linux, "", 50000));
+    }
+
+    #[test]
+    fn format_checks() {
+        let checks = [
+            Check::new("probe-rs", Status::Pass, "0.24.0 at /usr/bin/probe-rs"),
+            Check::new("Probes", Status::Fail, "No debug probes are connected"),
+        ];
+
+        assert_eq!(
+            super::format_checks(&checks),
+            "- **Pass** probe-rs: 0.24.0 at /usr/bin/probe-rs\n\
+             - **Fail** Probes: No debug probes are connected\n\
+             \n1 of 2 checks failed.\n"
+        );
+    }
+}
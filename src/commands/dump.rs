@@ -0,0 +1,227 @@
+//! `/probe-rs-dump`: read a memory range from the target and show it as a hexdump.
+
+use std::fmt::Write;
+
+use zed_extension_api::{SlashCommandOutput, Worktree};
+
+pub const COMMAND_NAME: &str = "probe-rs-dump";
+
+/// Upper limit for a single dump, the output ends up in the assistant context.
+const MAX_DUMP_LENGTH: u64 = 64 * 1024;
+
+const USAGE: &str = "Usage: /probe-rs-dump <chip> <address> <length> [probe]";
+
+#[derive(Debug, PartialEq)]
+struct DumpRequest {
+    chip: String,
+    address: u64,
+    length: u64,
+    probe: Option<String>,
+}
+
+impl DumpRequest {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let [chip, address, length, rest @ ..] = args else {
+            return Err(USAGE.to_string());
+        };
+
+        let probe = match rest {
+            [] => None,
+            [probe] => Some(probe.clone()),
+            _ => return Err(USAGE.to_string()),
+        };
+
+        let address =
+            parse_number(address).ok_or_else(|| format!("Invalid address '{address}'. {USAGE}"))?;
+        let length =
+            parse_number(length).ok_or_else(|| format!("Invalid length '{length}'. {USAGE}"))?;
+
+        if length == 0 || length > MAX_DUMP_LENGTH {
+            return Err(format!(
+                "Invalid length {length}, expected a value between 1 and {MAX_DUMP_LENGTH}"
+            ));
+        }
+
+        if address.checked_add(length).is_none() {
+            return Err(format!(
+                "Range starting at {address:#x} with length {length} overflows the address space"
+            ));
+        }
+
+        Ok(Self {
+            chip: chip.clone(),
+            address,
+            length,
+            probe,
+        })
+    }
+
+    fn probe_rs_args(&self) -> Vec<String> {
+        let mut args = vec!["read".to_string(), "--chip".to_string(), self.chip.clone()];
+
+        if let Some(probe) = &self.probe {
+            args.extend(["--probe".to_string(), probe.clone()]);
+        }
+
+        args.extend([
+            "b8".to_string(),
+            format!("{:#x}", self.address),
+            self.length.to_string(),
+        ]);
+
+        args
+    }
+}
+
+/// Parse a number given either in decimal or with a `0x` prefix in hex.
+fn parse_number(value: &str) -> Option<u64> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16).ok(),
+        None => value.replace('_', "").parse().ok(),
+    }
+}
+
+/// Parse the bytes printed by `probe-rs read b8`, which are whitespace separated hex values.
+pub fn parse_read_output(output: &str) -> Result<Vec<u8>, String> {
+    output
+        .split_whitespace()
+        .map(|word| {
+            let digits = word.strip_prefix("0x").unwrap_or(word);
+            u8::from_str_radix(digits, 16)
+                .map_err(|_| format!("Unexpected value '{word}' in probe-rs output"))
+        })
+        .collect()
+}
+
+fn hexdump(address: u64, data: &[u8]) -> String {
+    let mut dump = String::new();
+
+    for (index, line) in data.chunks(16).enumerate() {
+        let line_address = address + (index as u64) * 16;
+        let _ = write!(dump, "{line_address:08x}: ");
+
+        for column in 0..16 {
+            match line.get(column) {
+                Some(byte) => {
+                    let _ = write!(dump, "{byte:02x} ");
+                }
+                None => dump.push_str("   "),
+            }
+        }
+
+        dump.push(' ');
+        dump.extend(line.iter().map(|&byte| {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            }
+        }));
+        dump.push('\n');
+    }
+
+    dump
+}
+
+pub fn run(args: &[String], worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let request = DumpRequest::parse(args)?;
+
+    let binary = super::probe_rs_binary(worktree);
+    let stdout = crate::process::run(&binary, &request.probe_rs_args())?;
+    let data = parse_read_output(&stdout)?;
+
+    let label = format!(
+        "{:#x}..{:#x} on {}",
+        request.address,
+        request.address + request.length,
+        request.chip
+    );
+
+    Ok(super::output(
+        label,
+        format!("```\n{}```\n", hexdump(request.address, &data)),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_request() {
+        let request = DumpRequest::parse(&args(&["nRF52840_xxAA", "0x20000000", "256"])).unwrap();
+
+        assert_eq!(
+            request,
+            DumpRequest {
+                chip: "nRF52840_xxAA".to_string(),
+                address: 0x2000_0000,
+                length: 256,
+                probe: None,
+            }
+        );
+
+        assert_eq!(
+            request.probe_rs_args(),
+            args(&["read", "--chip", "nRF52840_xxAA", "b8", "0x20000000", "256"])
+        );
+    }
+
+    #[test]
+    fn parse_request_with_probe() {
+        let request = DumpRequest::parse(&args(&[
+            "STM32F401RETx",
+            "0x0800_0000",
+            "0x10",
+            "0483:374b",
+        ]))
+        .unwrap();
+
+        assert_eq!(request.address, 0x0800_0000);
+        assert_eq!(request.length, 16);
+        assert_eq!(
+            request.probe_rs_args(),
+            args(&[
+                "read",
+                "--chip",
+                "STM32F401RETx",
+                "--probe",
+                "0483:374b",
+                "b8",
+                "0x8000000",
+                "16"
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_request_invalid() {
+        assert!(DumpRequest::parse(&args(&["chip", "0x0"])).is_err());
+        assert!(DumpRequest::parse(&args(&["chip", "0x0", "16", "probe", "extra"])).is_err());
+        assert!(DumpRequest::parse(&args(&["chip", "zzz", "16"])).is_err());
+        assert!(DumpRequest::parse(&args(&["chip", "0x0", "0"])).is_err());
+        assert!(DumpRequest::parse(&args(&["chip", "0x0", "1000000"])).is_err());
+        assert!(DumpRequest::parse(&args(&["chip", "0xffffffffffffffff", "16"])).is_err());
+    }
+
+    #[test]
+    fn hexdump_partial_line() {
+        let output = parse_read_output("48 65 6c 6c 6f 00 ff\n").unwrap();
+        assert_eq!(
+            hexdump(0x2000_0000, &output),
+            "20000000: 48 65 6c 6c 6f 00 ff                             Hello..\n"
+        );
+    }
+
+    #[test]
+    fn parse_read_output_invalid() {
+        assert!(parse_read_output("Error: no probe found").is_err());
+    }
+}
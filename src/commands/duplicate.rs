@@ -0,0 +1,325 @@
+//! `/probe-rs-duplicate`: copy a scenario with some of its options changed.
+//!
+//! Multi-board projects need one scenario per board, which differ only in the chip, the probe
+//! or the build profile. The copy is shown as a scenario for `.zed/debug.json`, since the
+//! extension can't write files of the worktree.
+
+use zed_extension_api::{
+    SlashCommandOutput, Worktree,
+    serde_json::{self, Value},
+};
+
+pub const COMMAND_NAME: &str = "probe-rs-duplicate";
+
+const DEFAULT_PATH: &str = ".zed/debug.json";
+
+const USAGE: &str = "Usage: /probe-rs-duplicate <label> [chip=CHIP] [probe=VID:PID[:SERIAL]] \
+                     [serial=SERIAL] [profile=PROFILE] [label=LABEL] [option.path=VALUE]...";
+
+/// An option to change, as the key and the value.
+type Override<'a> = (&'a str, &'a str);
+
+/// The label of the scenario to copy, followed by the overrides as `key=value`.
+///
+/// Labels may contain spaces, so everything up to the first override is the label.
+fn parse_args(args: &[String]) -> Result<(String, Vec<Override<'_>>), String> {
+    let start = args
+        .iter()
+        .position(|arg| arg.contains('='))
+        .unwrap_or(args.len());
+
+    let label = args[..start].join(" ");
+    if label.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    let overrides = args[start..]
+        .iter()
+        .map(|arg| {
+            arg.split_once('=')
+                .filter(|(key, _)| !key.is_empty())
+                .ok_or_else(|| {
+                    format!("Expected an override like 'chip=nRF52833_xxAA', got '{arg}'")
+                })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok((label, overrides))
+}
+
+/// The directory of a cargo profile in `target/`.
+fn profile_directory(profile: &str) -> &str {
+    match profile {
+        "dev" | "test" => "debug",
+        "bench" => "release",
+        profile => profile,
+    }
+}
+
+/// Build the scenario with another cargo profile, in its `build` task and program paths.
+fn set_profile(scenario: &mut Value, profile: &str) {
+    let profile = if profile == "debug" { "dev" } else { profile };
+    let mut current = "dev".to_string();
+
+    if let Some(args) = scenario
+        .pointer_mut("/build/args")
+        .and_then(Value::as_array_mut)
+    {
+        let mut index = 0;
+        while index < args.len() {
+            match args[index].as_str() {
+                Some("--release") => {
+                    current = "release".to_string();
+                    args.remove(index);
+                }
+                Some("--profile") if index + 1 < args.len() => {
+                    current = args[index + 1].as_str().unwrap_or_default().to_string();
+                    args.drain(index..index + 2);
+                }
+                Some(arg) if arg.starts_with("--profile=") => {
+                    current = arg["--profile=".len()..].to_string();
+                    args.remove(index);
+                }
+                _ => index += 1,
+            }
+        }
+
+        match profile {
+            "dev" => {}
+            "release" => args.push("--release".into()),
+            profile => args.extend(["--profile".into(), profile.into()]),
+        }
+    }
+
+    let from = format!("/{}/", profile_directory(&current));
+    let to = format!("/{}/", profile_directory(profile));
+
+    for core in scenario
+        .get_mut("coreConfigs")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+    {
+        if let Some(program) = core.get("programBinary").and_then(Value::as_str) {
+            core["programBinary"] = program.replacen(&from, &to, 1).into();
+        }
+    }
+}
+
+/// Set an option by its path like `flashingConfig.haltAfterReset`. Values are parsed as JSON,
+/// and anything else is taken as a string.
+fn set_option(scenario: &mut Value, path: &str, value: &str) -> Result<(), String> {
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+
+    let mut target = scenario;
+    let mut keys = path.split('.').peekable();
+
+    while let Some(key) = keys.next() {
+        let object = target
+            .as_object_mut()
+            .ok_or_else(|| format!("Can't set '{path}', '{key}' is not inside an object"))?;
+
+        if keys.peek().is_none() {
+            object.insert(key.to_string(), value);
+            return Ok(());
+        }
+
+        target = object
+            .entry(key)
+            .or_insert_with(|| Value::Object(Default::default()));
+    }
+
+    Ok(())
+}
+
+/// Copy the scenario with the overrides applied.
+fn duplicate(scenario: &Value, overrides: &[Override]) -> Result<Value, String> {
+    let mut copy = scenario.clone();
+    let mut label = None;
+
+    for (key, value) in overrides {
+        match *key {
+            "label" => label = Some(value.to_string()),
+            "chip" => copy["chip"] = value.to_string().into(),
+            "probe" => {
+                copy["probe"] = crate::config::probe_from_selector(value).ok_or_else(|| {
+                    format!("Invalid probe '{value}', expected VID:PID or VID:PID:SERIAL in hex")
+                })?;
+            }
+            "serial" => {
+                let probe = copy
+                    .get_mut("probe")
+                    .and_then(Value::as_object_mut)
+                    .ok_or("'serial' needs a 'probe' in the scenario, use probe=VID:PID:SERIAL")?;
+                probe.insert("serial_number".to_string(), value.to_string().into());
+            }
+            "profile" => set_profile(&mut copy, value),
+            path => set_option(&mut copy, path, value)?,
+        }
+    }
+
+    let original = scenario
+        .get("label")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    copy["label"] = label
+        .unwrap_or_else(|| {
+            let overrides: Vec<String> = overrides
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect();
+
+            format!("{original} ({})", overrides.join(", "))
+        })
+        .into();
+
+    Ok(copy)
+}
+
+/// The probe-rs scenario with the label, preferring an exact match.
+fn find<'a>(scenarios: &'a Value, label: &str) -> Option<&'a Value> {
+    let scenarios: Vec<&Value> = scenarios
+        .as_array()?
+        .iter()
+        .filter(|scenario| {
+            scenario.get("adapter").and_then(Value::as_str) == Some(crate::ADAPTER_NAME)
+        })
+        .collect();
+    let label_of = |scenario: &Value| {
+        scenario
+            .get("label")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    scenarios
+        .iter()
+        .find(|scenario| label_of(scenario) == label)
+        .or_else(|| {
+            scenarios
+                .iter()
+                .find(|scenario| label_of(scenario).eq_ignore_ascii_case(label))
+        })
+        .copied()
+}
+
+pub fn run(args: &[String], worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let worktree = worktree.ok_or("/probe-rs-duplicate needs an open project")?;
+    let (label, overrides) = parse_args(args)?;
+
+    let contents = worktree
+        .read_text_file(DEFAULT_PATH)
+        .map_err(|err| format!("Failed to read '{DEFAULT_PATH}': {err}"))?;
+    let scenarios = crate::jsonc::parse(&contents)
+        .map_err(|err| format!("Failed to parse '{DEFAULT_PATH}': {err}"))?;
+
+    let scenario = find(&scenarios, &label).ok_or_else(|| {
+        format!("There is no probe-rs scenario labeled '{label}' in '{DEFAULT_PATH}'")
+    })?;
+    let copy = duplicate(scenario, &overrides)?;
+
+    let text = serde_json::to_string_pretty(&copy)
+        .map_err(|err| format!("Failed to format the scenario: {err}"))?;
+
+    Ok(super::output(
+        format!("Copy of {label}"),
+        format!("Add this scenario to `{DEFAULT_PATH}`:\n\n```json\n{text}\n```\n"),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_args() {
+        let args = args(&["Debug", "nRF52", "chip=nRF52833_xxAA", "speed=4000"]);
+
+        assert_eq!(
+            super::parse_args(&args).unwrap(),
+            (
+                "Debug nRF52".to_string(),
+                vec![("chip", "nRF52833_xxAA"), ("speed", "4000")]
+            )
+        );
+        assert!(super::parse_args(&self::args(&["chip=nRF52833_xxAA"])).is_err());
+        assert!(super::parse_args(&self::args(&["Debug", "=1"])).is_err());
+    }
+
+    #[test]
+    fn duplicate() {
+        let scenario = json!({
+            "label": "Debug board 1",
+            "adapter": "probe-rs",
+            "chip": "nRF52840_xxAA",
+            "build": { "command": "cargo", "args": ["build", "--bin", "app"] },
+            "probe": { "vendor_id": 0x1366, "product_id": 0x1015, "serial_number": "0001" },
+            "coreConfigs": [{ "programBinary": "target/thumbv7em-none-eabihf/debug/app" }],
+        });
+
+        let copy = super::duplicate(
+            &scenario,
+            &[
+                ("serial", "0002"),
+                ("profile", "release"),
+                ("flashingConfig.haltAfterReset", "true"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            copy,
+            json!({
+                "label": "Debug board 1 (serial=0002, profile=release, flashingConfig.haltAfterReset=true)",
+                "adapter": "probe-rs",
+                "chip": "nRF52840_xxAA",
+                "build": { "command": "cargo", "args": ["build", "--bin", "app", "--release"] },
+                "probe": { "vendor_id": 0x1366, "product_id": 0x1015, "serial_number": "0002" },
+                "coreConfigs": [{ "programBinary": "target/thumbv7em-none-eabihf/release/app" }],
+                "flashingConfig": { "haltAfterReset": true },
+            })
+        );
+
+        // And back to the dev profile, with a label and probe of its own.
+        let copy = super::duplicate(
+            &copy,
+            &[
+                ("profile", "debug"),
+                ("label", "Board 3"),
+                ("probe", "0483:374e"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(copy["label"], "Board 3");
+        assert_eq!(copy["build"]["args"], json!(["build", "--bin", "app"]));
+        assert_eq!(
+            copy["coreConfigs"][0]["programBinary"],
+            "target/thumbv7em-none-eabihf/debug/app"
+        );
+        assert_eq!(
+            copy["probe"],
+            json!({ "vendor_id": 0x0483, "product_id": 0x374e })
+        );
+
+        assert!(super::duplicate(&json!({}), &[("serial", "0002")]).is_err());
+    }
+
+    #[test]
+    fn find() {
+        let scenarios = json!([
+            { "label": "Debug", "adapter": "CodeLLDB" },
+            { "label": "debug", "adapter": "probe-rs" },
+        ]);
+
+        assert_eq!(
+            super::find(&scenarios, "Debug").unwrap()["adapter"],
+            "probe-rs"
+        );
+        assert!(super::find(&scenarios, "Release").is_none());
+    }
+}
@@ -0,0 +1,94 @@
+//! `/probe-rs-effective-config`: show exactly what was passed to probe-rs in the last session.
+//!
+//! The configuration in `.zed/debug.json` is only the start: task variables, settings,
+//! project defaults and fallbacks change it before probe-rs gets it. For configuration
+//! problems, the result is what matters.
+
+use std::fmt::Write;
+
+use zed_extension_api::{
+    SlashCommandOutput, Worktree,
+    serde_json::{self, Value},
+};
+
+pub const COMMAND_NAME: &str = "probe-rs-effective-config";
+
+pub fn run(_args: &[String], worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let worktree = worktree.ok_or("/probe-rs-effective-config needs an open project")?;
+    let root = worktree.root_path();
+
+    let (label, config) =
+        crate::history::last(&root).ok_or("No probe-rs session was started in this project yet")?;
+    let adapter = crate::history::last_adapter(&root).unwrap_or_default();
+
+    Ok(super::output(
+        format!("Effective configuration: {label}"),
+        format_effective(&label, &config, &adapter),
+    ))
+}
+
+fn format_effective(label: &str, config: &Value, adapter: &Value) -> String {
+    let mut text = format!("The last session, '{label}', was started like this.\n\n");
+
+    let string = |key: &str| adapter.get(key).and_then(Value::as_str);
+
+    if let Some(command) = string("command") {
+        let arguments: Vec<&str> = adapter
+            .get("arguments")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+
+        let _ = writeln!(text, "- Command: `{command} {}`", arguments.join(" "));
+    }
+
+    if let Some(server) = string("server") {
+        let _ = writeln!(text, "- Server: {server}");
+    }
+
+    if let Some(cwd) = string("cwd") {
+        let _ = writeln!(text, "- Working directory: {cwd}");
+    }
+
+    if let Some(envs) = adapter.get("envs").and_then(Value::as_object)
+        && !envs.is_empty()
+    {
+        let envs: Vec<String> = envs
+            .iter()
+            .map(|(key, value)| format!("`{key}={}`", value.as_str().unwrap_or_default()))
+            .collect();
+        let _ = writeln!(text, "- Environment: {}", envs.join(", "));
+    }
+
+    let configuration = serde_json::to_string_pretty(config).unwrap_or_default();
+    let _ = write!(
+        text,
+        "\nConfiguration sent to probe-rs:\n\n```json\n{configuration}\n```\n"
+    );
+
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    #[test]
+    fn format_effective() {
+        let adapter = json!({
+            "command": "/usr/bin/probe-rs",
+            "arguments": ["dap-server", "--port", "50000"],
+            "envs": { "RUST_LOG": "probe_rs=debug" },
+            "cwd": "/project",
+            "server": "127.0.0.1:50000"
+        });
+
+        let text = super::format_effective("Debug", &json!({ "chip": "rp2040" }), &adapter);
+
+        assert!(text.contains("- Command: `/usr/bin/probe-rs dap-server --port 50000`\n"));
+        assert!(text.contains("- Environment: `RUST_LOG=probe_rs=debug`\n"));
+        assert!(text.contains("\"chip\": \"rp2040\""));
+    }
+}
@@ -0,0 +1,192 @@
+//! `/probe-rs-flash-all`: flash the same firmware to every connected board.
+//!
+//! For small production runs and classrooms, the boards are flashed one after the other, and
+//! each one is reset afterwards so it runs the firmware. A failure on one board doesn't stop
+//! the others, the output lists the result of each board.
+
+use std::fmt::Write;
+
+use zed_extension_api::{SlashCommandOutput, Worktree};
+
+use crate::enumeration::ConnectedProbe;
+
+pub const COMMAND_NAME: &str = "probe-rs-flash-all";
+
+const USAGE: &str =
+    "Usage: /probe-rs-flash-all <chip> <program> [VID:PID | VID:PID:SERIAL-PREFIX | SERIAL-PREFIX]";
+
+/// Which of the connected probes are flashed.
+#[derive(Debug, Default, PartialEq)]
+struct ProbeFilter {
+    usb_id: Option<(u16, u16)>,
+    serial_prefix: Option<String>,
+}
+
+impl ProbeFilter {
+    /// Parse a filter, which is a USB id in hex, optionally followed by a serial number prefix,
+    /// or just a serial number prefix.
+    fn parse(filter: &str) -> Self {
+        let mut parts = filter.splitn(3, ':');
+        let usb_id = match (parts.next(), parts.next()) {
+            (Some(vendor_id), Some(product_id)) => u16::from_str_radix(vendor_id, 16)
+                .ok()
+                .zip(u16::from_str_radix(product_id, 16).ok()),
+            _ => None,
+        };
+
+        match usb_id {
+            Some(usb_id) => Self {
+                usb_id: Some(usb_id),
+                serial_prefix: parts.next().map(str::to_string),
+            },
+            None => Self {
+                usb_id: None,
+                serial_prefix: Some(filter.to_string()),
+            },
+        }
+    }
+
+    fn matches(&self, probe: &ConnectedProbe) -> bool {
+        self.usb_id
+            .is_none_or(|usb_id| usb_id == (probe.vendor_id, probe.product_id))
+            && self.serial_prefix.as_ref().is_none_or(|prefix| {
+                probe
+                    .serial
+                    .as_ref()
+                    .is_some_and(|serial| serial.starts_with(prefix.as_str()))
+            })
+    }
+}
+
+/// The selector of a single probe, which needs its serial number if there are several.
+fn selector(probe: &ConnectedProbe) -> String {
+    match &probe.serial {
+        Some(serial) => format!("{:04x}:{:04x}:{serial}", probe.vendor_id, probe.product_id),
+        None => format!("{:04x}:{:04x}", probe.vendor_id, probe.product_id),
+    }
+}
+
+fn report(program: &str, results: &[(String, Result<(), String>)]) -> String {
+    let flashed = results.iter().filter(|(_, result)| result.is_ok()).count();
+
+    let mut text = format!(
+        "Flashed '{program}' to {flashed} of {} boards.\n\n",
+        results.len()
+    );
+
+    for (selector, result) in results {
+        let _ = match result {
+            Ok(()) => writeln!(text, "- `{selector}`: flashed"),
+            Err(err) => writeln!(text, "- `{selector}`: **failed**: {err}"),
+        };
+    }
+
+    text
+}
+
+pub fn run(args: &[String], worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let (chip, program, filter) = match args {
+        [chip, program] => (chip, program, ProbeFilter::default()),
+        [chip, program, filter] => (chip, program, ProbeFilter::parse(filter)),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let program_path = super::resolve_path(program, worktree);
+    let binary = super::probe_rs_binary(worktree);
+
+    let probes: Vec<_> = crate::enumeration::try_list(&binary)?
+        .into_iter()
+        .filter(|probe| filter.matches(probe))
+        .collect();
+    if probes.is_empty() {
+        return Err("No connected probe matches the filter".to_string());
+    }
+
+    let results: Vec<_> = probes
+        .iter()
+        .map(|probe| {
+            let selector = selector(probe);
+            let flash = |command: &str| {
+                let mut args = vec![command.to_string(), "--chip".to_string(), chip.clone()];
+                args.extend(["--probe".to_string(), selector.clone()]);
+                if command == "download" {
+                    args.push(program_path.clone());
+                }
+
+                crate::process::run(&binary, &args).map(|_| ())
+            };
+
+            let result = if probe.serial.is_none() && probes.len() > 1 {
+                Err(
+                    "the probe has no serial number, so it can't be told apart from the others"
+                        .to_string(),
+                )
+            } else {
+                flash("download").and_then(|()| flash("reset"))
+            };
+
+            (selector, result)
+        })
+        .collect();
+
+    Ok(super::output(
+        format!("Flashed {program}"),
+        report(&program_path, &results),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::ProbeFilter;
+    use crate::enumeration::ConnectedProbe;
+
+    fn probe(vendor_id: u16, product_id: u16, serial: Option<&str>) -> ConnectedProbe {
+        ConnectedProbe {
+            vendor_id,
+            product_id,
+            serial: serial.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn filter() {
+        let stlink = probe(0x0483, 0x374e, Some("0023003A3133510B"));
+        let jlink = probe(0x1366, 0x0105, Some("000123456789"));
+
+        let filter = ProbeFilter::parse("0483:374e");
+        assert!(filter.matches(&stlink));
+        assert!(!filter.matches(&jlink));
+
+        let filter = ProbeFilter::parse("1366:0105:0001");
+        assert!(filter.matches(&jlink));
+        assert!(!ProbeFilter::parse("1366:0105:0002").matches(&jlink));
+
+        let filter = ProbeFilter::parse("0023");
+        assert_eq!(filter.usb_id, None);
+        assert!(filter.matches(&stlink));
+        assert!(!filter.matches(&probe(0x0483, 0x374e, None)));
+
+        assert!(ProbeFilter::default().matches(&jlink));
+    }
+
+    #[test]
+    fn report() {
+        let results = [
+            (
+                super::selector(&probe(0x1366, 0x0105, Some("000123456789"))),
+                Ok(()),
+            ),
+            (
+                super::selector(&probe(0x0483, 0x3748, None)),
+                Err("Connecting to the chip was unsuccessful.".to_string()),
+            ),
+        ];
+
+        assert_eq!(
+            super::report("/project/target/app", &results),
+            "Flashed '/project/target/app' to 1 of 2 boards.\n\n\
+             - `1366:0105:000123456789`: flashed\n\
+             - `0483:3748`: **failed**: Connecting to the chip was unsuccessful.\n"
+        );
+    }
+}
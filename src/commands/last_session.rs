@@ -0,0 +1,22 @@
+//! `/probe-rs-last-session`: show the configuration of the last session as a scenario.
+
+use zed_extension_api::{SlashCommandOutput, Worktree, serde_json};
+
+pub const COMMAND_NAME: &str = "probe-rs-last-session";
+
+pub fn run(_args: &[String], worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let worktree = worktree.ok_or("/probe-rs-last-session needs an open project")?;
+
+    let (label, config) = crate::history::last(&worktree.root_path())
+        .ok_or("No probe-rs session was started in this project yet")?;
+
+    let scenario = serde_json::to_string_pretty(&crate::history::scenario(&label, &config))
+        .map_err(|err| format!("Failed to format the scenario: {err}"))?;
+
+    Ok(super::output(
+        format!("Last session: {label}"),
+        format!(
+            "The last session in this project was started with this configuration. Add it to `.zed/debug.json` to repeat it:\n\n```json\n{scenario}\n```\n"
+        ),
+    ))
+}
@@ -0,0 +1,119 @@
+//! `/probe-rs-log`: show the warnings and errors from a probe-rs log file.
+//!
+//! The messages of the extension for the last session started in verbose mode are shown too,
+//! since a session which failed to start has no log of probe-rs.
+
+use std::fmt::Write;
+
+use zed_extension_api::{SlashCommandOutput, Worktree};
+
+use crate::log::{self, Level};
+
+pub const COMMAND_NAME: &str = "probe-rs-log";
+
+/// Log file written in verbose mode, relative to the worktree root.
+pub const DEFAULT_LOG_PATH: &str = ".zed/probe-rs.log";
+
+const USAGE: &str = "Usage: /probe-rs-log [--all] [path]";
+
+fn format_entries(contents: &str, min_level: Level) -> String {
+    let entries: Vec<_> = log::parse(contents)
+        .into_iter()
+        .filter(|entry| entry.level >= min_level)
+        .collect();
+
+    if entries.is_empty() {
+        return "No warnings or errors in the log.\n".to_string();
+    }
+
+    let mut text = String::new();
+    for entry in entries {
+        let _ = write!(text, "- **{}**", entry.level.name());
+        if !entry.target.is_empty() {
+            let _ = write!(text, " `{}`", entry.target);
+        }
+        let _ = writeln!(text, ": {}", entry.message);
+    }
+
+    text
+}
+
+pub fn run(args: &[String], worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let mut min_level = Level::Warn;
+    let mut path = None;
+
+    for arg in args {
+        match arg.as_str() {
+            "--all" => min_level = Level::Trace,
+            arg if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    let path = path.unwrap_or(DEFAULT_LOG_PATH);
+
+    let worktree = worktree.ok_or_else(|| "Reading a log file requires a worktree".to_string())?;
+    let contents = worktree
+        .read_text_file(path)
+        .map_err(|err| format!("Failed to read the log file '{path}': {err}"));
+
+    let text = match std::fs::read_to_string(log::EXTENSION_LOG_PATH) {
+        Ok(extension) => format_sections(&extension, contents, min_level),
+        Err(_) => format_entries(&contents?, min_level),
+    };
+
+    Ok(super::output(format!("probe-rs log {path}"), text))
+}
+
+/// The entries of the extension for the last session, followed by the ones of probe-rs, or why
+/// its log couldn't be read.
+fn format_sections(extension: &str, probe_rs: Result<String, String>, min_level: Level) -> String {
+    let probe_rs = probe_rs.map_or_else(
+        |err| format!("{err}\n"),
+        |contents| format_entries(&contents, min_level),
+    );
+
+    format!(
+        "Extension, for the last session started in verbose mode:\n{}\nprobe-rs:\n{probe_rs}",
+        format_entries(extension, min_level)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::log::Level;
+
+    #[test]
+    fn format_entries() {
+        let contents =
+            " INFO probe_rs: Attached\n WARN probe_rs::rtt: No RTT control block found\n";
+
+        assert_eq!(
+            super::format_entries(contents, Level::Warn),
+            "- **warning** `probe_rs::rtt`: No RTT control block found\n"
+        );
+        assert_eq!(
+            super::format_entries(" INFO probe_rs: Attached", Level::Warn),
+            "No warnings or errors in the log.\n"
+        );
+    }
+
+    #[test]
+    fn format_sections() {
+        let extension =
+            r#"{"fields":{"message":"No probe found"},"level":"ERROR","target":"zed_probe_rs"}"#;
+
+        assert_eq!(
+            super::format_sections(
+                extension,
+                Err("Failed to read the log file '.zed/probe-rs.log'".to_string()),
+                Level::Warn
+            ),
+            "Extension, for the last session started in verbose mode:\n\
+             - **error** `zed_probe_rs`: No probe found\n\
+             \n\
+             probe-rs:\n\
+             Failed to read the log file '.zed/probe-rs.log'\n"
+        );
+    }
+}
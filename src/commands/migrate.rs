@@ -0,0 +1,131 @@
+//! `/probe-rs-migrate`: suggest a debug scenario for projects using `probe-run` as runner.
+
+use zed_extension_api::{
+    SlashCommandOutput, Worktree,
+    serde_json::{self, Value, json},
+};
+
+use crate::project::{self, Runner};
+
+pub const COMMAND_NAME: &str = "probe-rs-migrate";
+
+/// Build a debug scenario which runs the program like the cargo runner does.
+fn scenario(runner: &Runner, package: Option<&str>, target: Option<&str>) -> Value {
+    let mut config = json!({
+        "label": "Debug with probe-rs",
+        "adapter": crate::ADAPTER_NAME,
+        "request": "launch",
+        "cwd": "$ZED_WORKTREE_ROOT",
+        "build": { "command": "cargo", "args": ["build"] },
+        "flashingConfig": { "flashingEnabled": true },
+    });
+
+    if let Some(chip) = &runner.chip {
+        config["chip"] = chip.clone().into();
+    }
+
+    if let Some(probe) = runner
+        .probe
+        .as_deref()
+        .and_then(crate::config::probe_from_selector)
+    {
+        config["probe"] = probe;
+    }
+
+    if let Some(speed) = runner.speed {
+        config["speed"] = speed.into();
+    }
+
+    if runner.connect_under_reset {
+        config["connectUnderReset"] = true.into();
+    }
+
+    let target_dir = match target {
+        Some(target) => format!("target/{target}/debug"),
+        None => "target/debug".to_string(),
+    };
+    let program = format!("{target_dir}/{}", package.unwrap_or("<binary>"));
+
+    config["coreConfigs"] = json!([{ "programBinary": program }]);
+
+    config
+}
+
+pub fn run(_args: &[String], worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let worktree = worktree.ok_or("/probe-rs-migrate needs an open project")?;
+
+    let cargo_config = project::CARGO_CONFIG_PATHS
+        .iter()
+        .find_map(|path| worktree.read_text_file(path).ok())
+        .ok_or("The project has no cargo configuration in '.cargo/config.toml'")?;
+
+    let runner = Runner::parse(&cargo_config).ok_or(
+        "The cargo configuration doesn't use probe-run or probe-rs run as runner, nothing to migrate",
+    )?;
+
+    let manifest = worktree.read_text_file("Cargo.toml").unwrap_or_default();
+
+    let scenario = scenario(
+        &runner,
+        project::package_name(&manifest),
+        project::build_target(&cargo_config),
+    );
+    let scenario = serde_json::to_string_pretty(&scenario)
+        .map_err(|err| format!("Failed to format the scenario: {err}"))?;
+
+    let mut text = format!(
+        "The project runs programs using `{}`. Add this scenario to `.zed/debug.json` to debug them:\n\n```json\n{scenario}\n```\n",
+        runner.command
+    );
+
+    if runner.chip.is_some() {
+        text.push_str(
+            "\nThe chip of the runner is also used for scenarios which don't configure one.\n",
+        );
+    }
+
+    Ok(super::output("probe-rs scenario", text))
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    use crate::project::Runner;
+
+    #[test]
+    fn scenario_from_runner() {
+        let runner = Runner {
+            command: "probe-run".to_string(),
+            chip: Some("STM32F401RETx".to_string()),
+            probe: Some("0483:374b".to_string()),
+            speed: Some(1000),
+            connect_under_reset: true,
+        };
+
+        let scenario = super::scenario(&runner, Some("blinky"), Some("thumbv7em-none-eabihf"));
+
+        assert_eq!(scenario["chip"], "STM32F401RETx");
+        assert_eq!(scenario["speed"], 1000);
+        assert_eq!(scenario["connectUnderReset"], true);
+        assert_eq!(
+            scenario["probe"],
+            json!({ "vendor_id": 0x0483, "product_id": 0x374b })
+        );
+        assert_eq!(
+            scenario["coreConfigs"],
+            json!([{ "programBinary": "target/thumbv7em-none-eabihf/debug/blinky" }])
+        );
+    }
+
+    #[test]
+    fn scenario_without_target() {
+        let scenario = super::scenario(&Runner::default(), None, None);
+
+        assert!(scenario.get("chip").is_none());
+        assert_eq!(
+            scenario["coreConfigs"][0]["programBinary"],
+            "target/debug/<binary>"
+        );
+    }
+}
@@ -0,0 +1,50 @@
+//! `/probe-rs-refresh-chips`: list the chips supported by probe-rs again.
+//!
+//! The cached chip list is replaced by the chips of the installed probe-rs, so chips added by
+//! an update are accepted by the validation right away.
+
+use std::fmt::Write;
+
+use zed_extension_api::{SlashCommandOutput, Worktree};
+
+pub const COMMAND_NAME: &str = "probe-rs-refresh-chips";
+
+fn report(binary: &str, chips: &[String], added: &[String]) -> String {
+    let mut text = format!("'{binary}' supports {} chips.\n", chips.len());
+
+    if !added.is_empty() {
+        let _ = writeln!(text, "\nNew since the last refresh:\n");
+        for chip in added {
+            let _ = writeln!(text, "- `{chip}`");
+        }
+    }
+
+    text
+}
+
+pub fn run(_args: &[String], worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let binary = super::probe_rs_binary(worktree);
+    let (chips, added) = crate::chips::refresh(&binary)?;
+
+    Ok(super::output(
+        "probe-rs chips",
+        report(&binary, &chips, &added),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn report() {
+        let chips = ["nRF52840_xxAA".to_string(), "nRF54L15".to_string()];
+
+        assert_eq!(
+            super::report("probe-rs", &chips, &chips[1..]),
+            "'probe-rs' supports 2 chips.\n\nNew since the last refresh:\n\n- `nRF54L15`\n"
+        );
+        assert_eq!(
+            super::report("probe-rs", &chips, &[]),
+            "'probe-rs' supports 2 chips.\n"
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! `/probe-rs-reset`: reset the extension to the state after installing it.
+//!
+//! After upgrades, cached data of an older version can keep the extension in a confusing
+//! state. This removes everything the extension stored in its working directory, and clears
+//! what it keeps in memory, so it behaves like after a fresh installation.
+
+use std::{fmt::Write, fs, io::ErrorKind};
+
+use zed_extension_api::{SlashCommandOutput, Worktree};
+
+pub const COMMAND_NAME: &str = "probe-rs-reset";
+
+/// What is removed, with a description for the report.
+const STORED: &[(&str, &str)] = &[
+    (
+        crate::state::STATE_FILE,
+        "settings, project detection, session history, last flashed programs and update checks",
+    ),
+    (
+        crate::install::CACHE_DIRECTORY,
+        "downloaded probe-rs versions",
+    ),
+    (crate::svd::DOWNLOAD_DIRECTORY, "downloaded SVD files"),
+    (crate::chips::CACHE_FILE, "list of supported chips"),
+    (crate::scratch::DIRECTORY, "files of earlier sessions"),
+];
+
+/// Remove a file or directory, returning whether there was something to remove.
+fn remove(path: &str) -> Result<bool, String> {
+    let result = match fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(err) => Err(err),
+    };
+
+    result.map(|()| true).map_err(|err| err.to_string())
+}
+
+fn report(results: &[(&str, Result<bool, String>)]) -> String {
+    let mut text = String::new();
+
+    for (description, result) in results {
+        let _ = match result {
+            Ok(true) => writeln!(text, "- Removed the {description}"),
+            Ok(false) => writeln!(text, "- No {description} were stored"),
+            Err(err) => writeln!(text, "- **Failed** to remove the {description}: {err}"),
+        };
+    }
+
+    if results.iter().any(|(_, result)| result.is_err()) {
+        text.push_str(
+            "\nFiles can't be removed while they are in use, stop all debug sessions and try again.\n",
+        );
+    }
+
+    text.push_str("\nThe usage statistics were kept.\n");
+
+    text
+}
+
+pub fn run(_args: &[String], _worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let results: Vec<_> = STORED
+        .iter()
+        .map(|(path, description)| (*description, remove(path)))
+        .collect();
+
+    crate::svd::clear_cache();
+    crate::locator::clear_offered();
+
+    Ok(super::output("probe-rs reset", report(&results)))
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn report() {
+        let text = super::report(&[
+            ("downloaded SVD files", Ok(true)),
+            ("downloaded probe-rs versions", Ok(false)),
+        ]);
+        assert_eq!(
+            text,
+            "- Removed the downloaded SVD files\n\
+             - No downloaded probe-rs versions were stored\n\
+             \n\
+             The usage statistics were kept.\n"
+        );
+
+        let text = super::report(&[(
+            "downloaded probe-rs versions",
+            Err("Access is denied. (os error 5)".to_string()),
+        )]);
+        assert!(text.starts_with("- **Failed** to remove the downloaded probe-rs versions"));
+        assert!(text.contains("stop all debug sessions"));
+    }
+}
@@ -0,0 +1,182 @@
+//! `/probe-rs-run`: run test firmware with `probe-rs run` and report its exit code.
+//!
+//! Test firmware reports the result through the semihosting exit call, which `probe-rs run`
+//! turns into its own exit code. Firmware which doesn't exit would block the command forever,
+//! so it's stopped after a timeout.
+
+use std::fmt::Write;
+
+use zed_extension_api::{self as zed, SlashCommandOutput, Worktree};
+
+use crate::process::{self, Output};
+
+pub const COMMAND_NAME: &str = "probe-rs-run";
+
+const USAGE: &str = "Usage: /probe-rs-run <chip> <program> [timeout in seconds]";
+
+const DEFAULT_TIMEOUT_SECONDS: u32 = 60;
+
+/// Lines of firmware output which are included in the result.
+const MAX_OUTPUT_LINES: usize = 100;
+
+#[derive(Debug, PartialEq)]
+struct RunRequest {
+    chip: String,
+    program: String,
+    timeout: u32,
+}
+
+impl RunRequest {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let (chip, program, timeout) = match args {
+            [chip, program] => (chip, program, DEFAULT_TIMEOUT_SECONDS),
+            [chip, program, timeout] => {
+                let timeout = timeout
+                    .parse()
+                    .ok()
+                    .filter(|timeout| *timeout > 0)
+                    .ok_or_else(|| format!("Invalid timeout '{timeout}'. {USAGE}"))?;
+
+                (chip, program, timeout)
+            }
+            _ => return Err(USAGE.to_string()),
+        };
+
+        Ok(Self {
+            chip: chip.clone(),
+            program: program.clone(),
+            timeout,
+        })
+    }
+}
+
+/// Describe the result of the run based on the exit code.
+fn describe_result(output: &Output, timeout: u32, with_timeout: bool) -> String {
+    let result = match output.status {
+        Some(0) => "**Passed**: the firmware exited with code 0.".to_string(),
+        Some(process::TIMEOUT_EXIT_CODE) if with_timeout => {
+            format!("**Timed out**: the firmware didn't exit within {timeout} seconds.")
+        }
+        Some(code) => format!("**Failed**: the firmware exited with code {code}."),
+        None if with_timeout => {
+            format!("**Timed out**: the firmware didn't exit within {timeout} seconds.")
+        }
+        None => "**Failed**: probe-rs was terminated.".to_string(),
+    };
+
+    // probe-rs prints the firmware output, like defmt logs, to stdout, and its own messages to
+    // stderr.
+    let lines: Vec<_> = output
+        .stdout
+        .lines()
+        .chain(output.stderr.lines())
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let skipped = lines.len().saturating_sub(MAX_OUTPUT_LINES);
+
+    let mut text = format!("{result}\n");
+
+    if !lines.is_empty() {
+        text.push_str("\n```\n");
+        if skipped > 0 {
+            let _ = writeln!(text, "... {skipped} lines skipped");
+        }
+        for line in &lines[skipped..] {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text.push_str("```\n");
+    }
+
+    text
+}
+
+pub fn run(args: &[String], worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let request = RunRequest::parse(args)?;
+
+    let program = super::resolve_path(&request.program, worktree);
+    let probe_rs = super::probe_rs_binary(worktree);
+    let run_args = [
+        "run".to_string(),
+        "--chip".to_string(),
+        request.chip.clone(),
+        program.clone(),
+    ];
+
+    let (os, _) = zed::current_platform();
+
+    let (command, args, with_timeout) =
+        match process::with_timeout(os, request.timeout, &probe_rs, &run_args) {
+            Some((command, args)) => (command, args, true),
+            // The firmware has to exit on its own.
+            None => (probe_rs, run_args.to_vec(), false),
+        };
+
+    let output = process::run_with_status(&command, &args)?;
+
+    Ok(super::output(
+        format!("probe-rs run {}", request.program),
+        describe_result(&output, request.timeout, with_timeout),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn output(status: Option<i32>, stdout: &str) -> Output {
+        Output {
+            status,
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+        }
+    }
+
+    #[test]
+    fn parse_request() {
+        assert_eq!(
+            RunRequest::parse(&args(&["rp2040", "target/test"])).unwrap(),
+            RunRequest {
+                chip: "rp2040".to_string(),
+                program: "target/test".to_string(),
+                timeout: DEFAULT_TIMEOUT_SECONDS,
+            }
+        );
+        assert_eq!(
+            RunRequest::parse(&args(&["rp2040", "target/test", "10"]))
+                .unwrap()
+                .timeout,
+            10
+        );
+        assert!(RunRequest::parse(&args(&["rp2040", "target/test", "0"])).is_err());
+        assert!(RunRequest::parse(&args(&["rp2040"])).is_err());
+    }
+
+    #[test]
+    fn describe_result() {
+        let text = super::describe_result(&output(Some(0), "INFO all tests passed\n"), 60, true);
+        assert!(text.starts_with("**Passed**"), "{text}");
+        assert!(text.contains("INFO all tests passed"), "{text}");
+
+        let text = super::describe_result(&output(Some(1), ""), 60, true);
+        assert_eq!(text, "**Failed**: the firmware exited with code 1.\n");
+
+        let text = super::describe_result(&output(Some(124), ""), 60, true);
+        assert!(text.starts_with("**Timed out**"), "{text}");
+
+        let text = super::describe_result(&output(Some(124), ""), 60, false);
+        assert!(text.contains("code 124"), "{text}");
+    }
+
+    #[test]
+    fn describe_result_skips_lines() {
+        let stdout: String = (0..150).map(|line| format!("line {line}\n")).collect();
+
+        let text = super::describe_result(&output(Some(0), &stdout), 60, true);
+        assert!(text.contains("... 50 lines skipped\nline 50\n"), "{text}");
+    }
+}
@@ -0,0 +1,17 @@
+//! `/probe-rs-statistics`: show the local usage statistics.
+
+use zed_extension_api::{SlashCommandOutput, Worktree};
+
+use crate::statistics::Statistics;
+
+pub const COMMAND_NAME: &str = "probe-rs-statistics";
+
+pub fn run(_args: &[String], _worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let text = if crate::settings::Settings::last_loaded().usage_statistics {
+        Statistics::load().summary()
+    } else {
+        "Usage statistics are disabled, enable them with `\"usageStatistics\": true` in `.zed/probe-rs.json`.\n".to_string()
+    };
+
+    Ok(super::output("probe-rs statistics", text))
+}
@@ -0,0 +1,153 @@
+//! `/probe-rs-target-gen`: generate a target description from a CMSIS-Pack.
+//!
+//! This is useful for chips which are missing from the built-in probe-rs target database.
+//! The generated YAML files are not used automatically, they have to be added to a scenario
+//! with the `chipDescriptionPath` option.
+
+use std::fmt::Write;
+
+use zed_extension_api::{self as zed, SlashCommandOutput, Worktree};
+
+pub const COMMAND_NAME: &str = "probe-rs-target-gen";
+
+const USAGE: &str = "Usage: /probe-rs-target-gen <pack> [output directory]";
+
+/// Default location for generated target descriptions, relative to the worktree root.
+const DEFAULT_OUTPUT_DIR: &str = ".zed/probe-rs-targets";
+
+fn parse_args(args: &[String]) -> Result<(&str, &str), String> {
+    match args {
+        [pack] => Ok((pack, DEFAULT_OUTPUT_DIR)),
+        [pack, output_dir] => Ok((pack, output_dir)),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn target_gen_args(pack_path: String, output_path: String) -> Vec<String> {
+    vec!["arm".to_string(), pack_path, output_path]
+}
+
+/// The target descriptions in a listing of the output directory.
+fn yaml_files(listing: &str) -> Vec<&str> {
+    listing
+        .lines()
+        .map(str::trim)
+        .filter(|name| name.ends_with(".yaml"))
+        .collect()
+}
+
+/// The files generated by target-gen, from listings of the output directory before and after
+/// it ran, as paths in the output directory.
+///
+/// target-gen overwrites the description of a family which was generated before, which only
+/// shows as a new file once. If no file is new, all descriptions in the directory are returned.
+fn generated_files(output_dir: &str, before: &str, after: &str) -> Vec<String> {
+    let existing = yaml_files(before);
+    let all = yaml_files(after);
+    let added: Vec<&str> = all
+        .iter()
+        .filter(|name| !existing.contains(name))
+        .copied()
+        .collect();
+
+    let files = if added.is_empty() { all } else { added };
+    files
+        .into_iter()
+        .map(|name| format!("{}/{name}", output_dir.trim_end_matches(['/', '\\'])))
+        .collect()
+}
+
+fn describe_result(output_dir: &str, files: &[String]) -> String {
+    let mut text = format!("Generated target descriptions in `{output_dir}`.\n\n");
+
+    let example = match files.first() {
+        Some(file) => {
+            text.push_str("Generated files:\n\n");
+            for file in files {
+                let _ = writeln!(text, "- `{file}`");
+            }
+            text.push('\n');
+            file.to_string()
+        }
+        None => format!("{output_dir}/<family>.yaml"),
+    };
+
+    let _ = write!(
+        text,
+        "The extension doesn't use generated files on its own. Use one by adding it to the debug \
+         configuration in `.zed/debug.json`:\n\n\
+         ```json\n\"chipDescriptionPath\": \"{example}\"\n```\n"
+    );
+
+    text
+}
+
+pub fn run(args: &[String], worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let (pack, output_dir) = parse_args(args)?;
+
+    let binary = worktree
+        .and_then(|worktree| worktree.which("target-gen"))
+        .unwrap_or_else(|| "target-gen".to_string());
+
+    let (os, _) = zed::current_platform();
+    let output_path = super::resolve_path(output_dir, worktree);
+    let before = crate::process::list_directory(os, &output_path).unwrap_or_default();
+
+    crate::process::run(
+        &binary,
+        &target_gen_args(super::resolve_path(pack, worktree), output_path.clone()),
+    )?;
+
+    let after = crate::process::list_directory(os, &output_path).unwrap_or_default();
+
+    Ok(super::output(
+        format!("Target description for {pack}"),
+        describe_result(output_dir, &generated_files(output_dir, &before, &after)),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_args_default_output() {
+        let args = vec!["Vendor.DFP.1.0.0.pack".to_string()];
+        assert_eq!(
+            parse_args(&args).unwrap(),
+            ("Vendor.DFP.1.0.0.pack", DEFAULT_OUTPUT_DIR)
+        );
+
+        assert!(parse_args(&[]).is_err());
+    }
+
+    #[test]
+    fn generated() {
+        let before = "STM32H5.yaml\nREADME.md\n";
+        let after = "STM32H5.yaml\nSTM32H7.yaml\nREADME.md\n";
+        assert_eq!(
+            generated_files(DEFAULT_OUTPUT_DIR, before, after),
+            [".zed/probe-rs-targets/STM32H7.yaml"]
+        );
+
+        // A family generated again is overwritten.
+        assert_eq!(
+            generated_files("targets/", before, before),
+            ["targets/STM32H5.yaml"]
+        );
+    }
+
+    #[test]
+    fn describe_generated_files() {
+        let files = generated_files(DEFAULT_OUTPUT_DIR, "", "STM32H5.yaml\n");
+
+        let text = describe_result(DEFAULT_OUTPUT_DIR, &files);
+        assert!(text.contains("\"chipDescriptionPath\": \".zed/probe-rs-targets/STM32H5.yaml\""));
+    }
+
+    #[test]
+    fn describe_without_generated_files() {
+        let text = describe_result(DEFAULT_OUTPUT_DIR, &[]);
+        assert!(text.contains("\"chipDescriptionPath\": \".zed/probe-rs-targets/<family>.yaml\""));
+    }
+}
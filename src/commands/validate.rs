@@ -0,0 +1,232 @@
+//! `/probe-rs-validate`: check the probe-rs scenarios of a debug configuration file.
+//!
+//! With `--json`, the findings are returned as JSON for use in automated checks.
+
+use std::fmt::Write;
+
+use zed_extension_api::{
+    SlashCommandOutput, Worktree,
+    serde_json::{self, Value, json},
+};
+
+use crate::{
+    settings::Settings,
+    targets::ResetConnection,
+    validate::{self, Finding},
+};
+
+pub const COMMAND_NAME: &str = "probe-rs-validate";
+
+const DEFAULT_PATH: &str = ".zed/debug.json";
+
+const USAGE: &str = "Usage: /probe-rs-validate [--json] [path]";
+
+#[derive(Debug, PartialEq)]
+struct Options<'a> {
+    json: bool,
+    path: &'a str,
+}
+
+fn parse_args(args: &[String]) -> Result<Options<'_>, String> {
+    let mut options = Options {
+        json: false,
+        path: DEFAULT_PATH,
+    };
+    let mut path = None;
+
+    for arg in args {
+        match arg.as_str() {
+            "--json" => options.json = true,
+            flag if flag.starts_with("--") => {
+                return Err(format!("Unknown flag '{flag}'. {USAGE}"));
+            }
+            arg if path.is_none() => path = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    options.path = path.unwrap_or(options.path);
+
+    Ok(options)
+}
+
+/// Findings for one scenario of the configuration file.
+struct ScenarioReport {
+    index: usize,
+    label: String,
+    findings: Vec<Finding>,
+}
+
+/// Validate the probe-rs scenarios, with the `connectUnderReset` flags of the settings.
+fn validate_scenarios(
+    scenarios: &Value,
+    flags: &[(String, ResetConnection)],
+) -> Result<Vec<ScenarioReport>, String> {
+    let scenarios = scenarios
+        .as_array()
+        .ok_or_else(|| "Expected a list of debug scenarios".to_string())?;
+
+    Ok(scenarios
+        .iter()
+        .enumerate()
+        .filter(|(_, scenario)| {
+            scenario.get("adapter").and_then(Value::as_str) == Some(crate::ADAPTER_NAME)
+        })
+        .map(|(index, scenario)| {
+            let mut scenario = scenario.clone();
+            let invalid_number = validate::normalize_numbers(&mut scenario);
+
+            ScenarioReport {
+                index,
+                label: scenario
+                    .get("label")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+                findings: invalid_number
+                    .into_iter()
+                    .chain(validate::validate(&scenario))
+                    .chain(validate::check_connect_under_reset(&scenario, flags))
+                    .collect(),
+            }
+        })
+        .collect())
+}
+
+fn format_json(path: &str, reports: &[ScenarioReport]) -> String {
+    let scenarios: Vec<_> = reports
+        .iter()
+        .map(|report| {
+            json!({
+                "index": report.index,
+                "label": report.label,
+                "findings": report.findings.iter().map(Finding::to_json).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    let output = json!({
+        "file": path,
+        "valid": reports.iter().all(|report| report.findings.is_empty()),
+        "scenarios": scenarios,
+    });
+
+    serde_json::to_string_pretty(&output).unwrap_or_default()
+}
+
+fn format_text(path: &str, reports: &[ScenarioReport]) -> String {
+    let mut text = String::new();
+
+    if reports.is_empty() {
+        let _ = writeln!(text, "No probe-rs scenarios found in `{path}`.");
+    }
+
+    for report in reports {
+        let _ = writeln!(text, "Scenario '{}':", report.label);
+
+        if report.findings.is_empty() {
+            text.push_str("- No problems found\n");
+        }
+
+        for finding in &report.findings {
+            let _ = write!(text, "- {finding}");
+            if let Some(suggestion) = &finding.suggestion {
+                let _ = write!(text, " ({suggestion})");
+            }
+            text.push('\n');
+        }
+    }
+
+    text
+}
+
+pub fn run(args: &[String], worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let options = parse_args(args)?;
+
+    let worktree =
+        worktree.ok_or_else(|| "Validating a configuration requires a worktree".to_string())?;
+
+    let contents = worktree
+        .read_text_file(options.path)
+        .map_err(|err| format!("Failed to read '{}': {err}", options.path))?;
+    let scenarios = crate::jsonc::parse(&contents)
+        .map_err(|err| format!("Failed to parse '{}': {err}", options.path))?;
+
+    let settings = Settings::load(worktree);
+    let reports = validate_scenarios(&scenarios, &settings.connect_under_reset)?;
+
+    let text = if options.json {
+        format!("```json\n{}\n```\n", format_json(options.path, &reports))
+    } else {
+        format_text(options.path, &reports)
+    };
+
+    Ok(super::output(
+        format!("Validation of {}", options.path),
+        text,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn parse_args() {
+        let args = vec!["--json".to_string(), "ci/debug.json".to_string()];
+        assert_eq!(
+            super::parse_args(&args).unwrap(),
+            Options {
+                json: true,
+                path: "ci/debug.json"
+            }
+        );
+
+        assert_eq!(
+            super::parse_args(&[]).unwrap(),
+            Options {
+                json: false,
+                path: DEFAULT_PATH
+            }
+        );
+
+        assert!(super::parse_args(&["--yaml".to_string()]).is_err());
+    }
+
+    #[test]
+    fn json_output() {
+        let scenarios = json!([
+            { "label": "ok", "adapter": "probe-rs", "chip": "nRF52840_xxAA" },
+            { "label": "other adapter", "adapter": "CodeLLDB", "speed": 0 },
+            { "label": "bad", "adapter": "probe-rs", "speed": 0 },
+        ]);
+
+        let reports = validate_scenarios(&scenarios, &[]).unwrap();
+        let output: Value = serde_json::from_str(&format_json(DEFAULT_PATH, &reports)).unwrap();
+
+        assert_eq!(output["valid"], false);
+        assert_eq!(output["scenarios"].as_array().unwrap().len(), 2);
+        assert_eq!(output["scenarios"][1]["index"], 2);
+        assert_eq!(output["scenarios"][1]["findings"][0]["code"], "speed-zero");
+        assert_eq!(
+            output["scenarios"][1]["findings"][0]["suggestion"],
+            "Remove the \"speed\" field"
+        );
+    }
+
+    #[test]
+    fn text_output() {
+        let reports = validate_scenarios(
+            &json!([
+                { "label": "bad", "adapter": "probe-rs", "speed": 0 },
+            ]),
+            &[],
+        )
+        .unwrap();
+
+        let text = format_text(DEFAULT_PATH, &reports);
+        assert!(text.starts_with("Scenario 'bad':\n- Warning (speed-zero)"));
+    }
+}
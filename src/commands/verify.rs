@@ -0,0 +1,104 @@
+//! `/probe-rs-verify`: compare the flash contents of the target against a built ELF.
+
+use zed_extension_api::{SlashCommandOutput, Worktree};
+
+pub const COMMAND_NAME: &str = "probe-rs-verify";
+
+const USAGE: &str = "Usage: /probe-rs-verify <chip> <program> [probe]";
+
+#[derive(Debug, PartialEq)]
+struct VerifyRequest {
+    chip: String,
+    program: String,
+    probe: Option<String>,
+}
+
+impl VerifyRequest {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let (chip, program, probe) = match args {
+            [chip, program] => (chip, program, None),
+            [chip, program, probe] => (chip, program, Some(probe.clone())),
+            _ => return Err(USAGE.to_string()),
+        };
+
+        Ok(Self {
+            chip: chip.clone(),
+            program: program.clone(),
+            probe,
+        })
+    }
+
+    fn probe_rs_args(&self, program_path: String) -> Vec<String> {
+        let mut args = vec![
+            "verify".to_string(),
+            "--chip".to_string(),
+            self.chip.clone(),
+        ];
+
+        if let Some(probe) = &self.probe {
+            args.extend(["--probe".to_string(), probe.clone()]);
+        }
+
+        args.push(program_path);
+
+        args
+    }
+}
+
+pub fn run(args: &[String], worktree: Option<&Worktree>) -> Result<SlashCommandOutput, String> {
+    let request = VerifyRequest::parse(args)?;
+
+    let program_path = super::resolve_path(&request.program, worktree);
+
+    let binary = super::probe_rs_binary(worktree);
+    crate::process::run(&binary, &request.probe_rs_args(program_path.clone()))
+        .map_err(|err| format!("Verification of '{program_path}' failed: {err}"))?;
+
+    Ok(super::output(
+        format!("Verified {}", request.program),
+        format!(
+            "Flash contents of the {} target match '{program_path}'.\n",
+            request.chip
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_request() {
+        let request = VerifyRequest::parse(&args(&["nRF52840_xxAA", "target/app"])).unwrap();
+
+        assert_eq!(
+            request.probe_rs_args("/project/target/app".to_string()),
+            args(&["verify", "--chip", "nRF52840_xxAA", "/project/target/app"])
+        );
+
+        let request =
+            VerifyRequest::parse(&args(&["nRF52840_xxAA", "target/app", "1366:1015"])).unwrap();
+
+        assert_eq!(
+            request.probe_rs_args("target/app".to_string()),
+            args(&[
+                "verify",
+                "--chip",
+                "nRF52840_xxAA",
+                "--probe",
+                "1366:1015",
+                "target/app"
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_request_invalid() {
+        assert!(VerifyRequest::parse(&args(&["nRF52840_xxAA"])).is_err());
+        assert!(VerifyRequest::parse(&args(&["a", "b", "c", "d"])).is_err());
+    }
+}
@@ -0,0 +1,1121 @@
+//! Helpers for reading the probe-rs debug configuration.
+//!
+//! The configuration is passed along to probe-rs mostly unchanged, so it is kept as a
+//! [`serde_json::Value`] and only the fields the extension needs are read from it.
+
+use zed_extension_api::serde_json::{self, Value};
+
+/// Parse the JSON configuration of a scenario.
+///
+/// An empty configuration is replaced by a minimal launch configuration for the first core, so
+/// that bare scenarios work with the chip coming from the environment or the settings.
+pub fn parse(config: &str, label: &str) -> Result<Value, String> {
+    if config.trim().is_empty() {
+        log!("Scenario '{label}' has an empty configuration, using defaults");
+
+        return Ok(serde_json::json!({
+            "request": "launch",
+            "coreConfigs": [{ "coreIndex": 0 }],
+        }));
+    }
+
+    serde_json::from_str(config).map_err(|err| format!("Failed to parse JSON config: {err}"))
+}
+
+/// Remove quotes around the paths of the configuration, see [`crate::paths::unquote`].
+///
+/// Returns the fields which were changed.
+pub fn normalize_paths(config: &mut Value) -> Vec<String> {
+    let mut changed = Vec::new();
+
+    let mut normalize = |object: &mut Value, path: &str, field: &str| {
+        let Some(Value::String(value)) = object.get_mut(field) else {
+            return;
+        };
+
+        let unquoted = crate::paths::unquote(value);
+        if unquoted != value {
+            *value = unquoted.to_string();
+            changed.push(format!("{path}{field}"));
+        }
+    };
+
+    for field in [
+        "adapterPath",
+        "cwd",
+        "chipDescriptionPath",
+        "flashAlgorithm",
+        "logFile",
+    ] {
+        normalize(config, "", field);
+    }
+
+    if let Some(cores) = config.get_mut("coreConfigs").and_then(Value::as_array_mut) {
+        for (index, core) in cores.iter_mut().enumerate() {
+            for field in ["programBinary", "svdFile"] {
+                normalize(core, &format!("coreConfigs[{index}]."), field);
+            }
+        }
+    }
+
+    if let Some(images) = config
+        .get_mut("additionalImages")
+        .and_then(Value::as_array_mut)
+    {
+        for (index, image) in images.iter_mut().enumerate() {
+            normalize(image, &format!("additionalImages[{index}]."), "path");
+        }
+    }
+
+    changed
+}
+
+/// Parse a number given as a string, in decimal or in hex with a `0x` prefix, like
+/// `"0x0800_0000"`. Underscores can be used to group digits.
+pub fn parse_number(number: &str) -> Option<u64> {
+    let number = number.trim().replace('_', "");
+
+    match number
+        .strip_prefix("0x")
+        .or_else(|| number.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => number.parse().ok(),
+    }
+}
+
+/// A numeric field of the configuration which is not a number, see [`normalize_numbers`].
+#[derive(Debug, PartialEq)]
+pub struct InvalidNumber {
+    pub path: String,
+    pub value: Value,
+}
+
+impl std::fmt::Display for InvalidNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "'{}' must be a positive whole number in decimal or hex, like 134217728 or \
+             \"0x08000000\", got {}",
+            self.path, self.value
+        )
+    }
+}
+
+/// Turn numbers given as strings into integers, which is what probe-rs expects.
+///
+/// Datasheets give addresses in hex, which JSON doesn't support, so addresses, sizes, USB ids
+/// and the speed can be given as strings like `"0x08000000"` as well.
+pub fn normalize_numbers(config: &mut Value) -> Result<(), InvalidNumber> {
+    let normalize = |object: &mut Value, path: &str, field: &str| {
+        let Some(value) = object.get_mut(field) else {
+            return Ok(());
+        };
+
+        let number = match &*value {
+            Value::String(number) => parse_number(number),
+            Value::Null => return Ok(()),
+            number => number.as_u64(),
+        };
+
+        match number {
+            Some(number) => {
+                *value = number.into();
+                Ok(())
+            }
+            None => Err(InvalidNumber {
+                path: format!("{path}{field}"),
+                value: value.clone(),
+            }),
+        }
+    };
+
+    normalize(config, "", "speed")?;
+
+    if let Some(probe) = config.get_mut("probe") {
+        for field in ["vendor_id", "product_id"] {
+            normalize(probe, "probe.", field)?;
+        }
+    }
+
+    if let Some(bin_options) = config.pointer_mut("/flashingConfig/formatOptions/bin_options") {
+        for field in ["base_address", "skip"] {
+            normalize(
+                bin_options,
+                "flashingConfig.formatOptions.bin_options.",
+                field,
+            )?;
+        }
+    }
+
+    if let Some(images) = config
+        .get_mut("additionalImages")
+        .and_then(Value::as_array_mut)
+    {
+        for (index, image) in images.iter_mut().enumerate() {
+            for field in ["baseAddress", "skip"] {
+                normalize(image, &format!("additionalImages[{index}]."), field)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Environment variable used as fallback for the `chip` field.
+const CHIP_ENV_VAR: &str = "PROBE_RS_CHIP";
+
+/// Use a fallback chip if the config doesn't specify one.
+///
+/// The `PROBE_RS_CHIP` environment variable is used first, then the default chip, which comes
+/// with where it was taken from, like the settings. An explicitly configured chip always takes
+/// precedence over both.
+pub fn apply_chip_fallback(
+    config: &mut Value,
+    env: &[(String, String)],
+    default_chip: Option<(&str, &str)>,
+) {
+    if config.get("chip").is_some_and(|chip| !chip.is_null()) {
+        return;
+    }
+
+    let env_chip = env
+        .iter()
+        .find(|(key, value)| key == CHIP_ENV_VAR && !value.is_empty())
+        .map(|(_, chip)| chip.as_str());
+
+    let chip = match (env_chip, default_chip) {
+        (Some(chip), _) => {
+            log!("Using chip '{chip}' from the {CHIP_ENV_VAR} environment variable");
+            chip
+        }
+        (None, Some((chip, source))) => {
+            log!("Using chip '{chip}' from {source}");
+            chip
+        }
+        (None, None) => return,
+    };
+
+    if let Some(config) = config.as_object_mut() {
+        config.insert("chip".to_string(), chip.into());
+    }
+}
+
+/// Flashing options set by `fastFlashing`, for quick iterations during development.
+///
+/// Erasing only the needed sectors and skipping verification are safe, but don't catch
+/// failures which a normal flash would.
+const FAST_FLASHING_OPTIONS: &[(&str, bool)] = &[
+    ("verifyBeforeFlashing", false),
+    ("verifyAfterFlashing", false),
+    ("fullChipErase", false),
+    ("restoreUnwrittenBytes", false),
+];
+
+/// Apply the `fastFlashing` toggle to the flashing configuration.
+///
+/// Options which are set explicitly in `flashingConfig` are kept.
+pub fn apply_fast_flashing(config: &mut Value) {
+    if !config
+        .get("fastFlashing")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+    {
+        return;
+    }
+
+    let Some(config) = config.as_object_mut() else {
+        return;
+    };
+
+    let flashing_config = config
+        .entry("flashingConfig")
+        .or_insert_with(|| Value::Object(Default::default()));
+
+    if let Some(flashing_config) = flashing_config.as_object_mut() {
+        for (option, value) in FAST_FLASHING_OPTIONS {
+            flashing_config
+                .entry(option.to_string())
+                .or_insert((*value).into());
+        }
+    }
+}
+
+/// The `haltAfterReset` values of the cores, for cores which set it.
+pub fn core_halt_after_reset(config: &Value) -> Vec<(usize, bool)> {
+    config
+        .get("coreConfigs")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .filter_map(|(index, core)| {
+            let halt = core.get("haltAfterReset")?.as_bool()?;
+            Some((index, halt))
+        })
+        .collect()
+}
+
+/// Translate `haltAfterReset` of the cores into the `flashingConfig` option of probe-rs.
+///
+/// probe-rs can only halt all cores or none after reset, so halting only some of the cores is
+/// an error rather than halting the others as well. An explicit `flashingConfig.haltAfterReset`
+/// takes precedence.
+pub fn apply_core_halt_after_reset(config: &mut Value) -> Result<(), String> {
+    let cores = core_halt_after_reset(config);
+
+    if cores.is_empty() || config.pointer("/flashingConfig/haltAfterReset").is_some() {
+        return Ok(());
+    }
+
+    let halted = cores.iter().filter(|(_, halt)| *halt).count();
+    let cores_count = config
+        .get("coreConfigs")
+        .and_then(Value::as_array)
+        .map_or(0, Vec::len);
+
+    if halted > 0 && halted < cores_count {
+        return Err(
+            "'haltAfterReset' is only set for some of the 'coreConfigs', but probe-rs can only \
+             halt all cores or none after a reset. Set it to the same value for all cores"
+                .to_string(),
+        );
+    }
+
+    let Some(config) = config.as_object_mut() else {
+        return Ok(());
+    };
+
+    let flashing_config = config
+        .entry("flashingConfig")
+        .or_insert_with(|| Value::Object(Default::default()));
+
+    if let Some(flashing_config) = flashing_config.as_object_mut() {
+        flashing_config.insert("haltAfterReset".to_string(), (halted > 0).into());
+    }
+
+    Ok(())
+}
+
+/// Whether the scenario sets `observeOnly`, to attach without resetting or halting the target.
+pub fn is_observe_only(config: &Value) -> bool {
+    config
+        .get("observeOnly")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// Whether the scenario sets `flashOnlyChanged`, to skip flashing programs which didn't change
+/// since they were last flashed.
+pub fn is_flash_only_changed(config: &Value) -> bool {
+    config
+        .get("flashOnlyChanged")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// Turn off all options which reset or halt the target, for `observeOnly`.
+///
+/// Options which were explicitly enabled are overridden, since the point of `observeOnly` is
+/// that it can be relied on. Returns the names of the overridden options.
+pub fn apply_observe_only(config: &mut Value) -> Vec<String> {
+    let mut overridden = Vec::new();
+
+    let mut disable = |object: &mut Value, path: &str, option: &str| {
+        let Some(object) = object.as_object_mut() else {
+            return;
+        };
+
+        if object.insert(option.to_string(), false.into()) == Some(true.into()) {
+            overridden.push(format!("{path}{option}"));
+        }
+    };
+
+    disable(config, "", "connectUnderReset");
+
+    if let Some(object) = config.as_object_mut() {
+        let flashing_config = object
+            .entry("flashingConfig")
+            .or_insert_with(|| Value::Object(Default::default()));
+
+        disable(flashing_config, "flashingConfig.", "flashingEnabled");
+        disable(flashing_config, "flashingConfig.", "haltAfterReset");
+    }
+
+    if let Some(cores) = config.get_mut("coreConfigs").and_then(Value::as_array_mut) {
+        for (index, core) in cores.iter_mut().enumerate() {
+            let path = format!("coreConfigs[{index}].");
+
+            for option in ["haltAfterReset", "catchReset", "catchHardfault"] {
+                disable(core, &path, option);
+            }
+        }
+    }
+
+    overridden
+}
+
+/// Merge `extraConfig` into the configuration, for options of newer probe-rs versions which the
+/// extension doesn't know about.
+///
+/// Objects are merged recursively, anything else replaces the value of the configuration. This
+/// is done after all checks, so the options aren't validated. Returns the paths of the options.
+pub fn apply_extra_config(config: &mut Value) -> Result<Vec<String>, String> {
+    fn merge(target: &mut Value, extra: Value, path: &str, merged: &mut Vec<String>) {
+        match (target, extra) {
+            (Value::Object(target), Value::Object(extra)) => {
+                for (key, value) in extra {
+                    let path = if path.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{path}.{key}")
+                    };
+
+                    match target.get_mut(&key) {
+                        Some(existing) if existing.is_object() && value.is_object() => {
+                            merge(existing, value, &path, merged);
+                        }
+                        _ => {
+                            target.insert(key, value);
+                            merged.push(path);
+                        }
+                    }
+                }
+            }
+            (target, extra) => {
+                *target = extra;
+                merged.push(path.to_string());
+            }
+        }
+    }
+
+    let Some(object) = config.as_object_mut() else {
+        return Ok(Vec::new());
+    };
+
+    let extra = match object.remove("extraConfig") {
+        None | Some(Value::Null) => return Ok(Vec::new()),
+        Some(extra @ Value::Object(_)) => extra,
+        Some(_) => return Err("'extraConfig' must be an object".to_string()),
+    };
+
+    let mut merged = Vec::new();
+    merge(config, extra, "", &mut merged);
+
+    Ok(merged)
+}
+
+/// The log file for probe-rs, relative to the worktree root.
+///
+/// `logFile` is either a path, or `true` to use a file named after the scenario in `.zed`.
+/// Without it, verbose mode logs to the default log file.
+pub fn log_file(config: &Value, label: &str, verbose: bool) -> Option<String> {
+    match config.get("logFile") {
+        Some(Value::String(path)) => Some(path.clone()),
+        Some(Value::Bool(true)) => Some(format!(".zed/probe-rs-{}.log", file_name(label))),
+        _ if verbose => Some(crate::commands::log::DEFAULT_LOG_PATH.to_string()),
+        _ => None,
+    }
+}
+
+/// Turn a scenario label or chip name into something usable as a file name.
+pub fn file_name(label: &str) -> String {
+    let name: String = label
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+
+    let name = name.trim_matches('-');
+
+    if name.is_empty() {
+        "session".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Additional arguments for the dap-server from the `serverArgs` field of the configuration.
+pub fn server_args(config: &Value) -> Result<Vec<String>, String> {
+    let Some(args) = config.get("serverArgs").filter(|args| !args.is_null()) else {
+        return Ok(Vec::new());
+    };
+
+    args.as_array()
+        .and_then(|args| {
+            args.iter()
+                .map(|arg| arg.as_str().map(str::to_string))
+                .collect()
+        })
+        .ok_or_else(|| "'serverArgs' must be a list of strings".to_string())
+}
+
+/// The command the debug adapter is started with, from `adapterWrapper` of the scenario or else
+/// the settings.
+///
+/// An empty list in the scenario starts the debug adapter directly, regardless of the settings.
+pub fn adapter_wrapper(config: &Value, setting: &[String]) -> Result<Vec<String>, String> {
+    let Some(wrapper) = config
+        .get("adapterWrapper")
+        .filter(|wrapper| !wrapper.is_null())
+    else {
+        return Ok(setting.to_vec());
+    };
+
+    wrapper
+        .as_array()
+        .and_then(|wrapper| {
+            wrapper
+                .iter()
+                .map(|arg| arg.as_str().map(str::to_string))
+                .collect()
+        })
+        .ok_or_else(|| {
+            "'adapterWrapper' must be a list of the program and its arguments, like [\"sudo\"]"
+                .to_string()
+        })
+}
+
+/// Environment variables from the `env` field of the configuration.
+pub fn env(config: &Value) -> Result<Vec<(String, String)>, String> {
+    let Some(env) = config.get("env").filter(|env| !env.is_null()) else {
+        return Ok(Vec::new());
+    };
+
+    let env = env
+        .as_object()
+        .ok_or_else(|| "'env' must be an object mapping variable names to values".to_string())?;
+
+    env.iter()
+        .map(|(key, value)| match value {
+            Value::String(value) => Ok((key.clone(), value.clone())),
+            Value::Number(_) | Value::Bool(_) => Ok((key.clone(), value.to_string())),
+            _ => Err(format!(
+                "Invalid value for environment variable '{key}' in 'env'"
+            )),
+        })
+        .collect()
+}
+
+/// The environment of the debug adapter: the environment of the worktree shell, with the
+/// variables of the scenario taking precedence.
+pub fn merge_env(
+    shell_env: Vec<(String, String)>,
+    envs: Vec<(String, String)>,
+) -> Vec<(String, String)> {
+    let mut merged: Vec<_> = shell_env
+        .into_iter()
+        .filter(|(key, _)| !envs.iter().any(|(name, _)| name == key))
+        .collect();
+    merged.extend(envs);
+
+    merged
+}
+
+/// The working directory of the session: the `cwd` of the configuration, or the worktree root.
+///
+/// A relative `cwd` is resolved against the worktree root.
+pub fn cwd(config: &Value, worktree_root: &str) -> String {
+    match config.get("cwd").and_then(Value::as_str) {
+        Some(cwd) => {
+            let cwd = cwd.replace("$ZED_WORKTREE_ROOT", worktree_root);
+            crate::paths::join(Some(worktree_root), &cwd)
+        }
+        None => worktree_root.to_string(),
+    }
+}
+
+/// The probe-rs binary configured for the project, from `adapterPath` of the scenario or else
+/// the settings, together with where it was configured.
+///
+/// A relative path is resolved against the worktree root, so a probe-rs built in a checkout
+/// next to the project can be given as `../probe-rs/target/release/probe-rs`.
+pub fn adapter_path(
+    config: &Value,
+    setting: Option<&str>,
+    worktree_root: &str,
+) -> Option<(String, String)> {
+    let (path, source) = match config.get("adapterPath").and_then(Value::as_str) {
+        Some(path) => (path, "'adapterPath' of the scenario".to_string()),
+        None => (
+            crate::paths::unquote(setting?),
+            format!("'adapterPath' in '{}'", crate::settings::SETTINGS_PATH),
+        ),
+    };
+
+    Some((crate::paths::join(Some(worktree_root), path), source))
+}
+
+/// Format the `probe` selector of the configuration the way the probe-rs CLI expects it,
+/// i.e. `VID:PID` or `VID:PID:SERIAL`.
+pub fn probe_selector(config: &Value) -> Result<Option<String>, String> {
+    let Some(probe) = config.get("probe").filter(|probe| !probe.is_null()) else {
+        return Ok(None);
+    };
+
+    let id = |field: &str| {
+        probe
+            .get(field)
+            .and_then(Value::as_u64)
+            .and_then(|id| u16::try_from(id).ok())
+            .ok_or_else(|| format!("Invalid probe selector, '{field}' must be a 16-bit number"))
+    };
+
+    let mut selector = format!("{:04x}:{:04x}", id("vendor_id")?, id("product_id")?);
+
+    if let Some(serial) = probe.get("serial_number").and_then(Value::as_str) {
+        selector.push(':');
+        selector.push_str(serial);
+    }
+
+    Ok(Some(selector))
+}
+
+/// The `probe` field for a probe selector in the `vvvv:pppp[:serial]` format of the CLI.
+pub fn probe_from_selector(selector: &str) -> Option<Value> {
+    let mut parts = selector.splitn(3, ':');
+
+    let mut id = || u16::from_str_radix(parts.next()?, 16).ok();
+    let vendor_id = id()?;
+    let product_id = id()?;
+
+    let mut probe = serde_json::json!({ "vendor_id": vendor_id, "product_id": product_id });
+
+    if let Some(serial) = parts.next().filter(|serial| !serial.is_empty()) {
+        probe["serial_number"] = serial.into();
+    }
+
+    Some(probe)
+}
+
+/// The configuration for attaching to the target connected to a specific probe.
+///
+/// Attaching in Zed is meant for processes on the host, and only tells the process id. With a
+/// probe selected in the settings, there is enough to attach to a target instead. Without a chip,
+/// the usual fallbacks apply when the session starts.
+pub fn attach_config(selector: Option<&str>, chip: Option<&str>) -> Result<Value, String> {
+    let selector = selector.ok_or_else(|| {
+        "Attaching needs a probe, set \"probe\" to its selector (VID:PID:SERIAL) in `.zed/probe-rs.json`".to_string()
+    })?;
+
+    let probe = probe_from_selector(selector).ok_or_else(|| {
+        format!("Invalid probe selector '{selector}' in the settings, expected VID:PID or VID:PID:SERIAL in hex")
+    })?;
+
+    let mut config = serde_json::json!({
+        "request": "attach",
+        "probe": probe,
+        "coreConfigs": [{ "coreIndex": 0 }],
+    });
+
+    if let Some(chip) = chip {
+        config["chip"] = chip.into();
+    }
+
+    Ok(config)
+}
+
+/// Arguments for probe-rs CLI commands which connect to the same target as the debug session.
+pub fn session_args(config: &Value) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+
+    let chip = config
+        .get("chip")
+        .and_then(Value::as_str)
+        .ok_or_else(|| "The 'chip' field is required to connect to the target".to_string())?;
+    args.extend(["--chip".to_string(), chip.to_string()]);
+
+    if let Some(path) = config.get("chipDescriptionPath").and_then(Value::as_str) {
+        args.extend(["--chip-description-path".to_string(), path.to_string()]);
+    }
+
+    if let Some(selector) = probe_selector(config)? {
+        args.extend(["--probe".to_string(), selector]);
+    }
+
+    if let Some(protocol) = config.get("wireProtocol").and_then(Value::as_str) {
+        args.extend(["--protocol".to_string(), protocol.to_lowercase()]);
+    }
+
+    if let Some(speed) = config.get("speed").and_then(Value::as_u64) {
+        args.extend(["--speed".to_string(), speed.to_string()]);
+    }
+
+    if config
+        .get("connectUnderReset")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+    {
+        args.push("--connect-under-reset".to_string());
+    }
+
+    Ok(args)
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    #[test]
+    fn parse_empty() {
+        for config in ["", "  \n"] {
+            let config = super::parse(config, "bare").unwrap();
+            assert_eq!(config["request"], "launch");
+        }
+
+        let err = super::parse("{", "broken").unwrap_err();
+        assert!(err.starts_with("Failed to parse JSON config"), "{err}");
+    }
+
+    #[test]
+    fn chip_fallback() {
+        let env = vec![("PROBE_RS_CHIP".to_string(), "nRF52840_xxAA".to_string())];
+
+        let mut config = json!({});
+        super::apply_chip_fallback(&mut config, &env, Some(("rp2040", "the settings")));
+        assert_eq!(config["chip"], "nRF52840_xxAA");
+
+        let mut config = json!({"chip": null});
+        super::apply_chip_fallback(&mut config, &env, None);
+        assert_eq!(config["chip"], "nRF52840_xxAA");
+
+        let mut config = json!({"chip": "STM32F401RETx"});
+        super::apply_chip_fallback(&mut config, &env, Some(("rp2040", "the settings")));
+        assert_eq!(config["chip"], "STM32F401RETx");
+
+        let mut config = json!({});
+        super::apply_chip_fallback(&mut config, &[], Some(("rp2040", "the settings")));
+        assert_eq!(config["chip"], "rp2040");
+
+        let mut config = json!({});
+        super::apply_chip_fallback(&mut config, &[], None);
+        assert!(config.get("chip").is_none());
+    }
+
+    #[test]
+    fn fast_flashing() {
+        let mut config = json!({
+            "fastFlashing": true,
+            "flashingConfig": { "flashingEnabled": true, "verifyAfterFlashing": true }
+        });
+        super::apply_fast_flashing(&mut config);
+
+        assert_eq!(
+            config["flashingConfig"],
+            json!({
+                "flashingEnabled": true,
+                "verifyBeforeFlashing": false,
+                "verifyAfterFlashing": true,
+                "fullChipErase": false,
+                "restoreUnwrittenBytes": false,
+            })
+        );
+
+        let mut config = json!({ "flashingConfig": {} });
+        super::apply_fast_flashing(&mut config);
+        assert_eq!(config["flashingConfig"], json!({}));
+    }
+
+    #[test]
+    fn core_halt_after_reset() {
+        let mut config = json!({
+            "coreConfigs": [{ "haltAfterReset": true }, {}, { "haltAfterReset": false }]
+        });
+
+        assert_eq!(
+            super::core_halt_after_reset(&config),
+            [(0, true), (2, false)]
+        );
+        assert!(super::apply_core_halt_after_reset(&mut config).is_err());
+
+        let mut config = json!({
+            "coreConfigs": [{ "haltAfterReset": true }, { "haltAfterReset": true }]
+        });
+        super::apply_core_halt_after_reset(&mut config).unwrap();
+        assert_eq!(config["flashingConfig"]["haltAfterReset"], true);
+
+        let mut config = json!({
+            "coreConfigs": [{ "haltAfterReset": true }, {}],
+            "flashingConfig": { "haltAfterReset": false }
+        });
+        super::apply_core_halt_after_reset(&mut config).unwrap();
+        assert_eq!(config["flashingConfig"]["haltAfterReset"], false);
+
+        let mut config = json!({ "coreConfigs": [{}] });
+        super::apply_core_halt_after_reset(&mut config).unwrap();
+        assert!(config.get("flashingConfig").is_none());
+    }
+
+    #[test]
+    fn observe_only() {
+        let mut config = json!({
+            "observeOnly": true,
+            "connectUnderReset": true,
+            "flashingConfig": { "flashingEnabled": false },
+            "coreConfigs": [{ "catchHardfault": true, "programBinary": "firmware" }]
+        });
+
+        assert!(super::is_observe_only(&config));
+        assert_eq!(
+            super::apply_observe_only(&mut config),
+            ["connectUnderReset", "coreConfigs[0].catchHardfault"]
+        );
+        assert_eq!(
+            config["flashingConfig"],
+            json!({ "flashingEnabled": false, "haltAfterReset": false })
+        );
+        assert_eq!(
+            config["coreConfigs"][0],
+            json!({
+                "catchHardfault": false,
+                "catchReset": false,
+                "haltAfterReset": false,
+                "programBinary": "firmware"
+            })
+        );
+
+        assert!(!super::is_observe_only(&json!({})));
+    }
+
+    #[test]
+    fn apply_extra_config() {
+        let mut config = json!({
+            "chip": "nRF52840_xxAA",
+            "flashingConfig": { "flashingEnabled": true },
+            "extraConfig": {
+                "flashingConfig": { "preverify": true },
+                "coreConfigs": [{ "coreIndex": 0 }],
+            },
+        });
+
+        assert_eq!(
+            super::apply_extra_config(&mut config).unwrap(),
+            ["coreConfigs", "flashingConfig.preverify"]
+        );
+        assert_eq!(
+            config,
+            json!({
+                "chip": "nRF52840_xxAA",
+                "flashingConfig": { "flashingEnabled": true, "preverify": true },
+                "coreConfigs": [{ "coreIndex": 0 }],
+            })
+        );
+
+        assert!(
+            super::apply_extra_config(&mut json!({}))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(super::apply_extra_config(&mut json!({ "extraConfig": [] })).is_err());
+    }
+
+    #[test]
+    fn log_file() {
+        assert_eq!(super::log_file(&json!({}), "Debug app", false), None);
+        assert_eq!(
+            super::log_file(&json!({}), "Debug app", true).as_deref(),
+            Some(".zed/probe-rs.log")
+        );
+        assert_eq!(
+            super::log_file(&json!({"logFile": true}), "Debug app (nRF52)", false).as_deref(),
+            Some(".zed/probe-rs-debug-app--nrf52.log")
+        );
+        assert_eq!(
+            super::log_file(&json!({"logFile": "logs/probe-rs.log"}), "Debug app", true).as_deref(),
+            Some("logs/probe-rs.log")
+        );
+        assert_eq!(
+            super::log_file(&json!({"logFile": true}), "???", false).as_deref(),
+            Some(".zed/probe-rs-session.log")
+        );
+    }
+
+    #[test]
+    fn server_args() {
+        assert!(super::server_args(&json!({})).unwrap().is_empty());
+        assert_eq!(
+            super::server_args(&json!({"serverArgs": ["--extra"]})).unwrap(),
+            ["--extra"]
+        );
+        assert!(super::server_args(&json!({"serverArgs": [1]})).is_err());
+        assert!(super::server_args(&json!({"serverArgs": "--extra"})).is_err());
+    }
+
+    #[test]
+    fn env() {
+        assert!(super::env(&json!({})).unwrap().is_empty());
+
+        assert_eq!(
+            super::env(&json!({"env": {"RUST_LOG": "probe_rs=debug", "RETRIES": 3}})).unwrap(),
+            [
+                ("RETRIES".to_string(), "3".to_string()),
+                ("RUST_LOG".to_string(), "probe_rs=debug".to_string())
+            ]
+        );
+
+        assert!(super::env(&json!({"env": ["A=B"]})).is_err());
+        assert!(super::env(&json!({"env": {"A": {"nested": true}}})).is_err());
+    }
+
+    #[test]
+    fn adapter_wrapper() {
+        let setting = ["sudo".to_string()];
+
+        assert_eq!(
+            super::adapter_wrapper(&json!({}), &setting).unwrap(),
+            ["sudo"]
+        );
+        assert_eq!(
+            super::adapter_wrapper(
+                &json!({ "adapterWrapper": ["flatpak-spawn", "--host"] }),
+                &setting
+            )
+            .unwrap(),
+            ["flatpak-spawn", "--host"]
+        );
+        assert!(
+            super::adapter_wrapper(&json!({ "adapterWrapper": [] }), &setting)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(super::adapter_wrapper(&json!({ "adapterWrapper": "sudo" }), &setting).is_err());
+    }
+
+    #[test]
+    fn merge_env() {
+        let shell_env = [("PATH", "/nix/store/probe-rs/bin"), ("RUST_LOG", "info")];
+        let shell_env = shell_env
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .to_vec();
+
+        assert_eq!(
+            super::merge_env(
+                shell_env,
+                vec![("RUST_LOG".to_string(), "probe_rs=debug".to_string())]
+            ),
+            [
+                ("PATH".to_string(), "/nix/store/probe-rs/bin".to_string()),
+                ("RUST_LOG".to_string(), "probe_rs=debug".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn adapter_path() {
+        let root = "/home/user/project";
+
+        assert_eq!(super::adapter_path(&json!({}), None, root), None);
+        assert_eq!(
+            super::adapter_path(&json!({}), Some("../probe-rs/target/debug/probe-rs"), root),
+            Some((
+                "/home/user/project/../probe-rs/target/debug/probe-rs".to_string(),
+                "'adapterPath' in '.zed/probe-rs.json'".to_string()
+            ))
+        );
+
+        // The scenario takes precedence over the settings.
+        let config = json!({ "adapterPath": "/opt/probe-rs/bin/probe-rs" });
+        assert_eq!(
+            super::adapter_path(&config, Some("probe-rs"), root)
+                .unwrap()
+                .0,
+            "/opt/probe-rs/bin/probe-rs"
+        );
+    }
+
+    #[test]
+    fn cwd() {
+        let root = "/home/user/project";
+
+        assert_eq!(super::cwd(&json!({}), root), root);
+        assert_eq!(
+            super::cwd(&json!({"cwd": "$ZED_WORKTREE_ROOT"}), root),
+            root
+        );
+        assert_eq!(
+            super::cwd(&json!({"cwd": "$ZED_WORKTREE_ROOT/firmware"}), root),
+            "/home/user/project/firmware"
+        );
+        assert_eq!(
+            super::cwd(&json!({"cwd": "firmware"}), root),
+            "/home/user/project/firmware"
+        );
+        assert_eq!(super::cwd(&json!({"cwd": "/opt/fw"}), root), "/opt/fw");
+        assert_eq!(
+            super::cwd(&json!({"cwd": "my firmware"}), "/home/user/my project"),
+            "/home/user/my project/my firmware"
+        );
+    }
+
+    #[test]
+    fn probe_selector() {
+        assert_eq!(super::probe_selector(&json!({})).unwrap(), None);
+        assert_eq!(
+            super::probe_selector(&json!({"probe": null})).unwrap(),
+            None
+        );
+
+        assert_eq!(
+            super::probe_selector(&json!({"probe": {"vendor_id": 0x1366, "product_id": 0x1015}}))
+                .unwrap()
+                .as_deref(),
+            Some("1366:1015")
+        );
+
+        assert_eq!(
+            super::probe_selector(&json!({"probe": {
+                "vendor_id": 0x0483,
+                "product_id": 0x374b,
+                "serial_number": "0671FF"
+            }}))
+            .unwrap()
+            .as_deref(),
+            Some("0483:374b:0671FF")
+        );
+
+        assert!(super::probe_selector(&json!({"probe": {"vendor_id": 0x1366}})).is_err());
+
+        for selector in ["1366:1015", "0483:374b:0671FF"] {
+            let config = json!({ "probe": super::probe_from_selector(selector).unwrap() });
+            assert_eq!(
+                super::probe_selector(&config).unwrap().as_deref(),
+                Some(selector)
+            );
+        }
+        assert!(super::probe_from_selector("jlink").is_none());
+        assert!(
+            super::probe_selector(&json!({"probe": {"vendor_id": 70000, "product_id": 1}}))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn attach_config() {
+        assert_eq!(
+            super::attach_config(Some("0483:374b:0671FF38"), Some("STM32F401RETx")).unwrap(),
+            json!({
+                "request": "attach",
+                "chip": "STM32F401RETx",
+                "probe": { "vendor_id": 0x0483, "product_id": 0x374b, "serial_number": "0671FF38" },
+                "coreConfigs": [{ "coreIndex": 0 }],
+            })
+        );
+
+        let config = super::attach_config(Some("1366:0105"), None).unwrap();
+        assert!(config.get("chip").is_none());
+
+        assert!(
+            super::attach_config(None, None)
+                .unwrap_err()
+                .contains("needs a probe")
+        );
+        assert!(super::attach_config(Some("jlink"), None).is_err());
+    }
+
+    #[test]
+    fn session_args() {
+        let config = json!({
+            "chip": "STM32L475VGTx",
+            "wireProtocol": "Swd",
+            "speed": 4000,
+            "connectUnderReset": true,
+        });
+
+        assert_eq!(
+            super::session_args(&config).unwrap(),
+            [
+                "--chip",
+                "STM32L475VGTx",
+                "--protocol",
+                "swd",
+                "--speed",
+                "4000",
+                "--connect-under-reset"
+            ]
+        );
+
+        assert!(super::session_args(&json!({})).is_err());
+
+        // Paths are single arguments, without quotes.
+        let config = json!({
+            "chip": "STM32L475VGTx",
+            "chipDescriptionPath": "C:\\My Targets\\board.yaml",
+        });
+        assert_eq!(
+            super::session_args(&config).unwrap()[2..],
+            ["--chip-description-path", "C:\\My Targets\\board.yaml"]
+        );
+    }
+
+    #[test]
+    fn normalize_numbers() {
+        let mut config = json!({
+            "speed": "4_000",
+            "probe": { "vendor_id": "0x1366", "product_id": 4117 },
+            "additionalImages": [{ "path": "boot.bin", "baseAddress": "0x0800_0000", "skip": null }],
+            "flashingConfig": { "formatOptions": { "bin_options": { "base_address": "0X10000" } } },
+        });
+
+        super::normalize_numbers(&mut config).unwrap();
+        assert_eq!(
+            config,
+            json!({
+                "speed": 4000,
+                "probe": { "vendor_id": 0x1366, "product_id": 4117 },
+                "additionalImages": [{ "path": "boot.bin", "baseAddress": 0x0800_0000, "skip": null }],
+                "flashingConfig": { "formatOptions": { "bin_options": { "base_address": 0x10000 } } },
+            })
+        );
+
+        let err = super::normalize_numbers(&mut json!({
+            "additionalImages": [{ "baseAddress": "0x0800000g" }]
+        }))
+        .unwrap_err();
+        assert_eq!(err.path, "additionalImages[0].baseAddress");
+        assert_eq!(
+            err.to_string(),
+            "'additionalImages[0].baseAddress' must be a positive whole number in decimal or hex, \
+             like 134217728 or \"0x08000000\", got \"0x0800000g\""
+        );
+        assert!(super::normalize_numbers(&mut json!({ "speed": -1 })).is_err());
+    }
+
+    #[test]
+    fn normalize_paths() {
+        let mut config = json!({
+            "cwd": "\"C:\\My Projects\\board\"",
+            "chipDescriptionPath": "targets/my board.yaml",
+            "coreConfigs": [{
+                "programBinary": "'target/my firmware'",
+                "svdFile": "\"svd/STM32 F4.svd\""
+            }],
+            "additionalImages": [{ "path": " \"boot loader.hex\" " }]
+        });
+
+        assert_eq!(
+            super::normalize_paths(&mut config),
+            [
+                "cwd",
+                "coreConfigs[0].programBinary",
+                "coreConfigs[0].svdFile",
+                "additionalImages[0].path"
+            ]
+        );
+        assert_eq!(config["cwd"], "C:\\My Projects\\board");
+        assert_eq!(config["chipDescriptionPath"], "targets/my board.yaml");
+        assert_eq!(
+            config["coreConfigs"][0]["programBinary"],
+            "target/my firmware"
+        );
+        assert_eq!(config["coreConfigs"][0]["svdFile"], "svd/STM32 F4.svd");
+        assert_eq!(config["additionalImages"][0]["path"], "boot loader.hex");
+
+        assert_eq!(super::cwd(&config, "C:\\root"), "C:\\My Projects\\board");
+        assert!(super::normalize_paths(&mut config).is_empty());
+    }
+}
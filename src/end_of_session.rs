@@ -0,0 +1,167 @@
+//! What happens to the target when a session ends, for `endOfSession`.
+//!
+//! When Zed stops a session, it asks probe-rs to terminate the debuggee, and probe-rs halts
+//! the cores, so firmware which has to keep running stops. The extension isn't told when a
+//! session ends, and the probe-rs CLI can neither halt nor resume a core outside of a session.
+//! What it can do is resetting the target after the session, so the firmware runs again: the
+//! dap-server is started in single-session mode by a shell, which runs `probe-rs reset` once
+//! the server has exited and released the probe.
+
+use zed_extension_api::{Os, serde_json::Value};
+
+use crate::server::Cli;
+
+/// The `endOfSession` option of a scenario.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum EndOfSession {
+    /// Leave the target in the state probe-rs leaves it in.
+    #[default]
+    Keep,
+    /// Reset the target after the session, so the firmware runs without the debugger.
+    Reset,
+}
+
+impl EndOfSession {
+    /// Read the `endOfSession` option, which is `"keep"` or `"reset"`.
+    pub fn from_config(config: &Value) -> Result<Self, String> {
+        match config.get("endOfSession") {
+            None | Some(Value::Null) => Ok(EndOfSession::Keep),
+            Some(Value::String(policy)) if policy == "keep" => Ok(EndOfSession::Keep),
+            Some(Value::String(policy)) if policy == "reset" => Ok(EndOfSession::Reset),
+            Some(_) => Err(
+                "'endOfSession' must be \"keep\" or \"reset\". Halting or resuming the target \
+                 after the session is not possible, since probe-rs can only do that during a \
+                 session"
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+/// Why the target can't be reset after the session, or `None` if it can.
+///
+/// This is decided before the server is set up, so it's only started in single-session mode
+/// if the reset actually happens.
+pub fn unsupported(os: Os, cli: Cli) -> Option<&'static str> {
+    if os == Os::Windows {
+        Some("Windows has no POSIX shell")
+    } else if cli == Cli::Legacy {
+        Some("the legacy 'debug --dap' has no single-session mode")
+    } else {
+        None
+    }
+}
+
+/// The arguments of `probe-rs reset` for the target of the scenario.
+pub fn reset_args(config: &Value) -> Result<Vec<String>, String> {
+    let chip = config
+        .get("chip")
+        .and_then(Value::as_str)
+        .ok_or_else(|| "\"endOfSession\": \"reset\" needs the 'chip' of the target".to_string())?;
+
+    let mut args = vec!["reset".to_string(), "--chip".to_string(), chip.to_string()];
+    if let Some(selector) = crate::config::probe_selector(config)? {
+        args.extend(["--probe".to_string(), selector]);
+    }
+
+    Ok(args)
+}
+
+/// Quote an argument for a POSIX shell.
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// The command and arguments which start the server, and reset the target after it exited.
+///
+/// The server is passed to the shell as positional arguments, so only the reset has to be
+/// quoted. The exit status of the server is kept. See [`unsupported`] for where this works.
+pub fn wrap(command: &str, arguments: &[String], reset_args: &[String]) -> (String, Vec<String>) {
+    let reset = std::iter::once(command)
+        .chain(reset_args.iter().map(String::as_str))
+        .map(quote)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let script = format!("\"$@\"; status=$?; {reset}; exit $status");
+
+    let mut args = vec![
+        "-c".to_string(),
+        script,
+        "sh".to_string(),
+        command.to_string(),
+    ];
+    args.extend(arguments.iter().cloned());
+
+    ("sh".to_string(), args)
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::{Os, serde_json::json};
+
+    use super::EndOfSession;
+    use crate::server::Cli;
+
+    #[test]
+    fn from_config() {
+        assert_eq!(
+            EndOfSession::from_config(&json!({})),
+            Ok(EndOfSession::Keep)
+        );
+        assert_eq!(
+            EndOfSession::from_config(&json!({ "endOfSession": "reset" })),
+            Ok(EndOfSession::Reset)
+        );
+        assert!(EndOfSession::from_config(&json!({ "endOfSession": "halt" })).is_err());
+    }
+
+    #[test]
+    fn reset_args() {
+        let config = json!({
+            "chip": "nRF52840_xxAA",
+            "probe": { "vendor_id": 0x1366, "product_id": 0x1015, "serial_number": "123" },
+        });
+        assert_eq!(
+            super::reset_args(&config).unwrap(),
+            [
+                "reset",
+                "--chip",
+                "nRF52840_xxAA",
+                "--probe",
+                "1366:1015:123"
+            ]
+        );
+        assert!(super::reset_args(&json!({})).is_err());
+    }
+
+    #[test]
+    fn wrap() {
+        let arguments = ["dap-server".to_string(), "--single-session".to_string()];
+        let reset = [
+            "reset".to_string(),
+            "--chip".to_string(),
+            "it's".to_string(),
+        ];
+
+        let (command, args) = super::wrap("/opt/probe-rs", &arguments, &reset);
+        assert_eq!(command, "sh");
+        assert_eq!(
+            args,
+            [
+                "-c",
+                r#""$@"; status=$?; '/opt/probe-rs' 'reset' '--chip' 'it'\''s'; exit $status"#,
+                "sh",
+                "/opt/probe-rs",
+                "dap-server",
+                "--single-session"
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        assert_eq!(super::unsupported(Os::Linux, Cli::DapServer), None);
+        assert!(super::unsupported(Os::Windows, Cli::DapServer).is_some());
+        assert!(super::unsupported(Os::Mac, Cli::Legacy).is_some());
+    }
+}
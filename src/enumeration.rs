@@ -0,0 +1,97 @@
+//! Listing the connected debug probes, to check that the configured probe is available.
+//!
+//! `probe-rs list` can hang on broken USB stacks. The extension has no way to cancel a
+//! process, so the command is run through a timeout tool of the host. If the probes can't be
+//! listed in time, the check is skipped instead of delaying the session.
+
+use zed_extension_api as zed;
+
+/// How long listing the probes may take.
+const TIMEOUT_SECONDS: u32 = 5;
+
+/// A probe reported by `probe-rs list`.
+#[derive(Debug, PartialEq)]
+pub struct ConnectedProbe {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub serial: Option<String>,
+}
+
+/// Parse the output of `probe-rs list`, which has a line per probe like
+/// `[0]: STLink V2 (VID: 0483, PID: 3748, Serial: 56FF6B06, StLink)`.
+fn parse_list(output: &str) -> Vec<ConnectedProbe> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let field = |name: &str| {
+                let start = line.find(&format!("{name}: "))? + name.len() + 2;
+                let value = &line[start..];
+                let end = value.find([',', ')']).unwrap_or(value.len());
+
+                Some(value[..end].trim())
+            };
+
+            Some(ConnectedProbe {
+                vendor_id: u16::from_str_radix(field("VID")?, 16).ok()?,
+                product_id: u16::from_str_radix(field("PID")?, 16).ok()?,
+                serial: field("Serial")
+                    .filter(|serial| !serial.is_empty())
+                    .map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+/// List the connected probes, failing if they couldn't be listed in time.
+pub fn try_list(probe_rs: &str) -> Result<Vec<ConnectedProbe>, String> {
+    let (os, _) = zed::current_platform();
+
+    let (command, args) =
+        crate::process::with_timeout(os, TIMEOUT_SECONDS, probe_rs, &["list".to_string()])
+            .ok_or("Listing probes with a timeout is not supported on this platform")?;
+
+    crate::process::run(&command, &args).map(|output| parse_list(&output))
+}
+
+/// List the connected probes, or `None` if they couldn't be listed in time.
+pub fn list(probe_rs: &str) -> Option<Vec<ConnectedProbe>> {
+    log!("Looking for connected probes, for up to {TIMEOUT_SECONDS} seconds");
+
+    match try_list(probe_rs) {
+        Ok(probes) => Some(probes),
+        Err(err) => {
+            log!("Skipping the probe check, the probes couldn't be listed: {err}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ConnectedProbe;
+
+    #[test]
+    fn parse_list() {
+        let output = "The following debug probes were found:\n\
+            [0]: J-Link (J-Link) (VID: 1366, PID: 0101, Serial: 000123456789, JLink)\n\
+            [1]: STLink V2 (VID: 0483, PID: 3748, Serial: 56FF6B064966485627521187, StLink)\n";
+
+        assert_eq!(
+            super::parse_list(output),
+            [
+                ConnectedProbe {
+                    vendor_id: 0x1366,
+                    product_id: 0x0101,
+                    serial: Some("000123456789".to_string()),
+                },
+                ConnectedProbe {
+                    vendor_id: 0x0483,
+                    product_id: 0x3748,
+                    serial: Some("56FF6B064966485627521187".to_string()),
+                },
+            ]
+        );
+
+        assert!(super::parse_list("No debug probes were found.\n").is_empty());
+    }
+}
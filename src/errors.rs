@@ -0,0 +1,184 @@
+//! Categories of failures when starting a session, with hints on how to fix them.
+//!
+//! A session can fail at three different stages, and each needs a different fix: the debug
+//! adapter server couldn't be started or reached, the probe couldn't be opened, or the target
+//! couldn't be started. Errors are prefixed with the stage, so the cause is clear at a glance.
+//!
+//! For well-known failures, the error also links to the section of the probe-rs documentation
+//! explaining it.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Category {
+    /// The probe-rs dap-server couldn't be started or reached.
+    Server,
+    /// The debug probe couldn't be found or opened.
+    Probe,
+    /// The target couldn't be connected to, flashed or started.
+    Target,
+}
+
+/// Messages of probe-rs which indicate a problem with the probe.
+const PROBE_ERRORS: &[&str] = &[
+    "no connected probes",
+    "no probe",
+    "probe could not be opened",
+    "unable to open probe",
+    "failed to open the debug probe",
+    "permission denied",
+    "access denied",
+    "usb error",
+];
+
+/// Well-known failures by an error code, the messages of probe-rs and the extension which
+/// identify them, and the probe-rs documentation about them.
+const HELP_LINKS: &[(&str, &[&str], &str)] = &[
+    (
+        "chip-not-found",
+        &[
+            "was not found in the list of known targets",
+            "chip not found",
+            "no target with the name",
+        ],
+        "https://probe.rs/targets/",
+    ),
+    (
+        "probe-permissions",
+        &[
+            "permission denied",
+            "access denied",
+            "insufficient permissions",
+        ],
+        "https://probe.rs/docs/getting-started/probe-setup/",
+    ),
+    (
+        "version-too-old",
+        &["is too old", "unrecognized subcommand"],
+        "https://probe.rs/docs/getting-started/installation/",
+    ),
+];
+
+/// The code and documentation link of a well-known failure.
+fn help_link(err: &str) -> Option<(&'static str, &'static str)> {
+    let err = err.to_lowercase();
+
+    HELP_LINKS
+        .iter()
+        .find(|(_, messages, _)| messages.iter().any(|message| err.contains(message)))
+        .map(|(code, _, url)| (*code, *url))
+}
+
+impl Category {
+    fn prefix(self) -> &'static str {
+        match self {
+            Category::Server => "Couldn't start or reach the probe-rs debug adapter",
+            Category::Probe => "Couldn't open the debug probe",
+            Category::Target => "Couldn't start the target",
+        }
+    }
+
+    fn hint(self) -> &'static str {
+        match self {
+            Category::Server => {
+                "Check that probe-rs is installed, and that the `server` address is correct if one is configured."
+            }
+            Category::Probe => {
+                "Check that the probe is connected and not used by another program. On Linux, the udev rules have to be installed, see <https://probe.rs/docs/getting-started/probe-setup/>."
+            }
+            Category::Target => {
+                "Check that the target is powered and the `chip` is correct. Locked or sleeping targets may need `connectUnderReset`, or a lower `speed`."
+            }
+        }
+    }
+
+    /// The category of an error created with [`Category::error`].
+    pub fn from_error(err: &str) -> Option<Self> {
+        [Category::Server, Category::Probe, Category::Target]
+            .into_iter()
+            .find(|category| err.contains(category.prefix()))
+    }
+
+    /// Short name of the category, for statistics.
+    pub fn code(self) -> &'static str {
+        match self {
+            Category::Server => "server",
+            Category::Probe => "probe",
+            Category::Target => "target",
+        }
+    }
+
+    /// Categorize an error of a probe-rs CLI command, which either failed to open the probe, or
+    /// failed once connected to the target.
+    pub fn of_probe_rs_error(message: &str) -> Self {
+        let message = message.to_lowercase();
+
+        if PROBE_ERRORS.iter().any(|error| message.contains(error)) {
+            Category::Probe
+        } else {
+            Category::Target
+        }
+    }
+
+    /// Prefix the error with the category, and add the hint and the documentation link of a
+    /// well-known failure, unless the error or hint already link to it.
+    pub fn error(self, err: impl std::fmt::Display) -> String {
+        let error = format!("{}: {err}\n{}", self.prefix(), self.hint());
+
+        match help_link(&err.to_string()) {
+            Some((code, url)) if !error.contains(url) => {
+                format!("{error}\nSee <{url}> ({code}).")
+            }
+            _ => error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Category;
+
+    #[test]
+    fn of_probe_rs_error() {
+        assert_eq!(
+            Category::of_probe_rs_error("Error: No connected probes were found."),
+            Category::Probe
+        );
+        assert_eq!(
+            Category::of_probe_rs_error("Probe could not be opened: Permission denied"),
+            Category::Probe
+        );
+        assert_eq!(
+            Category::of_probe_rs_error("Connecting to the chip was unsuccessful."),
+            Category::Target
+        );
+    }
+
+    #[test]
+    fn help_link() {
+        let error = Category::Target
+            .error("The requested chip 'nRF52841' was not found in the list of known targets.");
+        assert!(error.ends_with("\nSee <https://probe.rs/targets/> (chip-not-found)."));
+
+        // The hint already links to the probe setup.
+        let error = Category::Probe.error("Probe could not be opened: Permission denied");
+        assert_eq!(
+            error
+                .matches("https://probe.rs/docs/getting-started/probe-setup/")
+                .count(),
+            1
+        );
+
+        assert_eq!(
+            super::help_link("Connecting to the chip was unsuccessful."),
+            None
+        );
+    }
+
+    #[test]
+    fn error() {
+        let error = Category::Server.error("Invalid port number 'abc'");
+
+        assert!(error.starts_with(
+            "Couldn't start or reach the probe-rs debug adapter: Invalid port number 'abc'\n"
+        ));
+    }
+}
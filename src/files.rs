@@ -0,0 +1,49 @@
+//! Reading optional files of the worktree.
+//!
+//! Zed may deny access to files of the worktree, and remote worktrees don't provide all files
+//! to extensions. Files which only provide defaults, like `Cargo.toml` or the settings, must
+//! not fail the launch in that case. The feature depending on the file is skipped instead,
+//! with a note in the log, so the configuration can be completed manually.
+
+use zed_extension_api::Worktree;
+
+/// Read an optional file of the worktree.
+///
+/// A missing file is expected and returns `None` silently. If the file can't be read for other
+/// reasons, a note is logged, with `consequence` telling what is skipped because of it.
+pub fn read_optional(worktree: &Worktree, path: &str, consequence: &str) -> Option<String> {
+    match worktree.read_text_file(path) {
+        Ok(contents) => Some(contents),
+        Err(err) if is_missing(&err) => None,
+        Err(err) => {
+            log!("Can't read '{path}' from the worktree, {consequence}: {err}");
+            None
+        }
+    }
+}
+
+/// Whether a read error means that the file doesn't exist.
+fn is_missing(err: &str) -> bool {
+    let err = err.to_ascii_lowercase();
+
+    ["no such file", "not found", "cannot find", "os error 2"]
+        .iter()
+        .any(|pattern| err.contains(pattern))
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn is_missing() {
+        assert!(super::is_missing(
+            "failed to load file: No such file or directory (os error 2)"
+        ));
+        assert!(super::is_missing(
+            "The system cannot find the file specified. (os error 2)"
+        ));
+        assert!(!super::is_missing("Permission denied (os error 13)"));
+        assert!(!super::is_missing(
+            "reading files is not supported for remote worktrees"
+        ));
+    }
+}
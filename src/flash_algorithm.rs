@@ -0,0 +1,129 @@
+//! Custom flash algorithms, for `flashAlgorithm`.
+//!
+//! probe-rs takes flash algorithms only from target descriptions. External QSPI flash and
+//! vendor specific loaders come as CMSIS flash algorithm files (`.FLM`) or ELF files instead,
+//! so the extension adds the algorithm to a copy of the `chipDescriptionPath` using
+//! `target-gen elf`, and the session uses the copy. The original file is left unchanged.
+
+use std::fs;
+
+use zed_extension_api::{Worktree, serde_json::Value};
+
+/// File extensions of flash algorithms which target-gen can read.
+const EXTENSIONS: &[&str] = &["flm", "elf", "axf"];
+
+/// Read and check the `flashAlgorithm` option.
+pub fn algorithm_path(config: &Value) -> Result<Option<&str>, String> {
+    let path = match config.get("flashAlgorithm") {
+        None | Some(Value::Null) => return Ok(None),
+        Some(Value::String(path)) => path.as_str(),
+        Some(_) => return Err("'flashAlgorithm' must be the path of a flash algorithm".to_string()),
+    };
+
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+
+    if !extension.is_some_and(|extension| EXTENSIONS.contains(&extension.as_str())) {
+        return Err(format!(
+            "'flashAlgorithm' must be an .FLM or ELF file, got '{path}'"
+        ));
+    }
+
+    Ok(Some(path))
+}
+
+/// The name of the algorithm in the target description, taken from its file name.
+fn algorithm_name(path: &str) -> String {
+    let file = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    let stem = file.rsplit_once('.').map_or(file, |(stem, _)| stem);
+
+    crate::config::file_name(stem)
+}
+
+/// Add the flash algorithm to a copy of the target description in the session directory, see
+/// [`crate::scratch`], and use the copy.
+pub fn apply(
+    worktree: &Worktree,
+    config: &mut Value,
+    session_directory: &str,
+    cwd: &str,
+) -> Result<(), String> {
+    let Some(algorithm) = algorithm_path(config)? else {
+        return Ok(());
+    };
+    let algorithm = crate::paths::join(Some(cwd), algorithm);
+
+    let description = config
+        .get("chipDescriptionPath")
+        .and_then(Value::as_str)
+        .ok_or_else(|| {
+            "'flashAlgorithm' needs a 'chipDescriptionPath' to add the algorithm to, \
+             generate one with /probe-rs-target-gen"
+                .to_string()
+        })?;
+    let description = crate::paths::join(Some(cwd), description);
+
+    let root = worktree.root_path();
+    let relative = crate::paths::strip_root(&root, &description).ok_or_else(|| {
+        format!(
+            "The 'chipDescriptionPath' '{description}' has to be inside the worktree to add \
+             the 'flashAlgorithm' to it"
+        )
+    })?;
+    let contents = worktree
+        .read_text_file(relative)
+        .map_err(|err| format!("Failed to read the target description '{description}': {err}"))?;
+
+    let output = format!("{session_directory}/target.yaml");
+    fs::write(&output, contents)
+        .map_err(|err| format!("Failed to copy the target description: {err}"))?;
+    let output = crate::paths::in_work_dir(&output)?;
+
+    let target_gen = worktree.which("target-gen").ok_or_else(|| {
+        "'flashAlgorithm' needs target-gen, install it with `cargo install target-gen`".to_string()
+    })?;
+
+    let args = [
+        "elf".to_string(),
+        "--update".to_string(),
+        "--name".to_string(),
+        algorithm_name(&algorithm),
+        algorithm.clone(),
+        output.clone(),
+    ];
+    crate::process::run(&target_gen, &args)
+        .map_err(|err| format!("Failed to add the flash algorithm '{algorithm}': {err}"))?;
+
+    log!("Using the flash algorithm '{algorithm}' with the target description '{output}'");
+    config["chipDescriptionPath"] = output.into();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    #[test]
+    fn algorithm_path() {
+        assert_eq!(super::algorithm_path(&json!({})), Ok(None));
+        assert_eq!(
+            super::algorithm_path(&json!({ "flashAlgorithm": "loaders/MT25QL512.FLM" })),
+            Ok(Some("loaders/MT25QL512.FLM"))
+        );
+        assert!(super::algorithm_path(&json!({ "flashAlgorithm": "loader.elf" })).is_ok());
+        assert!(super::algorithm_path(&json!({ "flashAlgorithm": "loader.yaml" })).is_err());
+        assert!(super::algorithm_path(&json!({ "flashAlgorithm": "loader" })).is_err());
+        assert!(super::algorithm_path(&json!({ "flashAlgorithm": true })).is_err());
+    }
+
+    #[test]
+    fn algorithm_name() {
+        assert_eq!(
+            super::algorithm_name("C:\\Keil\\Flash\\STM32H7x_QSPI.FLM"),
+            "stm32h7x_qspi"
+        );
+        assert_eq!(super::algorithm_name("/loaders/w25q128.elf"), "w25q128");
+    }
+}
@@ -0,0 +1,299 @@
+//! Flashing before the session starts, and what to do if it fails.
+//!
+//! By default, probe-rs flashes the programs as part of the session, and a failure ends the
+//! session. With flaky connections, e.g. when debugging in the field, it's more useful to retry,
+//! or to attach to the firmware which is already on the target. For that, the `flashFailure`
+//! policy lets the extension flash the programs itself before the session starts.
+//!
+//! The same is done for `"restart": "reset"`. probe-rs flashes again whenever a session is
+//! restarted, if flashing is enabled for it. If the extension flashes instead, restarting only
+//! resets the target.
+//!
+//! The same is done if the `flashedCommand` setting is set, which is run when flashing is done,
+//! for example to show a notification. probe-rs doesn't tell when it has flashed the programs,
+//! so the extension has to flash them itself to know.
+//!
+//! The same is done for `flashOnlyChanged`, which compares the programs to the ones last
+//! flashed. The fingerprints of the programs must only be recorded once they were flashed.
+
+use zed_extension_api::serde_json::Value;
+
+/// What to do if flashing fails.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FailurePolicy {
+    /// Fail the session, which is also what probe-rs does.
+    Abort,
+    /// Retry flashing the given number of times.
+    Retry(u32),
+    /// Attach to the target without flashing.
+    Attach,
+}
+
+impl FailurePolicy {
+    /// Read the `flashFailure` option, which is `"abort"`, `"attach"` or `{ "retry": N }`.
+    pub fn from_config(config: &Value) -> Result<Self, String> {
+        match config.get("flashFailure") {
+            None | Some(Value::Null) => Ok(FailurePolicy::Abort),
+            Some(Value::String(policy)) if policy == "abort" => Ok(FailurePolicy::Abort),
+            Some(Value::String(policy)) if policy == "attach" => Ok(FailurePolicy::Attach),
+            Some(Value::Object(policy)) => policy
+                .get("retry")
+                .and_then(Value::as_u64)
+                .and_then(|retries| u32::try_from(retries).ok())
+                .map(FailurePolicy::Retry)
+                .ok_or_else(|| {
+                    "'flashFailure' must contain the number of retries, like { \"retry\": 3 }"
+                        .to_string()
+                }),
+            Some(_) => Err(
+                "'flashFailure' must be \"abort\", \"attach\" or { \"retry\": <count> }"
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+/// What restarting the session does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestartBehavior {
+    /// Flash the programs again, which is what probe-rs does.
+    Reflash,
+    /// Only reset the target.
+    Reset,
+}
+
+impl RestartBehavior {
+    /// Read the `restart` option, which is `"reflash"` or `"reset"`.
+    pub fn from_config(config: &Value) -> Result<Self, String> {
+        match config.get("restart") {
+            None | Some(Value::Null) => Ok(RestartBehavior::Reflash),
+            Some(Value::String(restart)) if restart == "reflash" => Ok(RestartBehavior::Reflash),
+            Some(Value::String(restart)) if restart == "reset" => Ok(RestartBehavior::Reset),
+            Some(_) => Err("'restart' must be \"reflash\" or \"reset\"".to_string()),
+        }
+    }
+}
+
+/// The result of flashing with a policy.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// The additional images were flashed, and probe-rs flashes the programs later.
+    Flashed,
+    /// The programs were flashed as well, so probe-rs doesn't flash them.
+    FlashedPrograms,
+    /// Flashing failed, and the session should attach instead.
+    Attach,
+}
+
+impl Outcome {
+    /// Whether the session flashes the programs, either the extension did already or probe-rs
+    /// does when the session starts.
+    pub fn programs_flashed(&self) -> bool {
+        matches!(self, Outcome::Flashed | Outcome::FlashedPrograms)
+    }
+}
+
+/// Run `flash` according to the policy.
+fn run_with_policy(
+    policy: FailurePolicy,
+    mut flash: impl FnMut() -> Result<(), String>,
+) -> Result<Outcome, String> {
+    let attempts = match policy {
+        FailurePolicy::Retry(retries) => retries.saturating_add(1),
+        FailurePolicy::Abort | FailurePolicy::Attach => 1,
+    };
+
+    let mut attempt = 1;
+
+    loop {
+        match flash() {
+            Ok(()) => return Ok(Outcome::Flashed),
+            Err(err) if attempt < attempts => {
+                log!("Flashing failed (attempt {attempt} of {attempts}), retrying: {err}");
+                attempt += 1;
+            }
+            Err(err) if policy == FailurePolicy::Attach => {
+                log!("Flashing failed, attaching to the target instead: {err}");
+                return Ok(Outcome::Attach);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Flash the additional images, and with a policy other than abort, if restarting should only
+/// reset the target, or if `always_programs` is set, also the programs.
+///
+/// If the programs are flashed here, flashing is disabled for probe-rs. If the session should
+/// attach instead, the request of the configuration is changed to attach.
+pub fn flash(
+    probe_rs: &str,
+    config: &mut Value,
+    cwd: &str,
+    programs: &[String],
+    always_programs: bool,
+) -> Result<Outcome, String> {
+    let policy = FailurePolicy::from_config(config)?;
+    let restart = RestartBehavior::from_config(config)?;
+    let flash_programs =
+        always_programs || policy != FailurePolicy::Abort || restart == RestartBehavior::Reset;
+
+    let outcome = run_with_policy(policy, || {
+        crate::images::flash(probe_rs, config, cwd)?;
+
+        if flash_programs {
+            crate::images::flash_programs(probe_rs, config, programs)?;
+        }
+
+        Ok(())
+    })?;
+
+    Ok(finish(outcome, flash_programs, config))
+}
+
+/// Tell probe-rs what is left to do after flashing.
+fn finish(outcome: Outcome, flash_programs: bool, config: &mut Value) -> Outcome {
+    match outcome {
+        Outcome::Flashed if flash_programs => {
+            config["flashingConfig"]["flashingEnabled"] = false.into();
+            Outcome::FlashedPrograms
+        }
+        Outcome::Attach => {
+            config["request"] = "attach".into();
+            Outcome::Attach
+        }
+        outcome => outcome,
+    }
+}
+
+/// The `flashedCommand` with its placeholders replaced, as the binary and its arguments.
+///
+/// `{label}` is replaced with the label of the scenario, and `{program}` with the first program.
+fn flashed_command(
+    command: &[String],
+    label: &str,
+    programs: &[String],
+) -> Option<(String, Vec<String>)> {
+    let program = programs.first().map_or("", String::as_str);
+    let mut command = command
+        .iter()
+        .map(|arg| arg.replace("{label}", label).replace("{program}", program));
+
+    Some((command.next()?, command.collect()))
+}
+
+/// Run the `flashedCommand` of the settings after the programs were flashed.
+///
+/// A failing command is only logged, since the target is flashed anyway.
+pub fn run_flashed_command(command: &[String], label: &str, programs: &[String]) {
+    let Some((binary, args)) = flashed_command(command, label, programs) else {
+        return;
+    };
+
+    if let Err(err) = crate::process::run(&binary, &args) {
+        log!("The flashedCommand failed: {err}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    use super::{FailurePolicy, Outcome, RestartBehavior};
+
+    #[test]
+    fn from_config() {
+        let policy = |config| FailurePolicy::from_config(&config);
+
+        assert_eq!(policy(json!({})), Ok(FailurePolicy::Abort));
+        assert_eq!(
+            policy(json!({ "flashFailure": "attach" })),
+            Ok(FailurePolicy::Attach)
+        );
+        assert_eq!(
+            policy(json!({ "flashFailure": { "retry": 3 } })),
+            Ok(FailurePolicy::Retry(3))
+        );
+        assert!(policy(json!({ "flashFailure": "sometimes" })).is_err());
+        assert!(policy(json!({ "flashFailure": { "retry": -1 } })).is_err());
+    }
+
+    #[test]
+    fn restart_from_config() {
+        let restart = |config| RestartBehavior::from_config(&config);
+
+        assert_eq!(restart(json!({})), Ok(RestartBehavior::Reflash));
+        assert_eq!(
+            restart(json!({ "restart": "reset" })),
+            Ok(RestartBehavior::Reset)
+        );
+        assert!(restart(json!({ "restart": true })).is_err());
+    }
+
+    #[test]
+    fn retry_until_success() {
+        let mut attempts = 0;
+        let outcome = super::run_with_policy(FailurePolicy::Retry(2), || {
+            attempts += 1;
+            if attempts < 3 {
+                Err("No connected probes were found".to_string())
+            } else {
+                Ok(())
+            }
+        });
+
+        assert_eq!(outcome, Ok(Outcome::Flashed));
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn retries_exhausted() {
+        let mut attempts = 0;
+        let outcome = super::run_with_policy(FailurePolicy::Retry(1), || {
+            attempts += 1;
+            Err("failed".to_string())
+        });
+
+        assert_eq!(outcome, Err("failed".to_string()));
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn finish() {
+        let mut config = json!({ "flashingConfig": { "flashingEnabled": true } });
+        assert_eq!(
+            super::finish(Outcome::Flashed, false, &mut config),
+            Outcome::Flashed
+        );
+        assert_eq!(config["flashingConfig"]["flashingEnabled"], true);
+
+        assert_eq!(
+            super::finish(Outcome::Flashed, true, &mut config),
+            Outcome::FlashedPrograms
+        );
+        assert_eq!(config["flashingConfig"]["flashingEnabled"], false);
+    }
+
+    #[test]
+    fn flashed_command() {
+        let command = ["notify-send", "{label}", "Flashed {program}"].map(str::to_string);
+
+        assert_eq!(
+            super::flashed_command(&command, "Debug nRF52", &["target/app".to_string()]),
+            Some((
+                "notify-send".to_string(),
+                vec!["Debug nRF52".to_string(), "Flashed target/app".to_string()]
+            ))
+        );
+        assert_eq!(super::flashed_command(&[], "Debug nRF52", &[]), None);
+    }
+
+    #[test]
+    fn fall_back_to_attach() {
+        let outcome = super::run_with_policy(FailurePolicy::Attach, || Err("failed".to_string()));
+        assert_eq!(outcome, Ok(Outcome::Attach));
+
+        let outcome = super::run_with_policy(FailurePolicy::Abort, || Err("failed".to_string()));
+        assert!(outcome.is_err());
+    }
+}
@@ -0,0 +1,208 @@
+//! Looking up releases on GitHub, with a token from the environment if there is one.
+//!
+//! Without a token, GitHub allows 60 API requests per hour and address, which a shared office
+//! network or CI machine can use up quickly. The lookups of Zed are unauthenticated, so with a
+//! `GITHUB_TOKEN` or `GH_TOKEN` in the environment of the worktree, the extension queries the
+//! GitHub API itself. Being rate limited is reported as such, instead of as a failed download.
+
+use zed_extension_api::{
+    self as zed, GithubRelease, GithubReleaseAsset, GithubReleaseOptions,
+    http_client::{HttpMethod, HttpRequest, RedirectPolicy},
+    serde_json::{self, Value},
+};
+
+/// Environment variables with a GitHub token, in order of preference.
+const TOKEN_VARIABLES: &[&str] = &["GITHUB_TOKEN", "GH_TOKEN"];
+
+const API_URL: &str = "https://api.github.com";
+
+/// The GitHub token in the environment of the worktree.
+pub fn token(env: &[(String, String)]) -> Option<String> {
+    TOKEN_VARIABLES.iter().find_map(|variable| {
+        env.iter()
+            .find(|(name, value)| name == variable && !value.trim().is_empty())
+            .map(|(_, value)| value.trim().to_string())
+    })
+}
+
+/// Whether an error of GitHub says that the rate limit was exceeded.
+///
+/// GitHub answers with 403 or 429, and a message mentioning the rate limit.
+fn is_rate_limited(err: &str) -> bool {
+    let err = err.to_lowercase();
+
+    err.contains("rate limit") || err.contains("status error 403") || err.contains("status 429")
+}
+
+/// Explain an error of a release lookup, which is most often the rate limit.
+fn explain(err: String, token: Option<&str>) -> String {
+    if !is_rate_limited(&err) {
+        return err;
+    }
+
+    match token {
+        Some(_) => {
+            format!("GitHub rate limited the lookup even with the token, try again later ({err})")
+        }
+        None => format!(
+            "GitHub rate limited the lookup, try again later, or set GITHUB_TOKEN in the \
+             environment of Zed to a GitHub token, which allows more requests ({err})"
+        ),
+    }
+}
+
+/// A release in the format of the GitHub API.
+fn parse_release(release: &Value) -> Option<GithubRelease> {
+    let assets = release
+        .get("assets")?
+        .as_array()?
+        .iter()
+        .filter_map(|asset| {
+            Some(GithubReleaseAsset {
+                name: asset.get("name")?.as_str()?.to_string(),
+                download_url: asset.get("browser_download_url")?.as_str()?.to_string(),
+            })
+        })
+        .collect();
+
+    Some(GithubRelease {
+        version: release.get("tag_name")?.as_str()?.to_string(),
+        assets,
+    })
+}
+
+/// Query the GitHub API with the token.
+fn fetch(path: &str, token: &str) -> Result<Value, String> {
+    let response = HttpRequest::builder()
+        .method(HttpMethod::Get)
+        .url(format!("{API_URL}/{path}"))
+        .header("Accept", "application/vnd.github+json")
+        .header("Authorization", format!("Bearer {token}"))
+        .header("User-Agent", "zed-probe-rs")
+        .redirect_policy(RedirectPolicy::FollowAll)
+        .build()?
+        .fetch()?;
+
+    let body: Value = serde_json::from_slice(&response.body)
+        .map_err(|err| format!("Invalid response from GitHub: {err}"))?;
+
+    // Errors come with a message instead of the release.
+    match body.get("message").and_then(Value::as_str) {
+        Some(message) if body.get("tag_name").is_none() => Err(message.to_string()),
+        _ => Ok(body),
+    }
+}
+
+/// The latest release of the repository with assets, either a stable release or a pre-release.
+pub fn latest_release(
+    repository: &str,
+    pre_release: bool,
+    token: Option<&str>,
+) -> Result<GithubRelease, String> {
+    let Some(token) = token else {
+        let options = GithubReleaseOptions {
+            require_assets: true,
+            pre_release,
+        };
+        return zed::latest_github_release(repository, options).map_err(|err| explain(err, None));
+    };
+
+    let release = if pre_release {
+        let releases = fetch(&format!("repos/{repository}/releases?per_page=30"), token);
+
+        releases.and_then(|releases| {
+            releases
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|release| release.get("prerelease") == Some(&Value::Bool(true)))
+                .filter_map(parse_release)
+                .find(|release| !release.assets.is_empty())
+                .ok_or_else(|| "no pre-release with assets found".to_string())
+        })
+    } else {
+        fetch(&format!("repos/{repository}/releases/latest"), token)
+            .and_then(|release| parse_release(&release).ok_or_else(invalid_release))
+    };
+
+    release.map_err(|err| explain(err, Some(token)))
+}
+
+/// The release of the repository with the given tag.
+pub fn release_by_tag(
+    repository: &str,
+    tag: &str,
+    token: Option<&str>,
+) -> Result<GithubRelease, String> {
+    let release = match token {
+        None => zed::github_release_by_tag_name(repository, tag),
+        Some(token) => fetch(&format!("repos/{repository}/releases/tags/{tag}"), token)
+            .and_then(|release| parse_release(&release).ok_or_else(invalid_release)),
+    };
+
+    release.map_err(|err| explain(err, token))
+}
+
+fn invalid_release() -> String {
+    "GitHub returned an invalid release".to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    #[test]
+    fn token() {
+        let env = |variables: &[(&str, &str)]| -> Vec<(String, String)> {
+            variables
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+
+        assert_eq!(
+            super::token(&env(&[("GH_TOKEN", "gho_2"), ("GITHUB_TOKEN", "ghp_1\n")])),
+            Some("ghp_1".to_string())
+        );
+        assert_eq!(
+            super::token(&env(&[("GITHUB_TOKEN", ""), ("HOME", "/")])),
+            None
+        );
+    }
+
+    #[test]
+    fn explain() {
+        let err = "status error 403, response: \"{\\\"message\\\":\\\"API rate limit exceeded for 1.2.3.4.\\\"}\"";
+
+        assert!(
+            super::explain(err.to_string(), None).starts_with(
+                "GitHub rate limited the lookup, try again later, or set GITHUB_TOKEN"
+            )
+        );
+        assert!(super::explain(err.to_string(), Some("ghp_1")).contains("even with the token"));
+        assert_eq!(
+            super::explain("no release found".to_string(), None),
+            "no release found"
+        );
+    }
+
+    #[test]
+    fn parse_release() {
+        let release = super::parse_release(&json!({
+            "tag_name": "v0.25.0",
+            "prerelease": false,
+            "assets": [{
+                "name": "probe-rs-tools-x86_64-unknown-linux-gnu.tar.xz",
+                "browser_download_url": "https://github.com/probe-rs/probe-rs/releases/download/v0.25.0/probe-rs-tools-x86_64-unknown-linux-gnu.tar.xz",
+            }],
+        }))
+        .unwrap();
+
+        assert_eq!(release.version, "v0.25.0");
+        assert_eq!(
+            release.assets[0].name,
+            "probe-rs-tools-x86_64-unknown-linux-gnu.tar.xz"
+        );
+        assert!(super::parse_release(&json!({ "message": "Not Found" })).is_none());
+    }
+}
@@ -0,0 +1,107 @@
+//! The last configuration, per worktree, with which a session could be started.
+//!
+//! After a broken edit of `.zed/debug.json`, it's useful to get back to a configuration which
+//! is known to work. The effective configuration of the last started session, with all
+//! defaults and fallbacks of the extension applied, is kept in the extension state for that.
+
+use zed_extension_api::serde_json::{Map, Value, json};
+
+use crate::state::State;
+
+const STATE_KEY: &str = "lastSessions";
+
+/// Remember the configuration of a session which was started in the given worktree.
+///
+/// `adapter` describes how the debug adapter was started, for showing it along with the
+/// configuration.
+pub fn record(worktree_root: &str, label: &str, config: &Value, adapter: &Value) {
+    let mut state = State::load();
+
+    state.section_mut(STATE_KEY).insert(
+        worktree_root.to_string(),
+        json!({ "label": label, "config": config, "adapter": adapter }),
+    );
+
+    if let Err(err) = state.save() {
+        log!("{err}");
+    }
+}
+
+/// The label and configuration of the last session started in the worktree.
+pub fn last(worktree_root: &str) -> Option<(String, Value)> {
+    last_from_state(&State::load(), worktree_root)
+}
+
+fn last_from_state(state: &State, worktree_root: &str) -> Option<(String, Value)> {
+    let session = state.section(STATE_KEY)?.get(worktree_root)?;
+
+    let label = session.get("label").and_then(Value::as_str)?;
+    let config = session.get("config").filter(|config| config.is_object())?;
+
+    Some((label.to_string(), config.clone()))
+}
+
+/// How the debug adapter of the last session in the worktree was started.
+pub fn last_adapter(worktree_root: &str) -> Option<Value> {
+    State::load()
+        .section(STATE_KEY)?
+        .get(worktree_root)?
+        .get("adapter")
+        .filter(|adapter| adapter.is_object())
+        .cloned()
+}
+
+/// A scenario for `.zed/debug.json` which repeats the session.
+pub fn scenario(label: &str, config: &Value) -> Value {
+    let mut scenario = Map::new();
+    scenario.insert(
+        "label".to_string(),
+        format!("{label} (last session)").into(),
+    );
+    scenario.insert("adapter".to_string(), crate::ADAPTER_NAME.into());
+
+    if let Some(config) = config.as_object() {
+        for (key, value) in config {
+            scenario.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+
+    Value::Object(scenario)
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    use crate::state::State;
+
+    #[test]
+    fn last_from_state() {
+        let mut state = State::default();
+        state.section_mut(super::STATE_KEY).insert(
+            "/project".to_string(),
+            json!({ "label": "Debug", "config": { "request": "launch" } }),
+        );
+
+        assert_eq!(
+            super::last_from_state(&state, "/project"),
+            Some(("Debug".to_string(), json!({ "request": "launch" })))
+        );
+        assert_eq!(super::last_from_state(&state, "/other"), None);
+    }
+
+    #[test]
+    fn scenario() {
+        let config = json!({ "request": "launch", "chip": "rp2040", "label": "ignored" });
+
+        assert_eq!(
+            super::scenario("Debug", &config),
+            json!({
+                "label": "Debug (last session)",
+                "adapter": "probe-rs",
+                "request": "launch",
+                "chip": "rp2040"
+            })
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! Detection of programs which were built for the host instead of the target.
+//!
+//! Without a `--target` or a `build.target` in `.cargo/config.toml`, cargo builds for the host,
+//! and puts the program into `target/debug/` instead of `target/<triple>/debug/`. Flashing such
+//! a program fails in confusing ways, or worse, flashes garbage, so it's refused up front.
+
+/// Cargo profiles whose output directory is directly below `target` for host builds.
+const PROFILES: &[&str] = &["debug", "release"];
+
+/// Whether the program is in the output directory of a host build, or is a Windows executable.
+pub fn is_host_build(program: &str) -> bool {
+    let program = program.replace('\\', "/");
+
+    if program.to_ascii_lowercase().ends_with(".exe") {
+        return true;
+    }
+
+    let components: Vec<&str> = program.split('/').collect();
+
+    components
+        .windows(2)
+        .any(|pair| pair[0] == "target" && PROFILES.contains(&pair[1]))
+}
+
+/// The path the program has when it's built for the target with the given triple.
+fn cross_compiled_path(program: &str, triple: &str) -> String {
+    let separator = if program.contains('\\') { "\\" } else { "/" };
+    let program = program
+        .strip_suffix(".exe")
+        .or_else(|| program.strip_suffix(".EXE"))
+        .unwrap_or(program);
+
+    PROFILES
+        .iter()
+        .fold(program.to_string(), |program, profile| {
+            program.replacen(
+                &format!("target{separator}{profile}{separator}"),
+                &format!("target{separator}{triple}{separator}{profile}{separator}"),
+                1,
+            )
+        })
+}
+
+/// Refuse programs which were built for the host.
+///
+/// The `triple` is the build target of the project, if it's known, for suggesting the path of
+/// the cross-compiled program.
+pub fn check(program: &str, triple: Option<&str>) -> Result<(), String> {
+    if !is_host_build(program) {
+        return Ok(());
+    }
+
+    let suggestion = match triple {
+        Some(triple) => format!(
+            "Use the program built for '{triple}' instead, '{}'.",
+            cross_compiled_path(program, triple)
+        ),
+        None => format!(
+            "Build with `--target <target triple>`, or set `target` in the `[build]` section of \
+             `.cargo/config.toml`, and use the program in '{}'.",
+            cross_compiled_path(program, "<target triple>")
+        ),
+    };
+
+    Err(format!(
+        "'{program}' was built for the host computer, not for the target. Cargo puts firmware \
+         into `target/<target triple>/`. {suggestion}"
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn is_host_build() {
+        assert!(super::is_host_build("target/debug/blinky"));
+        assert!(super::is_host_build(
+            "/home/me/fw/target/release/examples/blinky"
+        ));
+        assert!(super::is_host_build("C:\\fw\\target\\debug\\blinky.exe"));
+
+        assert!(!super::is_host_build(
+            "target/thumbv7em-none-eabihf/debug/blinky"
+        ));
+        assert!(!super::is_host_build(
+            "/home/me/target/fw/target/riscv32imc-unknown-none-elf/release/app"
+        ));
+        assert!(!super::is_host_build("build/zephyr/zephyr.elf"));
+    }
+
+    #[test]
+    fn cross_compiled_path() {
+        assert_eq!(
+            super::cross_compiled_path("target/debug/blinky", "thumbv6m-none-eabi"),
+            "target/thumbv6m-none-eabi/debug/blinky"
+        );
+        assert_eq!(
+            super::cross_compiled_path("C:\\fw\\target\\release\\app.exe", "thumbv7em-none-eabihf"),
+            "C:\\fw\\target\\thumbv7em-none-eabihf\\release\\app"
+        );
+    }
+
+    #[test]
+    fn check() {
+        assert!(super::check("target/thumbv6m-none-eabi/debug/blinky", None).is_ok());
+
+        let err = super::check("target/debug/blinky", Some("thumbv6m-none-eabi")).unwrap_err();
+        assert!(err.starts_with("'target/debug/blinky' was built for the host computer"));
+        assert!(err.ends_with("'target/thumbv6m-none-eabi/debug/blinky'."));
+
+        let err = super::check("target/debug/blinky", None).unwrap_err();
+        assert!(err.contains("'target/<target triple>/debug/blinky'"));
+    }
+}
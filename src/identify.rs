@@ -0,0 +1,196 @@
+//! Identification of the connected target, printed when a session starts.
+//!
+//! When several boards of the same family are connected, it's easy to debug the wrong one.
+//! With `identify` enabled, the identification registers of the chip are read using
+//! `probe-rs read` before the session starts, so the log shows which board was used.
+
+use std::fmt::Write;
+
+use zed_extension_api::serde_json::Value;
+
+/// How the value of a register is shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    /// Little-endian number in hex.
+    Hex,
+    /// Bytes in hex, like a serial number.
+    Bytes,
+    /// Little-endian number of KiB.
+    KiB,
+}
+
+#[derive(Debug, PartialEq)]
+struct Register {
+    name: &'static str,
+    address: u64,
+    length: u64,
+    format: Format,
+}
+
+const fn register(name: &'static str, address: u64, length: u64, format: Format) -> Register {
+    Register {
+        name,
+        address,
+        length,
+        format,
+    }
+}
+
+/// Identification registers by chip name prefix.
+const REGISTERS: &[(&str, &[Register])] = &[
+    (
+        "nrf52",
+        &[
+            register("Part", 0x1000_0100, 4, Format::Hex),
+            register("Device ID", 0x1000_0060, 8, Format::Bytes),
+            register("Flash size", 0x1000_0110, 4, Format::KiB),
+        ],
+    ),
+    (
+        "stm32f1",
+        &[
+            register("ID code", 0xE004_2000, 4, Format::Hex),
+            register("Unique ID", 0x1FFF_F7E8, 12, Format::Bytes),
+            register("Flash size", 0x1FFF_F7E0, 2, Format::KiB),
+        ],
+    ),
+    (
+        "stm32f4",
+        &[
+            register("ID code", 0xE004_2000, 4, Format::Hex),
+            register("Unique ID", 0x1FFF_7A10, 12, Format::Bytes),
+            register("Flash size", 0x1FFF_7A22, 2, Format::KiB),
+        ],
+    ),
+    (
+        "stm32l4",
+        &[
+            register("ID code", 0xE004_2000, 4, Format::Hex),
+            register("Unique ID", 0x1FFF_7590, 12, Format::Bytes),
+            register("Flash size", 0x1FFF_75E0, 2, Format::KiB),
+        ],
+    ),
+];
+
+fn registers(chip: &str) -> &'static [Register] {
+    let chip = chip.to_ascii_lowercase();
+
+    REGISTERS
+        .iter()
+        .find(|(prefix, _)| chip.starts_with(prefix))
+        .map_or(&[], |(_, registers)| registers)
+}
+
+fn format_value(format: Format, bytes: &[u8]) -> String {
+    let number = || {
+        bytes
+            .iter()
+            .rev()
+            .fold(0u64, |value, &byte| (value << 8) | u64::from(byte))
+    };
+
+    match format {
+        Format::Hex => format!("{:#0width$x}", number(), width = bytes.len() * 2 + 2),
+        Format::Bytes => bytes.iter().fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        }),
+        Format::KiB => format!("{} KiB", number()),
+    }
+}
+
+/// Read the identification registers of the target and print them.
+///
+/// Failures are only reported, a session shouldn't fail because the banner couldn't be read.
+pub fn print_banner(probe_rs: &str, config: &Value) {
+    let Some(chip) = config.get("chip").and_then(Value::as_str) else {
+        log!("Can't identify the target, no chip is configured");
+        return;
+    };
+
+    let registers = registers(chip);
+
+    if registers.is_empty() {
+        log!("Identification of '{chip}' targets is not supported");
+        return;
+    }
+
+    let session_args = match crate::config::session_args(config) {
+        Ok(args) => args,
+        Err(err) => {
+            log!("Can't identify the target: {err}");
+            return;
+        }
+    };
+
+    let mut values = Vec::new();
+
+    for register in registers {
+        let mut args = vec!["read".to_string()];
+        args.extend(session_args.iter().cloned());
+        args.extend([
+            "b8".to_string(),
+            format!("{:#x}", register.address),
+            register.length.to_string(),
+        ]);
+
+        let value = crate::process::run(probe_rs, &args)
+            .and_then(|output| crate::commands::dump::parse_read_output(&output));
+
+        match value {
+            Ok(bytes) => values.push((register.name, format_value(register.format, &bytes))),
+            Err(err) => log!("Failed to read the {} of the target: {err}", register.name),
+        }
+    }
+
+    log!("{}", banner(chip, &values));
+}
+
+fn banner(chip: &str, values: &[(&str, String)]) -> String {
+    let mut banner = format!("Target {chip}");
+
+    for (name, value) in values {
+        let _ = write!(banner, "\n  {name}: {value}");
+    }
+
+    banner
+}
+
+#[cfg(test)]
+mod test {
+    use super::Format;
+
+    #[test]
+    fn registers() {
+        assert_eq!(super::registers("nRF52840_xxAA").len(), 3);
+        assert_eq!(super::registers("STM32F401RETx")[1].address, 0x1FFF_7A10);
+        assert!(super::registers("rp2040").is_empty());
+    }
+
+    #[test]
+    fn format_value() {
+        assert_eq!(
+            super::format_value(Format::Hex, &[0x13, 0x64, 0x00, 0x10]),
+            "0x10006413"
+        );
+        assert_eq!(
+            super::format_value(Format::Bytes, &[0x36, 0x00, 0x4a, 0x01]),
+            "36004a01"
+        );
+        assert_eq!(super::format_value(Format::KiB, &[0x00, 0x02]), "512 KiB");
+    }
+
+    #[test]
+    fn banner() {
+        assert_eq!(
+            super::banner(
+                "STM32F401RETx",
+                &[
+                    ("ID code", "0x10006433".to_string()),
+                    ("Flash size", "512 KiB".to_string())
+                ]
+            ),
+            "Target STM32F401RETx\n  ID code: 0x10006433\n  Flash size: 512 KiB"
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Flashing of additional images before the debug session starts.
+//!
+//! probe-rs only flashes a single program binary per core as part of a debug session.
+//! Products using a bootloader (e.g. MCUboot) need the bootloader flashed as well, so the
+//! `additionalImages` option lists images which the extension flashes using `probe-rs download`
+//! before the session is started.
+
+use zed_extension_api::serde_json::Value;
+
+const FORMATS: &[&str] = &["Elf", "Hex", "Bin", "Idf", "Uf2"];
+
+#[derive(Debug, PartialEq)]
+pub struct Image {
+    path: String,
+    format: Option<String>,
+    base_address: Option<u64>,
+    skip: Option<u64>,
+}
+
+impl Image {
+    fn parse(index: usize, value: &Value) -> Result<Self, String> {
+        let path = value
+            .get("path")
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("additionalImages[{index}]: missing 'path'"))?;
+
+        let format = match value.get("format").and_then(Value::as_str) {
+            Some(format) if FORMATS.contains(&format) => Some(format.to_string()),
+            Some(format) => {
+                return Err(format!(
+                    "additionalImages[{index}]: unsupported format '{format}', expected one of {}",
+                    FORMATS.join(", ")
+                ));
+            }
+            None => None,
+        };
+
+        let number = |field: &str| match value.get(field) {
+            None | Some(Value::Null) => Ok(None),
+            Some(number) => number.as_u64().map(Some).ok_or_else(|| {
+                format!(
+                    "additionalImages[{index}]: '{field}' must be a positive number, like \
+                     134217728 or \"0x08000000\""
+                )
+            }),
+        };
+
+        let base_address = number("baseAddress")?;
+        let skip = number("skip")?;
+
+        if (base_address.is_some() || skip.is_some()) && format.as_deref() != Some("Bin") {
+            return Err(format!(
+                "additionalImages[{index}]: 'baseAddress' and 'skip' are only supported for the 'Bin' format"
+            ));
+        }
+
+        Ok(Self {
+            path: path.to_string(),
+            format,
+            base_address,
+            skip,
+        })
+    }
+
+    /// Arguments for `probe-rs download`, without the session arguments like the chip.
+    pub fn download_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(format) = &self.format {
+            args.extend(["--binary-format".to_string(), format.to_lowercase()]);
+        }
+
+        if let Some(base_address) = self.base_address {
+            args.extend(["--base-address".to_string(), format!("{base_address:#x}")]);
+        }
+
+        if let Some(skip) = self.skip {
+            args.extend(["--skip".to_string(), skip.to_string()]);
+        }
+
+        args.push(self.path.clone());
+
+        args
+    }
+}
+
+/// Read the `additionalImages` list from the configuration.
+pub fn additional_images(config: &Value) -> Result<Vec<Image>, String> {
+    match config.get("additionalImages") {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::Array(images)) => images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| Image::parse(index, image))
+            .collect(),
+        Some(_) => Err("'additionalImages' must be a list of images".to_string()),
+    }
+}
+
+/// Flash all additional images listed in the configuration, in order.
+///
+/// Relative image paths are resolved against `cwd`, like the program binaries of the session.
+pub fn flash(probe_rs: &str, config: &Value, cwd: &str) -> Result<(), String> {
+    let images = additional_images(config)?;
+
+    if images.is_empty() {
+        return Ok(());
+    }
+
+    let session_args = crate::config::session_args(config)?;
+
+    for mut image in images {
+        image.path = crate::paths::join(Some(cwd), &image.path);
+
+        log!("Flashing additional image '{}'", image.path);
+        download(probe_rs, &session_args, &image, "additional image")?;
+    }
+
+    Ok(())
+}
+
+/// Flash the program binaries of the session, instead of letting probe-rs flash them.
+pub fn flash_programs(probe_rs: &str, config: &Value, programs: &[String]) -> Result<(), String> {
+    let session_args = crate::config::session_args(config)?;
+
+    for program in programs {
+        let image = Image {
+            path: program.clone(),
+            format: None,
+            base_address: None,
+            skip: None,
+        };
+
+        log!("Flashing program '{program}'");
+        download(probe_rs, &session_args, &image, "program")?;
+    }
+
+    Ok(())
+}
+
+fn download(
+    probe_rs: &str,
+    session_args: &[String],
+    image: &Image,
+    kind: &str,
+) -> Result<(), String> {
+    let mut args = vec!["download".to_string()];
+    args.extend(session_args.iter().cloned());
+    args.extend(image.download_args());
+
+    let result = crate::process::run(probe_rs, &args);
+    crate::statistics::record_flash(result.is_ok());
+
+    result.map(drop).map_err(|err| {
+        crate::errors::Category::of_probe_rs_error(&err).error(format_args!(
+            "Failed to flash {kind} '{}': {err}",
+            image.path
+        ))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    #[test]
+    fn additional_images() {
+        let config = json!({
+            "additionalImages": [
+                { "path": "bootloader.hex", "format": "Hex" },
+                { "path": "signed.bin", "format": "Bin", "baseAddress": 0x10000, "skip": 32 },
+                { "path": "app.elf" },
+            ]
+        });
+
+        let images = super::additional_images(&config).unwrap();
+
+        assert_eq!(
+            images[0].download_args(),
+            ["--binary-format", "hex", "bootloader.hex"]
+        );
+        assert_eq!(
+            images[1].download_args(),
+            [
+                "--binary-format",
+                "bin",
+                "--base-address",
+                "0x10000",
+                "--skip",
+                "32",
+                "signed.bin"
+            ]
+        );
+        assert_eq!(images[2].download_args(), ["app.elf"]);
+    }
+
+    #[test]
+    fn additional_images_missing() {
+        assert!(super::additional_images(&json!({})).unwrap().is_empty());
+    }
+
+    #[test]
+    fn additional_images_invalid() {
+        for config in [
+            json!({"additionalImages": "bootloader.hex"}),
+            json!({"additionalImages": [{ "format": "Hex" }]}),
+            json!({"additionalImages": [{ "path": "a", "format": "Srec" }]}),
+            json!({"additionalImages": [{ "path": "a", "format": "Hex", "baseAddress": 0 }]}),
+            json!({"additionalImages": [{ "path": "a", "format": "Bin", "skip": -1 }]}),
+        ] {
+            assert!(super::additional_images(&config).is_err(), "{config}");
+        }
+    }
+}
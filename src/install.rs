@@ -0,0 +1,1298 @@
+//! Installation of probe-rs from its GitHub releases.
+//!
+//! If probe-rs is not installed on the host, the release for the host platform is downloaded
+//! into the working directory of the extension, like other debugger extensions of Zed do. Each
+//! version is installed into its own directory in `cache/probe-rs/<version>/`, and reused by all
+//! worktrees, so a version is only downloaded once. Teams can pin the version with
+//! `adapterVersion`, so all of them debug with the same probe-rs.
+
+use std::{
+    fs,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use zed_extension_api::{self as zed, Architecture, DownloadedFileType, GithubRelease, Os};
+
+use crate::{server::Cli, version::Version};
+
+pub const REPOSITORY: &str = "probe-rs/probe-rs";
+
+/// Which releases are downloaded if no version is pinned.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Channel {
+    #[default]
+    Stable,
+    /// Pre-releases as well, for users tracking the development of probe-rs.
+    Prerelease,
+}
+
+impl Channel {
+    pub fn parse(channel: &str) -> Option<Self> {
+        match channel {
+            "stable" => Some(Channel::Stable),
+            "prerelease" => Some(Channel::Prerelease),
+            _ => None,
+        }
+    }
+}
+
+/// Directory containing a directory per installed version.
+pub const CACHE_DIRECTORY: &str = "cache/probe-rs";
+
+/// The crate containing probe-rs on crates.io, for building it with cargo.
+const CRATE_NAME: &str = "probe-rs-tools";
+
+/// How many of the downloaded versions are kept, see [`prune`].
+const KEPT_VERSIONS: usize = 3;
+
+/// File which marks an installation as verified and complete.
+const VERIFIED_MARKER: &str = ".verified";
+
+/// File which marks an installation as complete, of a release without a checksum to verify it.
+const UNVERIFIED_MARKER: &str = ".unverified";
+
+/// The C library of a Linux host, which decides which Linux binaries run on it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Libc {
+    Gnu,
+    Musl,
+}
+
+/// The target triples of release assets which run on a host, in order of preference.
+///
+/// Statically linked musl binaries also run on glibc hosts, but not the other way around.
+/// Windows on ARM runs x86-64 binaries through emulation, which is still better than no
+/// probe-rs at all. Apple Silicon is deliberately not given x86-64 binaries, they would only
+/// run if Rosetta is installed.
+const TARGET_TRIPLES: &[(Os, Architecture, Libc, &[&str])] = &[
+    (
+        Os::Linux,
+        Architecture::X8664,
+        Libc::Gnu,
+        &["x86_64-unknown-linux-gnu", "x86_64-unknown-linux-musl"],
+    ),
+    (
+        Os::Linux,
+        Architecture::X8664,
+        Libc::Musl,
+        &["x86_64-unknown-linux-musl"],
+    ),
+    (
+        Os::Linux,
+        Architecture::Aarch64,
+        Libc::Gnu,
+        &["aarch64-unknown-linux-gnu", "aarch64-unknown-linux-musl"],
+    ),
+    (
+        Os::Linux,
+        Architecture::Aarch64,
+        Libc::Musl,
+        &["aarch64-unknown-linux-musl"],
+    ),
+    (
+        Os::Mac,
+        Architecture::X8664,
+        Libc::Gnu,
+        &["x86_64-apple-darwin"],
+    ),
+    (
+        Os::Mac,
+        Architecture::Aarch64,
+        Libc::Gnu,
+        &["aarch64-apple-darwin"],
+    ),
+    (
+        Os::Windows,
+        Architecture::X8664,
+        Libc::Gnu,
+        &["x86_64-pc-windows-msvc"],
+    ),
+    (
+        Os::Windows,
+        Architecture::Aarch64,
+        Libc::Gnu,
+        &["aarch64-pc-windows-msvc", "x86_64-pc-windows-msvc"],
+    ),
+];
+
+/// The target triples for a host platform, see [`TARGET_TRIPLES`].
+///
+/// The C library only matters on Linux, other platforms use [`Libc::Gnu`].
+fn target_triples(
+    os: Os,
+    arch: Architecture,
+    libc: Libc,
+) -> Result<&'static [&'static str], String> {
+    let libc = if os == Os::Linux { libc } else { Libc::Gnu };
+
+    TARGET_TRIPLES
+        .iter()
+        .find(|entry| (entry.0, entry.1, entry.2) == (os, arch, libc))
+        .map(|entry| entry.3)
+        .ok_or_else(|| {
+            format!(
+                "There are no probe-rs releases for {os:?} on {arch:?}, install probe-rs manually"
+            )
+        })
+}
+
+/// The target triples for the host the extension runs on.
+fn host_triples() -> Result<(Os, &'static [&'static str]), String> {
+    let (os, arch) = zed::current_platform();
+    let libc = if os == Os::Linux {
+        host_libc()
+    } else {
+        Libc::Gnu
+    };
+
+    Ok((os, target_triples(os, arch, libc)?))
+}
+
+/// The C library of the Linux host, detected once using `ldd --version`.
+///
+/// Zed only reports the OS and architecture to extensions. The `ldd` of glibc names itself
+/// "GNU libc" or "GLIBC", the one of musl prints "musl libc" and fails. If `ldd` can't be run,
+/// glibc is assumed, since almost all desktop distributions use it.
+fn host_libc() -> Libc {
+    static LIBC: OnceLock<Libc> = OnceLock::new();
+
+    *LIBC.get_or_init(|| {
+        crate::process::run_with_status("ldd", &["--version".to_string()])
+            .map(|output| parse_libc(&format!("{}{}", output.stdout, output.stderr)))
+            .unwrap_or(Libc::Gnu)
+    })
+}
+
+/// The C library named in the output of `ldd --version`.
+fn parse_libc(output: &str) -> Libc {
+    if output.to_lowercase().contains("musl") {
+        Libc::Musl
+    } else {
+        Libc::Gnu
+    }
+}
+
+/// The most preferred of the `triples` for which the release has an asset.
+fn select_triple<'a>(os: Os, triples: &[&'a str], assets: &[String]) -> Option<&'a str> {
+    triples
+        .iter()
+        .find(|triple| assets.contains(&asset_name(os, triple)))
+        .copied()
+}
+
+/// The name of the release asset for a target.
+fn asset_name(os: Os, triple: &str) -> String {
+    let extension = if os == Os::Windows { "zip" } else { "tar.xz" };
+
+    format!("probe-rs-tools-{triple}.{extension}")
+}
+
+/// The places where the binary can be in an extracted release, relative to its directory.
+///
+/// The archives for Unix contain a directory named like the asset, the ones for Windows
+/// contain the files directly. Versions built with cargo have the binary in `bin/`.
+fn binary_candidates(os: Os, triple: &str) -> [String; 3] {
+    let binary = if os == Os::Windows {
+        "probe-rs.exe"
+    } else {
+        "probe-rs"
+    };
+
+    [
+        format!("probe-rs-tools-{triple}/{binary}"),
+        binary.to_string(),
+        format!("bin/{binary}"),
+    ]
+}
+
+/// The binary in the installation directory of a version, if it was installed completely.
+fn find_binary(directory: &str, os: Os, triples: &[&str]) -> Option<String> {
+    [VERIFIED_MARKER, UNVERIFIED_MARKER]
+        .iter()
+        .find_map(|marker| fs::metadata(format!("{directory}/{marker}")).ok())?;
+
+    triples
+        .iter()
+        .find_map(|triple| extracted_binary(directory, os, triple))
+}
+
+/// The binary in an extracted release.
+///
+/// If it's not in one of the known places, the directories of the release are searched as
+/// well, so a release with a differently named top-level directory still works.
+fn extracted_binary(directory: &str, os: Os, triple: &str) -> Option<String> {
+    let is_file = |binary: &String| fs::metadata(binary).is_ok_and(|metadata| metadata.is_file());
+
+    let candidates = binary_candidates(os, triple);
+    if let Some(binary) = candidates
+        .iter()
+        .map(|binary| format!("{directory}/{binary}"))
+        .find(is_file)
+    {
+        return Some(binary);
+    }
+
+    // The second candidate is the name of the binary.
+    let name = candidates[1].as_str();
+
+    fs::read_dir(directory)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .map(|subdirectory| format!("{directory}/{subdirectory}/{name}"))
+        .find(is_file)
+}
+
+/// The installed versions, with the name of their directory.
+fn installed_versions() -> Vec<(Version, String)> {
+    let Ok(entries) = fs::read_dir(CACHE_DIRECTORY) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter_map(|name| Some((Version::parse(&name)?, directory_name(&name))))
+        .collect()
+}
+
+/// The directories of the versions which are removed after installing `current`.
+///
+/// The newest versions are kept, so going back to the previous version doesn't need another
+/// download, and the version which was just installed is always kept, even if it's older
+/// because it was pinned.
+fn prunable(mut versions: Vec<(Version, String)>, current: &str) -> Vec<String> {
+    versions.sort();
+
+    versions
+        .into_iter()
+        .rev()
+        .skip(KEPT_VERSIONS)
+        .map(|(_, directory)| directory)
+        .filter(|directory| directory != current)
+        .collect()
+}
+
+/// Remove older versions, so the directory of the extension doesn't grow with every release.
+///
+/// Versions which are still running, for example with another session on Windows, can't be
+/// removed, and are removed after a later installation instead.
+fn prune(current: &str) {
+    for directory in prunable(installed_versions(), current) {
+        match fs::remove_dir_all(&directory) {
+            Ok(()) => log!("Removed the old probe-rs version in '{directory}'"),
+            Err(err) => log!("Failed to remove the old probe-rs version in '{directory}': {err}"),
+        }
+    }
+}
+
+/// The newest installed probe-rs binary, as an absolute path.
+pub fn installed() -> Option<String> {
+    let (os, triples) = host_triples().ok()?;
+
+    let mut versions = installed_versions();
+    versions.sort();
+
+    versions
+        .iter()
+        .rev()
+        .find_map(|(_, directory)| find_binary(directory, os, triples))
+        .and_then(|binary| crate::paths::in_work_dir(&binary).ok())
+}
+
+/// The installed binary of exactly the given version, as an absolute path.
+pub fn cached(version: &str) -> Option<String> {
+    let (os, triples) = host_triples().ok()?;
+
+    find_binary(&directory_name(version), os, triples)
+        .and_then(|binary| crate::paths::in_work_dir(&binary).ok())
+}
+
+/// The directory into which a version is installed.
+fn directory_name(version: &str) -> String {
+    format!("{CACHE_DIRECTORY}/{}", release_version(version))
+}
+
+/// The version as probe-rs tags its releases, so a pinned `0.24` or `v0.24` is `0.24.0`. A
+/// pre-release suffix like `-alpha.1` is kept, and a version that doesn't parse is only
+/// stripped of its `v`.
+fn release_version(version: &str) -> String {
+    let version = version.trim().trim_start_matches('v');
+    let suffix = version
+        .find(['-', '+'])
+        .map_or("", |start| &version[start..]);
+
+    match Version::parse(version) {
+        Some(parsed) => format!("{parsed}{suffix}"),
+        None => version.to_string(),
+    }
+}
+
+/// The version of a probe-rs binary, according to `probe-rs --version`.
+pub fn version(binary: &str) -> Option<Version> {
+    version_output(binary).and_then(|output| Version::from_version_output(&output))
+}
+
+/// The output of `probe-rs --version`, like `probe-rs 0.27.0 (git commit: 1a2b3c4)`.
+pub fn version_output(binary: &str) -> Option<String> {
+    crate::process::run(binary, &["--version".to_string()])
+        .ok()
+        .map(|output| output.trim().to_string())
+        .filter(|output| !output.is_empty())
+}
+
+/// The line logged at the start of a session about the debug adapter which is used, so bug
+/// reports contain it.
+///
+/// `source` tells how the binary was found, and `version_output` is what it printed for
+/// `--version`. Values are quoted, so the line stays on one line.
+pub fn adapter_summary(binary: &str, source: &str, version_output: Option<&str>) -> String {
+    let version =
+        version_output.map_or_else(|| "unknown".to_string(), |output| format!("{output:?}"));
+
+    format!("Debug adapter: path={binary:?} source={source} version={version}")
+}
+
+/// The command line interface of a probe-rs binary, from its help.
+///
+/// If the help can't be shown, the current interface is assumed.
+pub fn cli(binary: &str) -> Cli {
+    crate::process::run_with_status(binary, &["--help".to_string()])
+        .map_or(Cli::DapServer, |output| {
+            Cli::from_help(&format!("{}\n{}", output.stdout, output.stderr))
+        })
+}
+
+/// Check that a configured probe-rs binary can be run, and return the output of `--version`.
+///
+/// `source` tells where the binary was configured, like `the debugger settings of Zed`.
+///
+/// Without this, a wrong path only shows up as a timeout when Zed connects to the dap-server
+/// which never started.
+pub fn check_configured(binary: &str, source: &str) -> Result<String, String> {
+    check_configured_path(binary, source)?;
+
+    let output = crate::process::run_with_status(binary, &["--version".to_string()])
+        .map_err(|err| configured_error(binary, source, &err))?;
+
+    if Version::from_version_output(&output.stdout).is_none() {
+        log!("The configured debug adapter '{binary}' didn't report a probe-rs version");
+    }
+
+    Ok(output.stdout.trim().to_string())
+}
+
+/// The checks of a configured path which don't need to run it.
+fn check_configured_path(binary: &str, source: &str) -> Result<(), String> {
+    if binary.trim().is_empty() {
+        return Err(configured_error(binary, source, "the path is empty"));
+    }
+
+    if binary.ends_with(['/', '\\']) {
+        return Err(configured_error(binary, source, "the path is a directory"));
+    }
+
+    Ok(())
+}
+
+fn configured_error(binary: &str, source: &str, reason: &str) -> String {
+    format!(
+        "The probe-rs binary '{binary}' configured in {source} can't be run: \
+         {reason}. Check that the file exists and is executable, or remove the setting to use \
+         the probe-rs on the PATH"
+    )
+}
+
+/// Whether a probe-rs binary has the given version.
+pub fn has_version(binary: &str, version: &str) -> bool {
+    Version::parse(version).is_some_and(|version| self::version(binary) == Some(version))
+}
+
+/// The latest release in the channel.
+///
+/// The releases of GitHub are either looked up among the pre-releases or the stable releases.
+/// probe-rs only publishes pre-releases now and then, so the latest pre-release may be older
+/// than the latest stable release, which is used then. `token` is a GitHub token for the
+/// lookups, see [`crate::github`].
+pub fn latest_release(channel: Channel, token: Option<&str>) -> Result<GithubRelease, String> {
+    let latest = |pre_release| crate::github::latest_release(REPOSITORY, pre_release, token);
+
+    let stable = latest(false);
+
+    if channel == Channel::Prerelease
+        && let Ok(prerelease) = latest(true)
+        && stable
+            .as_ref()
+            .ok()
+            .is_none_or(|stable| is_newer(&prerelease.version, &stable.version))
+    {
+        return Ok(prerelease);
+    }
+
+    stable.map_err(|err| format!("Failed to find the latest probe-rs release: {err}"))
+}
+
+/// Whether a pre-release is newer than a stable release.
+///
+/// The final release of a version comes after its pre-releases, so for the same version the
+/// stable release is preferred.
+fn is_newer(prerelease: &str, stable: &str) -> bool {
+    match (Version::parse(prerelease), Version::parse(stable)) {
+        (Some(prerelease), Some(stable)) => prerelease > stable,
+        (prerelease, _) => prerelease.is_some(),
+    }
+}
+
+/// Install probe-rs, returning the absolute path of the binary.
+///
+/// With a `pinned` version, exactly that release is used. Otherwise an installed version is
+/// used without checking for a newer release, and the latest release of the `channel` is
+/// installed if there is none. With a `mirror`, the release is downloaded from there instead
+/// of GitHub, see [`mirror_url`].
+pub fn install(
+    pinned: Option<&str>,
+    channel: Channel,
+    mirror: Option<&str>,
+    token: Option<&str>,
+    allow_unverified: bool,
+) -> Result<String, String> {
+    let installed = match pinned {
+        Some(version) => cached(version),
+        None => installed(),
+    };
+
+    if let Some(binary) = installed {
+        return Ok(binary);
+    }
+
+    let (os, triples) = host_triples()?;
+
+    if let Some(mirror) = mirror {
+        return install_from_mirror(mirror, pinned, os, triples);
+    }
+
+    let release = match pinned {
+        Some(version) => {
+            let tag = format!("v{}", release_version(version));
+            crate::github::release_by_tag(REPOSITORY, &tag, token)
+                .map_err(|err| format!("Failed to find the probe-rs release {tag}: {err}"))?
+        }
+        None => latest_release(channel, token)?,
+    };
+
+    let names: Vec<String> = release
+        .assets
+        .iter()
+        .map(|asset| asset.name.clone())
+        .collect();
+    let available: Vec<&str> = triples
+        .iter()
+        .copied()
+        .filter(|triple| select_triple(os, &[triple], &names).is_some())
+        .collect();
+    if available.is_empty() {
+        return Err(format!(
+            "The probe-rs release {} has no binary for this host, expected one of: {}",
+            release.version,
+            triples.join(", ")
+        ));
+    }
+
+    let directory = directory_name(&release.version);
+
+    for (index, triple) in available.iter().enumerate() {
+        let name = asset_name(os, triple);
+        let asset = release
+            .assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .expect("the asset was selected from the release");
+
+        let checksum_name = format!("{name}.sha256");
+        let checksum_url = release
+            .assets
+            .iter()
+            .find(|asset| asset.name == checksum_name)
+            .map(|asset| asset.download_url.as_str());
+        check_unverified(&release.version, checksum_url, allow_unverified)?;
+
+        let verified = download(
+            os,
+            &release.version,
+            &asset.download_url,
+            checksum_url,
+            &directory,
+        )?;
+
+        let binary = complete(&directory, os, triple, &release.version, verified)?;
+
+        if let Some(next) = available.get(index + 1)
+            && let Some(err) = loader_error(os, &binary)
+        {
+            log!("{}", fallback_notice(triple, next, &err));
+            fs::remove_dir_all(&directory).ok();
+            continue;
+        }
+
+        return Ok(binary);
+    }
+
+    unreachable!("the last available build is always returned")
+}
+
+/// Messages of the dynamic loader of Linux about a binary it can't load, either because the
+/// glibc of the host is too old, or because the loader itself is missing.
+const LOADER_ERRORS: &[&str] = &[
+    "glibc_",
+    "no such file or directory",
+    "cannot execute",
+    "required by",
+];
+
+/// Whether running a binary failed because the dynamic loader couldn't load it.
+fn is_loader_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+
+    LOADER_ERRORS.iter().any(|error| message.contains(error))
+}
+
+/// Why the installed binary can't be run on this Linux host, if the loader refused it.
+///
+/// The glibc builds need the glibc of a recent distribution, so older ones like Debian
+/// oldstable get the statically linked musl build instead.
+fn loader_error(os: Os, binary: &str) -> Option<String> {
+    if os != Os::Linux {
+        return None;
+    }
+
+    match crate::process::run_with_status(binary, &["--version".to_string()]) {
+        Ok(output) if output.status == Some(0) => None,
+        Ok(output) => is_loader_error(&output.stderr).then(|| output.stderr.trim().to_string()),
+        Err(err) => is_loader_error(&err).then_some(err),
+    }
+}
+
+fn fallback_notice(triple: &str, next: &str, err: &str) -> String {
+    format!(
+        "The {triple} build of probe-rs can't run on this host, probably because its glibc is \
+         too old ({err}). Using the {next} build instead"
+    )
+}
+
+/// Install a pinned version from a mirror of the releases.
+///
+/// Without GitHub, there is no list of assets, so the archives for the host are tried in order
+/// of preference. The mirror has to provide the checksum files next to the archives.
+fn install_from_mirror(
+    mirror: &str,
+    pinned: Option<&str>,
+    os: Os,
+    triples: &[&str],
+) -> Result<String, String> {
+    let version = pinned.ok_or_else(|| {
+        "Downloading from 'downloadMirror' needs a pinned 'adapterVersion', the latest release can't be looked up without GitHub".to_string()
+    })?;
+    let directory = directory_name(version);
+
+    let mut errors = Vec::new();
+
+    for (index, triple) in triples.iter().enumerate() {
+        let name = asset_name(os, triple);
+        let url = mirror_url(mirror, version, &name)?;
+        let checksum_url = mirror_url(mirror, version, &format!("{name}.sha256"))?;
+
+        if let Err(err) = download(os, version, &url, Some(&checksum_url), &directory) {
+            errors.push(err);
+            continue;
+        }
+
+        let binary = complete(&directory, os, triple, version, true)?;
+
+        if let Some(next) = triples.get(index + 1)
+            && let Some(err) = loader_error(os, &binary)
+        {
+            log!("{}", fallback_notice(triple, next, &err));
+            fs::remove_dir_all(&directory).ok();
+            continue;
+        }
+
+        return Ok(binary);
+    }
+
+    Err(errors.join("\n"))
+}
+
+/// The URL of a release asset on a mirror.
+///
+/// The template contains `{asset}` for the file name, and `{version}` or `{tag}` for the
+/// version, without or with the `v` prefix of the release tags.
+fn mirror_url(template: &str, version: &str, asset: &str) -> Result<String, String> {
+    if !template.contains("{asset}") {
+        return Err(format!(
+            "'downloadMirror' must contain {{asset}} for the file name, got '{template}'"
+        ));
+    }
+
+    let version = release_version(version);
+
+    Ok(template
+        .replace("{asset}", asset)
+        .replace("{version}", &version)
+        .replace("{tag}", &format!("v{version}")))
+}
+
+/// Build probe-rs with cargo, for hosts without a release binary.
+///
+/// With `binstall`, `cargo binstall` is used, which downloads a binary from elsewhere if it
+/// can find one, and builds it otherwise. Building takes several minutes and needs the native
+/// dependencies of probe-rs, like libudev on Linux. The version is only known after building,
+/// so an unpinned build is moved into the directory of its version afterwards.
+pub fn build(cargo: &str, binstall: bool, pinned: Option<&str>) -> Result<String, String> {
+    let directory = match pinned {
+        Some(version) => directory_name(version),
+        None => format!("{CACHE_DIRECTORY}/build"),
+    };
+
+    fs::remove_dir_all(&directory).ok();
+    fs::create_dir_all(&directory)
+        .map_err(|err| format!("Failed to create the directory '{directory}': {err}"))?;
+
+    let root = crate::paths::in_work_dir(&directory)?;
+    log!("Building probe-rs with cargo into {root}, this takes a few minutes");
+
+    let result = crate::process::run(cargo, &cargo_args(binstall, &root, pinned))
+        .map_err(|err| format!("Building probe-rs with cargo failed: {err}"))
+        .and_then(|_| built(&directory, pinned));
+
+    if result.is_err() {
+        fs::remove_dir_all(&directory).ok();
+    }
+
+    result
+}
+
+/// The arguments of cargo for installing probe-rs into `root`.
+fn cargo_args(binstall: bool, root: &str, pinned: Option<&str>) -> Vec<String> {
+    let mut args = if binstall {
+        vec!["binstall", CRATE_NAME, "--no-confirm"]
+    } else {
+        vec!["install", CRATE_NAME]
+    };
+    args.extend(["--locked", "--root", root]);
+
+    if let Some(version) = pinned {
+        args.extend(["--version", version.trim_start_matches('v')]);
+    }
+
+    args.into_iter().map(str::to_string).collect()
+}
+
+/// Mark a version built with cargo as complete, after moving it into its version directory.
+fn built(directory: &str, pinned: Option<&str>) -> Result<String, String> {
+    let (os, _) = host_triples()?;
+    let name = if os == Os::Windows {
+        "probe-rs.exe"
+    } else {
+        "probe-rs"
+    };
+    let binary_in = |directory: &str| {
+        let binary = format!("{directory}/bin/{name}");
+        fs::metadata(&binary)
+            .is_ok_and(|metadata| metadata.is_file())
+            .then_some(binary)
+            .ok_or_else(|| "cargo didn't install a probe-rs binary".to_string())
+    };
+
+    let mut binary = binary_in(directory)?;
+    let mut directory = directory.to_string();
+
+    if pinned.is_none() {
+        let version = version(&binary)
+            .ok_or_else(|| format!("Failed to get the version of the built '{binary}'"))?;
+        let versioned = directory_name(&version.to_string());
+
+        fs::remove_dir_all(&versioned).ok();
+        fs::rename(&directory, &versioned)
+            .map_err(|err| format!("Failed to move the built probe-rs to '{versioned}': {err}"))?;
+        binary = binary_in(&versioned)?;
+        directory = versioned;
+    }
+
+    fs::write(format!("{directory}/{VERIFIED_MARKER}"), "")
+        .map_err(|err| format!("Failed to complete the installation of probe-rs: {err}"))?;
+    prune(&directory);
+
+    crate::paths::in_work_dir(&binary)
+}
+
+/// Make the extracted binary executable and mark the installation as complete, and as verified
+/// if its checksum was checked.
+fn complete(
+    directory: &str,
+    os: Os,
+    triple: &str,
+    version: &str,
+    verified: bool,
+) -> Result<String, String> {
+    let binary = extracted_binary(directory, os, triple).ok_or_else(|| {
+        format!("The probe-rs release {version} doesn't contain a probe-rs binary")
+    })?;
+    zed::make_file_executable(&binary)?;
+    let marker = if verified {
+        VERIFIED_MARKER
+    } else {
+        UNVERIFIED_MARKER
+    };
+    fs::write(format!("{directory}/{marker}"), "")
+        .map_err(|err| format!("Failed to complete the installation of probe-rs: {err}"))?;
+    log!("Installed probe-rs {version} into '{directory}'");
+    prune(directory);
+
+    crate::paths::in_work_dir(&binary)
+}
+
+/// Download a release archive, verify its checksum and extract it into `directory`.
+///
+/// Returns whether the checksum was verified, which isn't possible without a `checksum_url`.
+/// If anything fails, the directory is removed again, so a broken download is never used.
+///
+/// Zed doesn't report the progress of downloads, so each step is logged instead, and the log
+/// doesn't look stuck while a large archive is downloaded over a slow connection.
+fn download(
+    os: Os,
+    version: &str,
+    url: &str,
+    checksum_url: Option<&str>,
+    directory: &str,
+) -> Result<bool, String> {
+    // A previous download may have been interrupted.
+    fs::remove_dir_all(directory).ok();
+    fs::create_dir_all(directory)
+        .map_err(|err| format!("Failed to create '{directory}': {err}"))?;
+
+    let extension = if os == Os::Windows { "zip" } else { "tar.xz" };
+    let archive = format!("{directory}/probe-rs.{extension}");
+
+    log!("Downloading probe-rs {version} from {url}, this can take a while on slow connections");
+    let started = Instant::now();
+
+    let result = download_file(url, &archive)
+        .and_then(|()| {
+            let size = fs::metadata(&archive).map_or(0, |metadata| metadata.len());
+            log!("{}", downloaded_notice(version, size, started.elapsed()));
+            verify(&archive, checksum_url, directory)
+        })
+        .and_then(|verified| {
+            log!("Extracting probe-rs {version}");
+            extract(os, &archive, directory).map(|()| verified)
+        });
+
+    fs::remove_file(&archive).ok();
+    if result.is_err() {
+        fs::remove_dir_all(directory).ok();
+    }
+
+    result
+}
+
+/// How often a download is attempted before giving up.
+const DOWNLOAD_ATTEMPTS: u32 = 3;
+
+/// How long to wait before the first retry of a download, doubled for each further one.
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Download a file, retrying a failed download after a delay, since a flaky connection
+/// shouldn't fail the first session of the day.
+fn download_file(url: &str, path: &str) -> Result<(), String> {
+    retry(
+        || zed::download_file(url, path, DownloadedFileType::Uncompressed),
+        |attempt, delay, err| {
+            log!(
+                "Downloading '{url}' failed ({err}), retrying in {} s (attempt {} of {DOWNLOAD_ATTEMPTS})",
+                delay.as_secs(),
+                attempt + 1
+            );
+            fs::remove_file(path).ok();
+            std::thread::sleep(delay);
+        },
+    )
+    .map_err(|err| format!("Failed to download '{url}' after {DOWNLOAD_ATTEMPTS} attempts: {err}"))
+}
+
+/// Run `operation` up to [`DOWNLOAD_ATTEMPTS`] times, until it succeeds.
+///
+/// Before each retry, `wait` is called with the number of the failed attempt, the delay to
+/// wait and the error. The error of the last attempt is returned.
+fn retry(
+    mut operation: impl FnMut() -> Result<(), String>,
+    mut wait: impl FnMut(u32, Duration, &str),
+) -> Result<(), String> {
+    let mut delay = RETRY_DELAY;
+
+    for attempt in 1.. {
+        match operation() {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt == DOWNLOAD_ATTEMPTS => return Err(err),
+            Err(err) => wait(attempt, delay, &err),
+        }
+        delay *= 2;
+    }
+
+    unreachable!("the last attempt always returns")
+}
+
+fn downloaded_notice(version: &str, size: u64, elapsed: Duration) -> String {
+    format!(
+        "Downloaded probe-rs {version} ({:.1} MB) in {:.1} s, verifying it",
+        size as f64 / 1_000_000.0,
+        elapsed.as_secs_f64()
+    )
+}
+
+/// Refuse to install a release without a checksum, unless `allowUnverifiedDownloads` is set.
+fn check_unverified(
+    version: &str,
+    checksum_url: Option<&str>,
+    allow_unverified: bool,
+) -> Result<(), String> {
+    if checksum_url.is_some() || allow_unverified {
+        return Ok(());
+    }
+
+    Err(format!(
+        "The probe-rs release {version} has no checksum, so the download can't be verified. Set \
+         \"allowUnverifiedDownloads\": true in '{}' to install it anyway, or install probe-rs \
+         yourself",
+        crate::settings::SETTINGS_PATH
+    ))
+}
+
+/// Check the SHA-256 checksum of a downloaded archive against the one of the release.
+///
+/// Returns whether the checksum was checked, which only a release without a checksum skips. A
+/// given checksum which can't be downloaded, like one missing on a mirror, fails the download.
+fn verify(archive: &str, checksum_url: Option<&str>, directory: &str) -> Result<bool, String> {
+    let Some(checksum_url) = checksum_url else {
+        log!("The probe-rs release has no checksum, the download can't be verified");
+        return Ok(false);
+    };
+
+    let checksum_file = format!("{directory}/probe-rs.sha256");
+    download_file(checksum_url, &checksum_file).map_err(|err| {
+        format!("The checksum of probe-rs is needed to verify the download: {err}")
+    })?;
+
+    let contents = fs::read_to_string(&checksum_file)
+        .map_err(|err| format!("Failed to read the checksum of probe-rs: {err}"))?;
+    fs::remove_file(&checksum_file).ok();
+
+    let expected = parse_checksum(&contents)
+        .ok_or_else(|| format!("Invalid checksum file '{checksum_url}'"))?;
+
+    let data = fs::read(archive)
+        .map_err(|err| format!("Failed to read the downloaded '{archive}': {err}"))?;
+    let actual = crate::sha256::hex_digest(&data);
+
+    if actual != expected {
+        return Err(format!(
+            "The downloaded probe-rs is corrupted or incomplete, its SHA-256 checksum is {actual} instead of {expected}. Start the session again to retry the download."
+        ));
+    }
+
+    Ok(true)
+}
+
+/// The checksum from a checksum file like `<hash> *<file name>`.
+fn parse_checksum(contents: &str) -> Option<String> {
+    let checksum = contents.split_whitespace().next()?.to_ascii_lowercase();
+
+    (checksum.len() == 64 && checksum.chars().all(|c| c.is_ascii_hexdigit())).then_some(checksum)
+}
+
+/// Extract an archive using the host's tar, which also handles zip archives on Windows.
+///
+/// Zed can only extract gzip and zip archives, but the releases for Unix use xz.
+///
+/// Windows versions without tar extract the zip archive with PowerShell instead. The tar of
+/// Unix hosts needs `xz` for the archives, which minimal installations may lack.
+fn extract(os: Os, archive: &str, directory: &str) -> Result<(), String> {
+    let flags = if os == Os::Windows { "-xf" } else { "-xJf" };
+    let archive = crate::paths::in_work_dir(archive)?;
+    let directory = crate::paths::in_work_dir(directory)?;
+
+    let args = [
+        flags.to_string(),
+        archive.clone(),
+        "-C".to_string(),
+        directory.clone(),
+    ];
+
+    let err = match crate::process::run("tar", &args) {
+        Ok(_) => return Ok(()),
+        Err(err) => err,
+    };
+
+    if os != Os::Windows {
+        return Err(format!(
+            "Failed to extract probe-rs, check that tar and xz are installed: {err}"
+        ));
+    }
+
+    log!("Extracting probe-rs with tar failed, using PowerShell instead: {err}");
+    crate::process::run("powershell", &expand_archive_args(&archive, &directory))
+        .map(|_| ())
+        .map_err(|err| format!("Failed to extract probe-rs: {err}"))
+}
+
+/// The arguments of PowerShell for extracting a zip archive.
+fn expand_archive_args(archive: &str, directory: &str) -> Vec<String> {
+    // Single quotes in PowerShell strings are escaped by doubling them.
+    let quote = |path: &str| format!("'{}'", path.replace('\'', "''"));
+
+    // PowerShell joins the arguments after `-Command` into the command, they are passed
+    // separately to match the capabilities of the extension.
+    vec![
+        "-NoProfile".to_string(),
+        "-NonInteractive".to_string(),
+        "-Command".to_string(),
+        "Expand-Archive".to_string(),
+        "-LiteralPath".to_string(),
+        quote(archive),
+        "-DestinationPath".to_string(),
+        quote(directory),
+        "-Force".to_string(),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use zed_extension_api::{Architecture, Os};
+
+    use super::Libc;
+    use crate::version::Version;
+
+    #[test]
+    fn target_triples() {
+        assert_eq!(
+            super::target_triples(Os::Mac, Architecture::Aarch64, Libc::Gnu).unwrap(),
+            ["aarch64-apple-darwin"]
+        );
+        assert_eq!(
+            super::target_triples(Os::Linux, Architecture::Aarch64, Libc::Musl).unwrap(),
+            ["aarch64-unknown-linux-musl"]
+        );
+        assert_eq!(
+            super::target_triples(Os::Linux, Architecture::X8664, Libc::Gnu).unwrap()[0],
+            "x86_64-unknown-linux-gnu"
+        );
+        // The C library is ignored outside of Linux.
+        assert_eq!(
+            super::target_triples(Os::Windows, Architecture::X8664, Libc::Musl).unwrap(),
+            ["x86_64-pc-windows-msvc"]
+        );
+        assert!(super::target_triples(Os::Windows, Architecture::X86, Libc::Gnu).is_err());
+    }
+
+    #[test]
+    fn parse_libc() {
+        assert_eq!(
+            super::parse_libc("ldd (Ubuntu GLIBC 2.39-0ubuntu8) 2.39\nCopyright (C) 2024"),
+            Libc::Gnu
+        );
+        assert_eq!(
+            super::parse_libc("musl libc (x86_64)\nVersion 1.2.5\nDynamic Program Loader"),
+            Libc::Musl
+        );
+    }
+
+    #[test]
+    fn select_triple() {
+        let assets = [
+            "probe-rs-tools-x86_64-unknown-linux-gnu.tar.xz".to_string(),
+            "probe-rs-tools-x86_64-unknown-linux-gnu.tar.xz.sha256".to_string(),
+            "probe-rs-tools-aarch64-unknown-linux-gnu.tar.xz".to_string(),
+            "probe-rs-tools-x86_64-pc-windows-msvc.zip".to_string(),
+        ];
+
+        let triples = ["x86_64-unknown-linux-gnu", "x86_64-unknown-linux-musl"];
+        assert_eq!(
+            super::select_triple(Os::Linux, &triples, &assets),
+            Some("x86_64-unknown-linux-gnu")
+        );
+
+        // Windows on ARM falls back to the x86-64 release.
+        let triples = ["aarch64-pc-windows-msvc", "x86_64-pc-windows-msvc"];
+        assert_eq!(
+            super::select_triple(Os::Windows, &triples, &assets),
+            Some("x86_64-pc-windows-msvc")
+        );
+
+        // A musl host doesn't get the glibc release.
+        let triples = ["aarch64-unknown-linux-musl"];
+        assert_eq!(super::select_triple(Os::Linux, &triples, &assets), None);
+    }
+
+    #[test]
+    fn is_loader_error() {
+        assert!(super::is_loader_error(
+            "/ext/probe-rs/0.25.0/probe-rs: /lib/x86_64-linux-gnu/libc.so.6: version `GLIBC_2.34' \
+             not found (required by /ext/probe-rs/0.25.0/probe-rs)"
+        ));
+        assert!(super::is_loader_error(
+            "Failed to run '/ext/probe-rs': No such file or directory (os error 2)"
+        ));
+        assert!(!super::is_loader_error(
+            "error: unexpected argument '--version'"
+        ));
+    }
+
+    #[test]
+    fn asset_name() {
+        assert_eq!(
+            super::asset_name(Os::Linux, "x86_64-unknown-linux-gnu"),
+            "probe-rs-tools-x86_64-unknown-linux-gnu.tar.xz"
+        );
+        assert_eq!(
+            super::asset_name(Os::Windows, "x86_64-pc-windows-msvc"),
+            "probe-rs-tools-x86_64-pc-windows-msvc.zip"
+        );
+    }
+
+    #[test]
+    fn retry() {
+        let mut calls = 0;
+        let mut delays = Vec::new();
+        let result = super::retry(
+            || {
+                calls += 1;
+                if calls < 3 {
+                    Err(format!("timeout {calls}"))
+                } else {
+                    Ok(())
+                }
+            },
+            |attempt, delay, err| delays.push((attempt, delay.as_secs(), err.to_string())),
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            delays,
+            [
+                (1, 2, "timeout 1".to_string()),
+                (2, 4, "timeout 2".to_string())
+            ]
+        );
+
+        let mut calls = 0;
+        let result = super::retry(
+            || {
+                calls += 1;
+                Err(format!("timeout {calls}"))
+            },
+            |_, _, _| {},
+        );
+        assert_eq!(result, Err("timeout 3".to_string()));
+        assert_eq!(calls, super::DOWNLOAD_ATTEMPTS);
+    }
+
+    #[test]
+    fn downloaded_notice() {
+        assert_eq!(
+            super::downloaded_notice("0.25.0", 12_345_678, Duration::from_millis(4_250)),
+            "Downloaded probe-rs 0.25.0 (12.3 MB) in 4.2 s, verifying it"
+        );
+    }
+
+    #[test]
+    fn check_unverified() {
+        let url = Some("https://github.com/probe-rs/probe-rs/releases/probe-rs.tar.xz.sha256");
+
+        assert!(super::check_unverified("0.25.0", url, false).is_ok());
+        assert!(super::check_unverified("0.25.0", None, false).is_err());
+        assert!(super::check_unverified("0.25.0", None, true).is_ok());
+    }
+
+    #[test]
+    fn parse_checksum() {
+        let checksum = "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae";
+
+        assert_eq!(
+            super::parse_checksum(&format!(
+                "{} *probe-rs-tools-x86_64-unknown-linux-gnu.tar.xz\n",
+                checksum.to_uppercase()
+            ))
+            .as_deref(),
+            Some(checksum)
+        );
+        assert_eq!(super::parse_checksum("<html>Not Found</html>"), None);
+    }
+
+    #[test]
+    fn adapter_summary() {
+        assert_eq!(
+            super::adapter_summary(
+                "/home/me/.cargo/bin/probe-rs",
+                "cargo",
+                Some("probe-rs 0.27.0 (git commit: 1a2b3c4)")
+            ),
+            "Debug adapter: path=\"/home/me/.cargo/bin/probe-rs\" source=cargo \
+             version=\"probe-rs 0.27.0 (git commit: 1a2b3c4)\""
+        );
+        assert!(
+            super::adapter_summary("probe-rs", "PATH", Some("probe-rs 0.27.0\nfeatures: rtt"))
+                .ends_with(r#"version="probe-rs 0.27.0\nfeatures: rtt""#)
+        );
+        assert!(super::adapter_summary("probe-rs", "PATH", None).ends_with("version=unknown"));
+    }
+
+    #[test]
+    fn check_configured_path() {
+        let source = "the debugger settings of Zed";
+
+        assert!(super::check_configured_path("/usr/local/bin/probe-rs", source).is_ok());
+        assert!(super::check_configured_path("C:\\Tools\\probe-rs.exe", source).is_ok());
+
+        let err = super::check_configured_path(" ", source).unwrap_err();
+        assert!(err.contains("the path is empty"));
+
+        let err = super::check_configured_path("/opt/probe-rs/", source).unwrap_err();
+        assert!(err.starts_with(
+            "The probe-rs binary '/opt/probe-rs/' configured in the debugger settings of Zed"
+        ));
+    }
+
+    #[test]
+    fn is_newer() {
+        assert!(super::is_newer("v0.26.0-alpha.1", "v0.25.0"));
+        assert!(!super::is_newer("v0.25.0-alpha.1", "v0.25.0"));
+        assert!(!super::is_newer("v0.24.0-rc.1", "v0.25.0"));
+        assert!(!super::is_newer("nightly", "v0.25.0"));
+    }
+
+    #[test]
+    fn channel() {
+        assert_eq!(
+            super::Channel::parse("prerelease"),
+            Some(super::Channel::Prerelease)
+        );
+        assert_eq!(super::Channel::parse("nightly"), None);
+    }
+
+    #[test]
+    fn mirror_url() {
+        assert_eq!(
+            super::mirror_url(
+                "https://mirror.example.com/probe-rs/{tag}/{asset}",
+                "0.27.0",
+                "probe-rs-tools-x86_64-unknown-linux-gnu.tar.xz"
+            )
+            .unwrap(),
+            "https://mirror.example.com/probe-rs/v0.27.0/probe-rs-tools-x86_64-unknown-linux-gnu.tar.xz"
+        );
+        assert_eq!(
+            super::mirror_url("https://m/{version}/{asset}", "v0.27.0", "a.zip").unwrap(),
+            "https://m/0.27.0/a.zip"
+        );
+        assert!(super::mirror_url("https://m/probe-rs.tar.xz", "0.27.0", "a.zip").is_err());
+    }
+
+    #[test]
+    fn prunable() {
+        let versions = ["0.22.0", "0.25.0", "0.23.0", "0.24.0", "0.21.0"]
+            .map(|version| {
+                (
+                    Version::parse(version).unwrap(),
+                    super::directory_name(version),
+                )
+            })
+            .to_vec();
+
+        assert_eq!(
+            super::prunable(versions.clone(), "cache/probe-rs/0.25.0"),
+            ["cache/probe-rs/0.22.0", "cache/probe-rs/0.21.0"]
+        );
+
+        // A pinned older version which was just installed is kept.
+        assert_eq!(
+            super::prunable(versions, "cache/probe-rs/0.21.0"),
+            ["cache/probe-rs/0.22.0"]
+        );
+    }
+
+    #[test]
+    fn directory_name() {
+        assert_eq!(super::directory_name("v0.24.0"), "cache/probe-rs/0.24.0");
+        assert_eq!(super::directory_name("0.25.0"), "cache/probe-rs/0.25.0");
+        assert_eq!(super::directory_name("0.24"), "cache/probe-rs/0.24.0");
+    }
+
+    #[test]
+    fn release_version() {
+        assert_eq!(super::release_version("0.24"), "0.24.0");
+        assert_eq!(super::release_version("v0.24"), "0.24.0");
+        assert_eq!(super::release_version("0.27.0"), "0.27.0");
+        assert_eq!(super::release_version("0.25.0-alpha.1"), "0.25.0-alpha.1");
+        assert_eq!(super::release_version("nightly"), "nightly");
+    }
+
+    #[test]
+    fn expand_archive_args() {
+        assert_eq!(
+            super::expand_archive_args("C:\\Users\\O'Brien\\probe-rs.zip", "C:\\ext\\0.25.0")[3..],
+            [
+                "Expand-Archive",
+                "-LiteralPath",
+                "'C:\\Users\\O''Brien\\probe-rs.zip'",
+                "-DestinationPath",
+                "'C:\\ext\\0.25.0'",
+                "-Force"
+            ]
+        );
+    }
+
+    #[test]
+    fn binary_candidates() {
+        assert_eq!(
+            super::binary_candidates(Os::Windows, "x86_64-pc-windows-msvc")[1],
+            "probe-rs.exe"
+        );
+        assert_eq!(
+            super::binary_candidates(Os::Linux, "aarch64-unknown-linux-gnu")[0],
+            "probe-rs-tools-aarch64-unknown-linux-gnu/probe-rs"
+        );
+        assert_eq!(
+            super::binary_candidates(Os::Linux, "aarch64-unknown-linux-gnu")[2],
+            "bin/probe-rs"
+        );
+    }
+
+    #[test]
+    fn cargo_args() {
+        assert_eq!(
+            super::cargo_args(false, "/ext/probe-rs/0.25.0", Some("v0.25.0")),
+            [
+                "install",
+                "probe-rs-tools",
+                "--locked",
+                "--root",
+                "/ext/probe-rs/0.25.0",
+                "--version",
+                "0.25.0"
+            ]
+        );
+        assert_eq!(
+            super::cargo_args(true, "/ext/probe-rs/build", None),
+            [
+                "binstall",
+                "probe-rs-tools",
+                "--no-confirm",
+                "--locked",
+                "--root",
+                "/ext/probe-rs/build"
+            ]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! Parsing of JSON with comments and trailing commas, as used by Zed's configuration files.
+
+use zed_extension_api::serde_json::{self, Value};
+
+pub fn parse(contents: &str) -> Result<Value, String> {
+    serde_json::from_str(&strip(contents)).map_err(|err| err.to_string())
+}
+
+/// Remove comments and trailing commas, so the result can be parsed as regular JSON.
+fn strip(contents: &str) -> String {
+    let mut output = String::with_capacity(contents.len());
+    let mut chars = contents.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            output.push(c);
+            match c {
+                '\\' => output.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                output.push(c);
+            }
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        output.push(c);
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = None;
+                for c in chars.by_ref() {
+                    if previous == Some('*') && c == '/' {
+                        break;
+                    }
+                    previous = Some(c);
+                }
+            }
+            (']' | '}', _) => {
+                let trimmed = output.trim_end().len();
+                if output[..trimmed].ends_with(',') {
+                    output.truncate(trimmed - 1);
+                }
+                output.push(c);
+            }
+            _ => output.push(c),
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    #[test]
+    fn parse_with_comments() {
+        let contents = r#"
+            // The scenarios
+            [
+                {
+                    "label": "Debug // not a comment", /* inline */
+                    "chip": "nRF52840_xxAA",
+                    "escaped": "quote \" /* still a string */",
+                },
+            ]
+        "#;
+
+        assert_eq!(
+            super::parse(contents).unwrap(),
+            json!([{
+                "label": "Debug // not a comment",
+                "chip": "nRF52840_xxAA",
+                "escaped": "quote \" /* still a string */",
+            }])
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(super::parse("[{]").is_err());
+    }
+}
@@ -0,0 +1,256 @@
+//! Boards of a classroom or lab, from the committed mapping file `.zed/probe-rs-lab.json`.
+//!
+//! Lab machines often have many identical boards attached, which probe-rs can only tell apart
+//! by the serial number of their probe. The mapping file gives each probe a board name and the
+//! chip of the board:
+//!
+//! ```json
+//! {
+//!   "000683455581": { "name": "Station 1", "chip": "nRF52840_xxAA" },
+//!   "000683455582": { "name": "Station 2", "chip": "nRF52840_xxAA" }
+//! }
+//! ```
+//!
+//! A session then uses the board selected with `labBoard`, or the only connected board of the
+//! mapping, without a `probe` in the scenario.
+
+use zed_extension_api::{
+    Worktree,
+    serde_json::{Value, json},
+};
+
+use crate::{enumeration::ConnectedProbe, errors::Category};
+
+pub const LAB_PATH: &str = ".zed/probe-rs-lab.json";
+
+#[derive(Debug, PartialEq)]
+pub struct Board {
+    pub serial: String,
+    pub name: String,
+    pub chip: Option<String>,
+}
+
+fn parse(contents: &str) -> Result<Vec<Board>, String> {
+    let Value::Object(boards) = crate::jsonc::parse(contents)? else {
+        return Err("expected an object with the serial numbers of the probes as keys".to_string());
+    };
+
+    boards
+        .into_iter()
+        .map(|(serial, board)| {
+            let field = |name: &str| board.get(name).and_then(Value::as_str).map(str::to_string);
+
+            Ok(Board {
+                name: field("name")
+                    .ok_or_else(|| format!("the board with the probe '{serial}' has no 'name'"))?,
+                chip: field("chip"),
+                serial,
+            })
+        })
+        .collect()
+}
+
+/// Load the boards of the mapping file, which is empty if the worktree has none.
+pub fn load(worktree: &Worktree) -> Vec<Board> {
+    let Some(contents) =
+        crate::files::read_optional(worktree, LAB_PATH, "lab boards are not selected")
+    else {
+        return Vec::new();
+    };
+
+    parse(&contents).unwrap_or_else(|err| {
+        log!("Ignoring '{LAB_PATH}': {err}");
+        Vec::new()
+    })
+}
+
+/// The board to use, and the connected probe of it.
+fn select<'a>(
+    boards: &'a [Board],
+    selected: Option<&str>,
+    probes: &'a [ConnectedProbe],
+) -> Result<Option<(&'a Board, &'a ConnectedProbe)>, String> {
+    let probe_of = |board: &Board| {
+        probes
+            .iter()
+            .find(|probe| probe.serial.as_deref() == Some(board.serial.as_str()))
+    };
+
+    if let Some(name) = selected {
+        let board = boards
+            .iter()
+            .find(|board| board.name == name)
+            .ok_or_else(|| format!("There is no lab board named '{name}' in '{LAB_PATH}'"))?;
+        let probe = probe_of(board).ok_or_else(|| {
+            format!(
+                "The probe of the lab board '{name}' with the serial number '{}' is not connected",
+                board.serial
+            )
+        })?;
+
+        return Ok(Some((board, probe)));
+    }
+
+    let connected: Vec<_> = boards
+        .iter()
+        .filter_map(|board| Some((board, probe_of(board)?)))
+        .collect();
+
+    match connected.as_slice() {
+        [] => Ok(None),
+        [connected] => Ok(Some(*connected)),
+        _ => Err(format!(
+            "Several lab boards are connected ({}), select one with 'labBoard'",
+            connected
+                .iter()
+                .map(|(board, _)| board.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Select the probe and chip of the lab board, unless the scenario selects a probe itself.
+///
+/// The chip of the board is only used if the scenario has none. `selected` is the `labBoard`
+/// of the scenario or the settings.
+pub fn apply(
+    boards: &[Board],
+    config: &mut Value,
+    selected: Option<&str>,
+    probes: Option<&[ConnectedProbe]>,
+) -> Result<(), String> {
+    if boards.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(probe) = config.get("probe") {
+        let serial = probe.get("serial_number").and_then(Value::as_str);
+        let chip = boards
+            .iter()
+            .find(|board| Some(board.serial.as_str()) == serial)
+            .and_then(|board| board.chip.clone());
+
+        if let Some(chip) = chip
+            && config.get("chip").is_none()
+        {
+            config["chip"] = chip.into();
+        }
+
+        return Ok(());
+    }
+
+    let selected = config
+        .get("labBoard")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .or(selected.map(str::to_string));
+
+    let Some(probes) = probes else {
+        if let Some(selected) = selected {
+            log!(
+                "Can't select the lab board '{selected}', the connected probes couldn't be listed"
+            );
+        }
+        return Ok(());
+    };
+
+    let Some((board, probe)) =
+        select(boards, selected.as_deref(), probes).map_err(|err| Category::Probe.error(err))?
+    else {
+        return Ok(());
+    };
+
+    log!(
+        "Using the lab board '{}' with the probe '{}'",
+        board.name,
+        board.serial
+    );
+
+    config["probe"] = json!({
+        "vendor_id": probe.vendor_id,
+        "product_id": probe.product_id,
+        "serial_number": board.serial,
+    });
+
+    if let Some(chip) = &board.chip
+        && config.get("chip").is_none()
+    {
+        config["chip"] = chip.clone().into();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    use super::Board;
+    use crate::enumeration::ConnectedProbe;
+
+    fn boards() -> Vec<Board> {
+        super::parse(
+            r#"{
+                // Front row
+                "000683455581": { "name": "Station 1", "chip": "nRF52840_xxAA" },
+                "000683455582": { "name": "Station 2" },
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn probe(serial: &str) -> ConnectedProbe {
+        ConnectedProbe {
+            vendor_id: 0x1366,
+            product_id: 0x1015,
+            serial: Some(serial.to_string()),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            boards()[0],
+            Board {
+                serial: "000683455581".to_string(),
+                name: "Station 1".to_string(),
+                chip: Some("nRF52840_xxAA".to_string()),
+            }
+        );
+        assert!(super::parse(r#"{ "000683455581": {} }"#).is_err());
+        assert!(super::parse("[]").is_err());
+    }
+
+    #[test]
+    fn apply() {
+        let boards = boards();
+        let probes = [probe("000683455581"), probe("000683455582")];
+
+        // The only connected board is used.
+        let mut config = json!({});
+        super::apply(&boards, &mut config, None, Some(&probes[..1])).unwrap();
+        assert_eq!(
+            config,
+            json!({
+                "probe": { "vendor_id": 0x1366, "product_id": 0x1015, "serial_number": "000683455581" },
+                "chip": "nRF52840_xxAA",
+            })
+        );
+
+        // With several, one has to be selected.
+        let mut config = json!({ "chip": "nRF52833_xxAA" });
+        assert!(super::apply(&boards, &mut config, None, Some(&probes)).is_err());
+        super::apply(&boards, &mut config, Some("Station 2"), Some(&probes)).unwrap();
+        assert_eq!(config["probe"]["serial_number"], "000683455582");
+        assert_eq!(config["chip"], "nRF52833_xxAA");
+
+        assert!(super::apply(&boards, &mut json!({}), Some("Station 3"), Some(&probes)).is_err());
+
+        // A probe of the scenario only gets the chip of its board.
+        let mut config = json!({ "probe": { "serial_number": "000683455581" } });
+        super::apply(&boards, &mut config, Some("Station 2"), Some(&probes)).unwrap();
+        assert_eq!(config["chip"], "nRF52840_xxAA");
+        assert_eq!(config["probe"], json!({ "serial_number": "000683455581" }));
+    }
+}
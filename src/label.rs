@@ -0,0 +1,120 @@
+//! Labels of generated scenarios, based on the `labelTemplate` setting.
+//!
+//! Teams building several firmware variants from one project use the template to add the git
+//! branch or a build id to the label, so the scenarios can be told apart in the picker.
+
+use crate::settings::Settings;
+
+/// Apply the label template from the settings to the label of a generated scenario.
+///
+/// The template can use `{label}`, `{branch}`, `{buildId}` and `{board}`, and the placeholders in `values`,
+/// which depend on the scenario. If a value used in the template is not available, the label
+/// is kept unchanged instead of showing a partial label.
+pub fn apply_template(label: &str, settings: &Settings, values: &[(&str, Option<&str>)]) -> String {
+    let Some(template) = &settings.label_template else {
+        return label.to_string();
+    };
+
+    let common = [
+        ("{label}", Some(label)),
+        ("{branch}", settings.git_branch.as_deref()),
+        ("{buildId}", settings.build_id.as_deref()),
+        ("{board}", settings.lab_board.as_deref()),
+    ];
+
+    let mut rendered = template.clone();
+
+    for &(placeholder, value) in common.iter().chain(values) {
+        if !rendered.contains(placeholder) {
+            continue;
+        }
+
+        let Some(value) = value else {
+            log!("Not using the label template, there is no value for {placeholder}");
+            return label.to_string();
+        };
+
+        rendered = rendered.replace(placeholder, value);
+    }
+
+    rendered
+}
+
+/// The current branch from the contents of `.git/HEAD`.
+///
+/// For a detached head, the abbreviated commit hash is used instead.
+pub fn parse_git_head(contents: &str) -> Option<String> {
+    let head = contents.trim();
+
+    if let Some(reference) = head.strip_prefix("ref:") {
+        let reference = reference.trim();
+        let branch = reference.strip_prefix("refs/heads/").unwrap_or(reference);
+
+        return Some(branch.to_string());
+    }
+
+    let is_hash = head.len() >= 7 && head.chars().all(|c| c.is_ascii_hexdigit());
+    is_hash.then(|| head[..7].to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::settings::Settings;
+
+    fn settings(template: &str) -> Settings {
+        Settings {
+            label_template: Some(template.to_string()),
+            git_branch: Some("feature/radio".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn apply_template() {
+        assert_eq!(
+            super::apply_template("cargo run", &Settings::default(), &[]),
+            "cargo run"
+        );
+        assert_eq!(
+            super::apply_template("cargo run", &settings("{label} ({branch})"), &[]),
+            "cargo run (feature/radio)"
+        );
+
+        // There is no build id, so the template can't be used.
+        assert_eq!(
+            super::apply_template("cargo run", &settings("{label} #{buildId}"), &[]),
+            "cargo run"
+        );
+
+        let lab = Settings {
+            lab_board: Some("Station 4".to_string()),
+            ..settings("{label} @ {board}")
+        };
+        assert_eq!(
+            super::apply_template("cargo run", &lab, &[]),
+            "cargo run @ Station 4"
+        );
+
+        assert_eq!(
+            super::apply_template(
+                "cargo run",
+                &settings("{package}/{bin}"),
+                &[("{package}", Some("radio")), ("{bin}", Some("firmware"))]
+            ),
+            "radio/firmware"
+        );
+    }
+
+    #[test]
+    fn parse_git_head() {
+        assert_eq!(
+            super::parse_git_head("ref: refs/heads/main\n").as_deref(),
+            Some("main")
+        );
+        assert_eq!(
+            super::parse_git_head("3f1c2a9d8e7b6a5f4e3d2c1b0a9f8e7d6c5b4a39\n").as_deref(),
+            Some("3f1c2a9")
+        );
+        assert_eq!(super::parse_git_head("garbage"), None);
+    }
+}
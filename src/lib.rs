@@ -1,12 +1,84 @@
+/// Write a line to the Zed log, tagged with the scenario of the session being started.
+macro_rules! log {
+    ($($arg:tt)*) => {{
+        let message = format!($($arg)*);
+        println!("{}{message}", $crate::session::prefix());
+        $crate::log::record($crate::log::Level::Info, module_path!(), &message);
+    }};
+}
+
+/// Like [`log!`], but recorded as a warning in the log of a session started in verbose mode.
+macro_rules! warn {
+    ($($arg:tt)*) => {{
+        let message = format!($($arg)*);
+        println!("{}{message}", $crate::session::prefix());
+        $crate::log::record($crate::log::Level::Warn, module_path!(), &message);
+    }};
+}
+
+mod adapter;
+mod attached;
+mod cache;
+mod cargo_home;
+mod chips;
+mod commands;
+mod config;
+mod end_of_session;
+mod enumeration;
+mod errors;
+mod files;
+mod flash_algorithm;
+mod flashing;
+mod github;
+mod history;
+mod host_binary;
+mod identify;
+mod images;
+mod install;
+mod jsonc;
+mod lab;
+mod label;
+mod locator;
+mod log;
+mod onboarding;
+mod paths;
+mod ports;
+mod power;
+mod probes;
+mod process;
+mod project;
+mod scratch;
+mod server;
+mod session;
+mod settings;
+mod sha256;
+mod speed;
+mod staleness;
+mod state;
+mod statistics;
+mod svd;
+mod targets;
+mod timing;
+mod update;
+mod validate;
+mod variables;
+mod version;
+mod vscode;
+
 use std::{net::Ipv4Addr, time::Duration};
 
 use zed_extension_api::{
     self as zed, DebugAdapterBinary, DebugConfig, DebugRequest, DebugScenario, DebugTaskDefinition,
-    StartDebuggingRequestArguments, StartDebuggingRequestArgumentsRequest, TcpArguments, Worktree,
-    serde_json,
+    SlashCommand, SlashCommandOutput, StartDebuggingRequestArguments,
+    StartDebuggingRequestArgumentsRequest, TaskTemplate, TcpArguments, Worktree, serde_json,
 };
 
-const ADAPTER_NAME: &str = "probe-rs";
+use errors::Category;
+use project::Project;
+use server::DapServer;
+use settings::Settings;
+
+pub(crate) const ADAPTER_NAME: &str = "probe-rs";
 
 fn verify_adapter_name(adapter_name: &str) -> Result<(), String> {
     if adapter_name != ADAPTER_NAME {
@@ -29,7 +101,8 @@ impl zed::Extension for ProbeRsDebugger {
     where
         Self: Sized,
     {
-        println!("Creating new instance of the probe-rs-debugger extension");
+        log!("Creating new instance of the probe-rs-debugger extension");
+        cache::prepare();
         Self {}
     }
 
@@ -38,25 +111,196 @@ impl zed::Extension for ProbeRsDebugger {
         adapter_name: String,
         config: DebugTaskDefinition,
         user_provided_debug_adapter_path: Option<String>,
-        _worktree: &Worktree,
+        worktree: &Worktree,
     ) -> Result<DebugAdapterBinary, String> {
-        // TODO: Handle task definition
+        session::begin(&config.label);
+
+        let binary = self
+            .dap_binary(
+                &adapter_name,
+                &config,
+                user_provided_debug_adapter_path,
+                worktree,
+            )
+            .map_err(|err| scenario_error(&config.label, err));
+
+        statistics::record_session(binary.as_ref().map(drop).map_err(String::as_str));
+        log::finish(binary.as_ref().err().map(String::as_str));
+        session::end();
+
+        binary
+    }
 
+    fn dap_request_kind(
+        &mut self,
+        adapter_name: String,
+        config: serde_json::Value,
+    ) -> Result<StartDebuggingRequestArgumentsRequest, String> {
         verify_adapter_name(&adapter_name)?;
 
-        // TODO: Can this be none if we want to use an already running instance of probe-rs?
-        //let command =
-        //    Some(user_provided_debug_adapter_path.unwrap_or_else(|| "probe-rs".to_string()));
+        // Zed doesn't pass the label here, so the error only names the adapter.
+        request_kind(&config).map_err(|err| format!("{ADAPTER_NAME}: {err}"))
+    }
+
+    fn dap_config_to_scenario(
+        &mut self,
+        debug_config: DebugConfig,
+    ) -> Result<DebugScenario, String> {
+        verify_adapter_name(&debug_config.adapter)
+            .map_err(|err| scenario_error(&debug_config.label, err))?;
+
+        match debug_config.request {
+            DebugRequest::Launch(launch_request) => {
+                if !launch_request.args.is_empty() {
+                    return Err(scenario_error(
+                        &debug_config.label,
+                        "Passing arguments is not supported by this debug adapter".to_string(),
+                    ));
+                }
+
+                let env: serde_json::Map<_, _> = launch_request
+                    .envs
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect();
+
+                // We only get a single program, so we can't create a configuration which would
+                // work in a multi-core scenario.
+                //
+                // We also enable flashing to mimic launching a program.
+                let settings = Settings::last_loaded();
+                let config = serde_json::json!({
+                    "cwd": launch_request.cwd,
+                    "coreConfigs": [
+                        {
+                            "programBinary": launch_request.program
+                        }
+                    ],
+                    "flashingConfig": {
+                        "flashingEnabled": true,
+                        "haltAfterReset": settings.halt_after_reset.or(debug_config.stop_on_entry),
+                    },
+                    "request": "launch",
+                    "env": env,
+                });
+
+                let scenario = DebugScenario {
+                    label: label::apply_template(&debug_config.label, &settings, &[]),
+                    adapter: debug_config.adapter,
+                    // TODO: Could integrate with cargo
+                    build: None,
+                    config: config.to_string(),
+                    tcp_connection: None,
+                };
+
+                Ok(scenario)
+            }
+            DebugRequest::Attach(_attach_request) => {
+                // We can't really support attach in the traditional sense, because we can't attach to a running program on the
+                // host. The process id is meaningless, but a probe from the settings tells which
+                // target to attach to.
+                let settings = Settings::last_loaded();
+                let chip = settings.chip.clone().or(Project::last_loaded().chip);
+                let config = config::attach_config(settings.probe.as_deref(), chip.as_deref())
+                    .map_err(|err| scenario_error(&debug_config.label, err))?;
+
+                Ok(DebugScenario {
+                    label: label::apply_template(&debug_config.label, &settings, &[]),
+                    adapter: debug_config.adapter,
+                    build: None,
+                    config: config.to_string(),
+                    tcp_connection: None,
+                })
+            }
+        }
+    }
+
+    fn dap_locator_create_scenario(
+        &mut self,
+        locator_name: String,
+        build_task: TaskTemplate,
+        resolved_label: String,
+        debug_adapter_name: String,
+    ) -> Option<DebugScenario> {
+        if locator_name != locator::LOCATOR_NAME || debug_adapter_name != ADAPTER_NAME {
+            return None;
+        }
+
+        locator::create_scenario(
+            build_task,
+            resolved_label,
+            &Settings::last_loaded(),
+            &Project::last_loaded(),
+        )
+    }
+
+    fn run_dap_locator(
+        &mut self,
+        locator_name: String,
+        build_task: TaskTemplate,
+    ) -> Result<DebugRequest, String> {
+        if locator_name != locator::LOCATOR_NAME {
+            return Err(format!("Unsupported debug locator '{locator_name}'"));
+        }
+
+        let label = build_task.label.clone();
+
+        locator::run(build_task).map_err(|err| scenario_error(&label, err))
+    }
+
+    fn run_slash_command(
+        &self,
+        command: SlashCommand,
+        args: Vec<String>,
+        worktree: Option<&Worktree>,
+    ) -> Result<SlashCommandOutput, String> {
+        commands::run(command, args, worktree)
+    }
+}
+
+impl ProbeRsDebugger {
+    fn dap_binary(
+        &mut self,
+        adapter_name: &str,
+        config: &DebugTaskDefinition,
+        user_provided_debug_adapter_path: Option<String>,
+        worktree: &Worktree,
+    ) -> Result<DebugAdapterBinary, String> {
+        verify_adapter_name(adapter_name)?;
 
-        let json_config: serde_json::Value = serde_json::from_str(&config.config)
-            .map_err(|err| format!("Failed to parse JSON config: {}", err))?;
+        let mut timings = timing::Timings::start();
+        let shell_env = worktree.shell_env();
+        process::set_environment(shell_env.clone());
+
+        // Loading the settings and detecting the project also makes them available to hooks
+        // without a worktree.
+        let settings = Settings::load(worktree);
+        let project = Project::load(worktree);
+
+        if settings.verbose {
+            log::begin();
+        }
+
+        let mut json_config = config::parse(&config.config, &config.label)?;
+        variables::substitute(&mut json_config, &worktree.root_path())?;
+
+        let unquoted = config::normalize_paths(&mut json_config);
+        if !unquoted.is_empty() {
+            log!(
+                "Removed the quotes around {}, paths are passed to probe-rs as is and don't need quotes",
+                unquoted.join(", ")
+            );
+        }
+
+        config::normalize_numbers(&mut json_config).map_err(|err| err.to_string())?;
 
         // TODO: Figure out the interaction with `DebugTaskDefinition.tcp_connection`.
         //
         // The use of the server field here is taken from the vscode plugin.
         let received_connection =
             if let Some(server_string) = json_config.get("server").and_then(|s| s.as_str()) {
-                let mut parsed = parse_server_string(server_string)?;
+                let mut parsed = parse_server_string(server_string)
+                    .map_err(|err| Category::Server.error(err))?;
 
                 // See <https://github.com/zed-industries/zed/blob/834cdc127176228c3c11f1d2cf68a90797a54f15/crates/dap/src/transport.rs#L577>,
                 // this seems to be in milliseconds
@@ -67,16 +311,107 @@ impl zed::Extension for ProbeRsDebugger {
                 None
             };
 
-        // TODO: Consider downloading the debug adapter if it's not found.
         let mut command = None;
         let mut arguments = Vec::new();
+        let mut install_error = None;
+        let mut version_output = None;
+        let mut reset_after_session = false;
+        let end_of_session = end_of_session::EndOfSession::from_config(&json_config)?;
+        let adapter = adapter::Adapter::new(
+            &json_config,
+            &settings,
+            user_provided_debug_adapter_path,
+            worktree,
+        );
 
         let connection = if received_connection.is_none() {
-            command =
-                Some(user_provided_debug_adapter_path.unwrap_or_else(|| "probe-rs".to_string()));
+            let pinned = adapter.pinned.clone();
+
+            if let Some((binary, source)) = &adapter.configured {
+                version_output = Some(
+                    install::check_configured(binary, source)
+                        .map_err(|err| Category::Server.error(err))?,
+                )
+                .filter(|output| !output.is_empty());
+            }
+
+            let probe_rs = timings.measure("binary", || {
+                adapter.find(worktree).or_else(|| {
+                    downloaded_binary(pinned.as_deref(), &settings, worktree)
+                        .map(|binary| (binary, "downloaded"))
+                        .map_err(|err| install_error = Some(err))
+                        .ok()
+                })
+            });
+            let (probe_rs, source) = probe_rs.unzip();
+
+            let mut cli = probe_rs
+                .as_deref()
+                .map_or(server::Cli::DapServer, server::Cli::from_binary);
+
+            let mut version = None;
+            if let Some(probe_rs) = &probe_rs {
+                version_output = version_output
+                    .or_else(|| timings.measure("version", || install::version_output(probe_rs)));
+                version = version_output
+                    .as_deref()
+                    .and_then(version::Version::from_version_output);
+
+                log!(
+                    "{}",
+                    install::adapter_summary(
+                        probe_rs,
+                        source.unwrap_or_default(),
+                        version_output.as_deref()
+                    )
+                );
+
+                // Only binaries which are too old for `dap-server` may be legacy ones under
+                // another name, so the help isn't read for every session.
+                if cli == server::Cli::DapServer
+                    && version.is_none_or(|version| version < version::MINIMUM)
+                {
+                    cli = timings.measure("cli", || install::cli(probe_rs));
+                }
+            }
+
+            // The legacy debugger is older than any supported version, but was chosen on purpose.
+            if cli == server::Cli::Legacy {
+                log!(
+                    "'{}' has no 'dap-server' command, starting it with the legacy 'debug --dap', \
+                     which ignores the server options of the extension, like 'singleSession' and \
+                     'logFile'",
+                    probe_rs.as_deref().unwrap_or(server::LEGACY_BINARY_NAME)
+                );
+            } else if let Some(version) = version {
+                // Binaries which don't report a version are given the benefit of the doubt.
+                version::check_minimum(version).map_err(|err| Category::Server.error(err))?;
+            }
 
-            // TOOD: Get a port from somewhere
-            let port = 50_000;
+            // A pinned version is outdated on purpose.
+            if settings.update_check
+                && !settings.offline
+                && settings.download_mirror.is_none()
+                && pinned.is_none()
+                && let Some(version) = version
+            {
+                let token = github::token(&shell_env);
+                timings.measure("update", || {
+                    update::check(version, settings.channel, token.as_deref())
+                });
+            }
+
+            command = Some(probe_rs.unwrap_or_else(|| "probe-rs".to_string()));
+
+            let recorded = history::last_adapter(&worktree.root_path())
+                .and_then(|adapter| {
+                    let server = adapter.get("server")?.as_str()?.to_string();
+                    parse_server_string(&server).ok()
+                })
+                .map(|connection| connection.port);
+            let port = timings
+                .measure("port", || ports::choose(recorded))
+                .map_err(|err| Category::Server.error(err))?;
 
             let tcp_arguments = TcpArguments {
                 port,
@@ -84,114 +419,396 @@ impl zed::Extension for ProbeRsDebugger {
                 timeout: Some(DEFAULT_TIMEOUT.as_millis() as u64),
             };
 
-            arguments.extend_from_slice(&[
-                "dap-server".to_string(),
-                "--port".to_string(),
-                port.to_string(),
-            ]);
+            let log_file = config::log_file(&json_config, &config.label, settings.verbose)
+                .map(|log_file| paths::join(Some(&worktree.root_path()), &log_file));
+
+            if end_of_session == end_of_session::EndOfSession::Reset {
+                let (os, _) = zed::current_platform();
+                match end_of_session::unsupported(os, cli) {
+                    Some(reason) => log!("'endOfSession' is ignored, since {reason}"),
+                    None => reset_after_session = true,
+                }
+            }
+
+            arguments = DapServer::new(port)
+                .cli(cli)
+                .single_session(settings.single_session || reset_after_session)
+                .log_file(log_file)
+                .extra_args(config::server_args(&json_config)?)
+                .arguments();
 
             Some(tcp_arguments)
         } else {
             received_connection
         };
 
-        println!("Configuration for DAP: {}", config.config);
-
-        // TODO: What happens if both command and tcp connection are provided?
+        let mut request = request_kind(&json_config)?;
 
-        Ok(DebugAdapterBinary {
-            command,
-            arguments,
-            envs: vec![],
-            cwd: None,
-            connection,
-            request_args: StartDebuggingRequestArguments {
-                // We just pass along the configuration
-                configuration: config.config,
-                request: StartDebuggingRequestArgumentsRequest::Launch,
-            },
-        })
-    }
+        if config::is_observe_only(&json_config) {
+            if request == StartDebuggingRequestArgumentsRequest::Launch {
+                log!("Attaching instead of launching, since 'observeOnly' is set");
+                request = StartDebuggingRequestArgumentsRequest::Attach;
+            }
 
-    fn dap_request_kind(
-        &mut self,
-        adapter_name: String,
-        config: serde_json::Value,
-    ) -> Result<StartDebuggingRequestArgumentsRequest, String> {
-        verify_adapter_name(&adapter_name)?;
+            let overridden = config::apply_observe_only(&mut json_config);
+            if !overridden.is_empty() {
+                log!(
+                    "'observeOnly' disabled {}, which would reset or halt the target",
+                    overridden.join(", ")
+                );
+            }
+        }
 
-        // There should be a request field to indicate if it should be launch or attach
-        let Some(request_value) = config.get("request").and_then(|f| f.as_str()) else {
-            return Err("Missing 'request' field in configuration".to_string());
+        // Only listed if the extension starts the server, a running server may already use the
+        // probe.
+        let probes = match &command {
+            Some(probe_rs) if install_error.is_none() => {
+                timings.measure("probes", || enumeration::list(probe_rs))
+            }
+            _ => None,
         };
 
-        match request_value {
-            "launch" => Ok(StartDebuggingRequestArgumentsRequest::Launch),
-            "attach" => Ok(StartDebuggingRequestArgumentsRequest::Attach),
-            _ => Err(format!(
-                "Invalid value for the 'request' field in configuration. Value is {}, but only 'launch' and 'attach' are supported",
-                request_value
-            )),
+        lab::apply(
+            &lab::load(worktree),
+            &mut json_config,
+            settings.lab_board.as_deref(),
+            probes.as_deref(),
+        )?;
+
+        config::apply_chip_fallback(
+            &mut json_config,
+            &shell_env,
+            settings
+                .chip
+                .as_deref()
+                .map(|chip| (chip, "the settings"))
+                .or(project
+                    .chip
+                    .as_deref()
+                    .map(|chip| (chip, "the Cargo.toml or cargo runner of the project"))),
+        );
+
+        if let Some(install_error) = install_error {
+            let prerequisites = onboarding::Prerequisites {
+                probe_rs: false,
+                chip: json_config.get("chip").is_some(),
+                scenarios: onboarding::has_scenarios(worktree),
+            };
+
+            return Err(format!(
+                "{}\n\n{install_error}",
+                onboarding::guidance(&prerequisites)
+            ));
         }
-    }
 
-    fn dap_config_to_scenario(
-        &mut self,
-        debug_config: DebugConfig,
-    ) -> Result<DebugScenario, String> {
-        verify_adapter_name(&debug_config.adapter)?;
+        if let Some(finding) = validate::resolve_chip_alias(&mut json_config) {
+            warn!("{finding}");
+        }
 
-        match debug_config.request {
-            DebugRequest::Launch(launch_request) => {
-                if !launch_request.args.is_empty() {
-                    return Err(
-                        "Passing arguments is not supported by this debug adapter".to_string()
-                    );
-                }
+        config::apply_fast_flashing(&mut json_config);
+        let ignored = files::read_optional(
+            worktree,
+            ".gitignore",
+            "ignored directories are searched for SVD files",
+        )
+        .map(|gitignore| svd::ignored_directories(&gitignore))
+        .unwrap_or_default();
+        timings.measure("svd", || {
+            svd::apply_discovered(
+                &mut json_config,
+                &worktree.root_path(),
+                &ignored,
+                settings.svd_directory.as_deref(),
+                settings.svd_url.as_deref().filter(|_| !settings.offline),
+            )
+        });
+
+        for finding in
+            validate::validate(&json_config)
+                .into_iter()
+                .chain(validate::check_connect_under_reset(
+                    &json_config,
+                    &settings.connect_under_reset,
+                ))
+        {
+            warn!("{finding}");
+        }
+
+        if let Some(probes) = &probes
+            && let Some(finding) = validate::check_connected(&json_config, probes)
+        {
+            warn!("{finding}");
+        }
 
-                if !launch_request.envs.is_empty() {
-                    return Err(
-                        "Setting environment variables is not supported by this debug adapter"
-                            .to_string(),
-                    );
+        // Like the probes, the chips are only listed if the extension starts the server.
+        if let Some(probe_rs) = &command
+            && let Some(version_output) = &version_output
+            && let Some(chips) = timings.measure("chips", || chips::load(probe_rs, version_output))
+            && let Some(finding) = validate::check_chip(&json_config, &chips)
+        {
+            warn!("{finding}");
+        }
+
+        // Any clamping was already reported by the validation above.
+        validate::clamp_speed(&mut json_config);
+        config::apply_core_halt_after_reset(&mut json_config)?;
+
+        let session_directory = scratch::create(&config.label)?;
+
+        // Like the probe check above, a running server may already use the probe.
+        if let Some(probe_rs) = &command {
+            // The target has to be powered before anything connects to it.
+            timings.measure("power", || {
+                power::apply(
+                    worktree,
+                    &json_config,
+                    probes.as_deref(),
+                    &session_directory,
+                )
+            })?;
+            timings.measure("speed", || speed::check(probe_rs, &mut json_config));
+        }
+
+        let cwd = config::cwd(&json_config, &worktree.root_path());
+        flash_algorithm::apply(worktree, &mut json_config, &session_directory, &cwd)?;
+        let programs = staleness::program_binaries(&json_config, &cwd);
+
+        // The build target is only looked up to explain the error.
+        if let Some(program) = programs
+            .iter()
+            .find(|program| host_binary::is_host_build(program))
+        {
+            host_binary::check(program, project::build_target_of(worktree).as_deref())?;
+        }
+
+        // Flashing is only done by probe-rs when the session is launched.
+        let flashing_enabled = json_config
+            .pointer("/flashingConfig/flashingEnabled")
+            .and_then(|enabled| enabled.as_bool())
+            .unwrap_or(false);
+
+        // With a running server, the CLI commands below still need a binary on the host, the
+        // one the session would have started.
+        let probe_rs = &command.clone().unwrap_or_else(|| adapter.binary(worktree));
+        let flash_only_changed = config::is_flash_only_changed(&json_config);
+
+        match request {
+            StartDebuggingRequestArgumentsRequest::Launch
+                if flashing_enabled && flash_only_changed && staleness::unchanged(&programs) =>
+            {
+                log!("Not flashing, since the programs didn't change since they were last flashed");
+                json_config["flashingConfig"]["flashingEnabled"] = false.into();
+            }
+            StartDebuggingRequestArgumentsRequest::Launch if flashing_enabled => {
+                attached::guard(&json_config)?;
+
+                let outcome = timings.measure("flash", || {
+                    flashing::flash(
+                        probe_rs,
+                        &mut json_config,
+                        &cwd,
+                        &programs,
+                        !settings.flashed_command.is_empty() || flash_only_changed,
+                    )
+                })?;
+
+                // Attaching later has to know what is on the target, even if probe-rs flashes
+                // the programs, which the extension can't see the result of.
+                if outcome.programs_flashed() {
+                    staleness::record_flashed(&programs);
                 }
 
-                // We only get a single program, so we can't create a configuration which would
-                // work in a multi-core scenario.
-                //
-                // We also enable flashing to mimic launching a program.
-                let config = serde_json::json!({
-                    "cwd": launch_request.cwd,
-                    "coreConfigs": [
-                        {
-                            "programBinary": launch_request.program
+                match outcome {
+                    flashing::Outcome::Flashed => {}
+                    flashing::Outcome::FlashedPrograms => {
+                        flashing::run_flashed_command(
+                            &settings.flashed_command,
+                            &config.label,
+                            &programs,
+                        );
+                    }
+                    flashing::Outcome::Attach => {
+                        request = StartDebuggingRequestArgumentsRequest::Attach;
+
+                        for warning in staleness::check_attach(&programs) {
+                            warn!("{warning}");
                         }
-                    ],
-                    "flashingConfig": {
-                        "flashingEnabled": true,
-                        "haltAfterReset": debug_config.stop_on_entry,
-                    },
-                    "request": "launch",
+                    }
+                }
+            }
+            StartDebuggingRequestArgumentsRequest::Launch => {}
+            StartDebuggingRequestArgumentsRequest::Attach => {
+                for warning in staleness::check_attach(&programs) {
+                    warn!("{warning}");
+                }
+            }
+        }
 
-                });
+        // The target has to be read before the server connects to the probe.
+        if json_config
+            .get("identify")
+            .and_then(|identify| identify.as_bool())
+            == Some(true)
+        {
+            timings.measure("identify", || {
+                identify::print_banner(probe_rs, &json_config)
+            });
+        }
 
-                let scenario = DebugScenario {
-                    label: debug_config.label,
-                    adapter: debug_config.adapter,
-                    // TODO: Could integrate with cargo
-                    build: None,
-                    config: config.to_string(),
-                    tcp_connection: None,
-                };
+        // The environment is only used if the extension starts the server itself.
+        let mut envs = config::env(&json_config)?;
 
-                Ok(scenario)
-            }
-            DebugRequest::Attach(_attach_request) => {
-                // We can't really support attach in the traditional sense, because we can't attach to a running program on the
-                // host
-                Err("Attaching to a process is not supported by this debug adapter".to_string())
+        if settings.verbose
+            && !envs
+                .iter()
+                .chain(&shell_env)
+                .any(|(key, _)| key == "RUST_LOG")
+        {
+            envs.push(("RUST_LOG".to_string(), "probe_rs=debug".to_string()));
+        }
+
+        envs.push((session::ENV_VAR.to_string(), config.label.clone()));
+
+        let extra = config::apply_extra_config(&mut json_config)?;
+        if !extra.is_empty() {
+            log!(
+                "Passing the unvalidated options {} from 'extraConfig' to probe-rs",
+                extra.join(", ")
+            );
+        }
+
+        // The target can only be reset once the server exits, so the server was started in
+        // single-session mode above.
+        if let Some(probe_rs) = &command
+            && reset_after_session
+        {
+            let reset_args = end_of_session::reset_args(&json_config)
+                .map_err(|err| Category::Target.error(err))?;
+            let (shell, wrapped) = end_of_session::wrap(probe_rs, &arguments, &reset_args);
+            command = Some(shell);
+            arguments = wrapped;
+        } else if end_of_session == end_of_session::EndOfSession::Reset && command.is_none() {
+            log!("'endOfSession' is ignored, since the extension doesn't start the server");
+        }
+
+        let wrapper = config::adapter_wrapper(&json_config, &settings.adapter_wrapper)?;
+        if !wrapper.is_empty() {
+            match command.take() {
+                Some(adapter) => {
+                    log!("Starting the debug adapter with '{}'", wrapper.join(" "));
+                    let (wrapped, wrapped_arguments) =
+                        server::wrap(&wrapper, adapter, std::mem::take(&mut arguments));
+                    command = Some(wrapped);
+                    arguments = wrapped_arguments;
+                }
+                None => log!(
+                    "'adapterWrapper' is ignored, since the extension doesn't start the server"
+                ),
             }
         }
+
+        // The configuration might have been adjusted above.
+        let configuration = json_config.to_string();
+
+        log!("Configuration for DAP: {configuration}");
+
+        let adapter = serde_json::json!({
+            "command": command,
+            "arguments": arguments,
+            "envs": envs
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone().into()))
+                .collect::<serde_json::Map<_, _>>(),
+            "cwd": cwd,
+            "server": connection.as_ref().map(|connection| {
+                format!("{}:{}", Ipv4Addr::from_bits(connection.host), connection.port)
+            }),
+        });
+        history::record(&worktree.root_path(), &config.label, &json_config, &adapter);
+        timings.finish(settings.timings);
+
+        // TODO: What happens if both command and tcp connection are provided?
+
+        Ok(DebugAdapterBinary {
+            command,
+            arguments,
+            // The environment of the shell isn't recorded, since it may contain secrets.
+            envs: config::merge_env(shell_env, envs),
+            cwd: Some(cwd),
+            connection,
+            request_args: StartDebuggingRequestArguments {
+                configuration,
+                request,
+            },
+        })
+    }
+}
+
+/// The probe-rs binary downloaded by the extension, or why there is none.
+///
+/// In offline mode nothing is downloaded, but an earlier download is still used. If there is
+/// no release binary for the host, probe-rs can be built with cargo instead.
+fn downloaded_binary(
+    pinned: Option<&str>,
+    settings: &Settings,
+    worktree: &Worktree,
+) -> Result<String, String> {
+    if settings.offline {
+        // A pinned version was already looked up among the downloaded versions.
+        return pinned.is_none().then(install::installed).flatten().ok_or_else(|| {
+            "Offline mode is enabled, so probe-rs is not downloaded, and it wasn't found locally."
+                .to_string()
+        });
+    }
+
+    let token = github::token(&worktree.shell_env());
+    let err = match install::install(
+        pinned,
+        settings.channel,
+        settings.download_mirror.as_deref(),
+        token.as_deref(),
+        settings.allow_unverified_downloads,
+    ) {
+        Ok(binary) => return Ok(binary),
+        Err(err) => format!("Downloading probe-rs failed: {err}"),
+    };
+
+    let Some(cargo) = worktree.which("cargo") else {
+        return Err(err);
+    };
+
+    if !settings.cargo_install {
+        return Err(format!(
+            "{err}\nSet \"cargoInstall\": true in '{}' to build it with cargo instead.",
+            settings::SETTINGS_PATH
+        ));
+    }
+
+    log!("{err}");
+    let binstall = worktree.which("cargo-binstall").is_some();
+    install::build(&cargo, binstall, pinned).map_err(|build_err| format!("{err}\n{build_err}"))
+}
+
+/// Add the scenario to an error, so it's clear which of the scenarios failed to start.
+fn scenario_error(label: &str, err: String) -> String {
+    format!("{ADAPTER_NAME} scenario '{label}': {err}")
+}
+
+fn request_kind(
+    config: &serde_json::Value,
+) -> Result<StartDebuggingRequestArgumentsRequest, String> {
+    // There should be a request field to indicate if it should be launch or attach
+    let Some(request_value) = config.get("request").and_then(|f| f.as_str()) else {
+        return Err("Missing 'request' field in configuration".to_string());
+    };
+
+    match request_value {
+        "launch" => Ok(StartDebuggingRequestArgumentsRequest::Launch),
+        "attach" => Ok(StartDebuggingRequestArgumentsRequest::Attach),
+        _ => Err(format!(
+            "Invalid value for the 'request' field in configuration. Value is {}, but only 'launch' and 'attach' are supported",
+            request_value
+        )),
     }
 }
 
@@ -237,6 +854,14 @@ zed::register_extension!(ProbeRsDebugger);
 mod test {
     use std::net::Ipv4Addr;
 
+    #[test]
+    fn scenario_error() {
+        assert_eq!(
+            super::scenario_error("Debug nRF52", "Missing 'request' field".to_string()),
+            "probe-rs scenario 'Debug nRF52': Missing 'request' field"
+        );
+    }
+
     #[test]
     fn parse_server_string_invalid_format() {
         // Test missing port
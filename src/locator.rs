@@ -0,0 +1,434 @@
+//! Debug locator which turns cargo tasks into probe-rs debug scenarios.
+//!
+//! Zed asks the locator for a scenario for tasks like `cargo run` of a binary. The generated
+//! scenario builds the binary with cargo, and [`run`] then finds the built executable using
+//! cargo's JSON output.
+
+use std::sync::Mutex;
+
+use zed_extension_api::{
+    BuildTaskDefinition, BuildTaskDefinitionTemplatePayload, DebugRequest, DebugScenario,
+    LaunchRequest, TaskTemplate,
+    serde_json::{self, Value},
+};
+
+use crate::{project::Project, settings::Settings, state::State};
+
+pub const LOCATOR_NAME: &str = "probe-rs";
+
+/// Number of scenarios offered for packages without firmware crates, unless configured.
+const DEFAULT_LIMIT: usize = 20;
+
+/// State key for the binaries which were built last by the locator.
+const RECENT_BUILDS_KEY: &str = "recentBuilds";
+
+/// Number of recently built binaries which are remembered.
+const RECENT_BUILDS: usize = 10;
+
+/// Ids of the scenarios offered so far which count towards the limit.
+///
+/// Scenarios which were offered once keep being offered, so the picker doesn't change while
+/// the extension is running.
+static OFFERED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Forget the offered scenarios, so the limit is applied from scratch.
+pub fn clear_offered() {
+    if let Ok(mut offered) = OFFERED.lock() {
+        offered.clear();
+    }
+}
+
+/// Create a probe-rs scenario for a cargo task.
+pub fn create_scenario(
+    build_task: TaskTemplate,
+    resolved_label: String,
+    settings: &Settings,
+    project: &Project,
+) -> Option<DebugScenario> {
+    let build_task = cargo_build_task(build_task, settings)?;
+    let key = ScenarioKey::from_args(
+        &build_task.args,
+        settings.chip.clone().or_else(|| project.chip.clone()),
+    );
+
+    let preferred = key
+        .package
+        .as_ref()
+        .is_some_and(|package| project.embedded_packages.contains(package))
+        || recent_builds(&State::load()).contains(&key.artifact());
+    let limit = settings.locator_limit.unwrap_or(DEFAULT_LIMIT);
+
+    let mut offered = OFFERED.lock().unwrap_or_else(|err| err.into_inner());
+    if !admit(&mut offered, &key.id(), preferred, limit) {
+        return None;
+    }
+
+    let mut core_config = serde_json::Map::new();
+    project.apply_defaults(&mut core_config);
+
+    let mut config = serde_json::json!({
+        "request": "launch",
+        "scenarioId": key.id(),
+        "cwd": build_task.cwd,
+        "coreConfigs": [core_config],
+        "flashingConfig": {
+            "flashingEnabled": true,
+        },
+    });
+
+    if let Some(halt) = settings.halt_after_reset {
+        config["flashingConfig"]["haltAfterReset"] = halt.into();
+    }
+
+    Some(DebugScenario {
+        label: crate::label::apply_template(&resolved_label, settings, &key.label_values()),
+        adapter: crate::ADAPTER_NAME.to_string(),
+        build: Some(BuildTaskDefinition::Template(
+            BuildTaskDefinitionTemplatePayload {
+                locator_name: Some(LOCATOR_NAME.to_string()),
+                template: build_task,
+            },
+        )),
+        config: config.to_string(),
+        tcp_connection: None,
+    })
+}
+
+/// What a generated scenario debugs, which identifies it across runs.
+///
+/// The fields are in the order in which scenarios should be sorted, so labels built from them
+/// keep their order in the picker.
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ScenarioKey {
+    pub package: Option<String>,
+    pub bin: Option<String>,
+    pub chip: Option<String>,
+}
+
+impl ScenarioKey {
+    /// The key from the arguments of a cargo task and the chip used for it.
+    fn from_args(args: &[String], chip: Option<String>) -> Self {
+        let mut key = ScenarioKey {
+            chip,
+            ..Default::default()
+        };
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+
+            let field = match flag {
+                "-p" | "--package" => &mut key.package,
+                "--bin" | "--example" => &mut key.bin,
+                _ => continue,
+            };
+
+            *field = inline_value.or_else(|| args.next().cloned());
+        }
+
+        key
+    }
+
+    /// An identifier which stays the same as long as the package, binary and chip do.
+    pub fn id(&self) -> String {
+        let part = |value: &Option<String>| value.clone().unwrap_or_default();
+
+        format!(
+            "probe-rs:{}:{}:{}",
+            part(&self.package),
+            part(&self.bin),
+            part(&self.chip)
+        )
+    }
+
+    /// The package and binary, which identify the built artifact regardless of the chip.
+    fn artifact(&self) -> String {
+        format!(
+            "{}:{}",
+            self.package.as_deref().unwrap_or_default(),
+            self.bin.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// Values for the placeholders of the label template.
+    fn label_values(&self) -> [(&'static str, Option<&str>); 3] {
+        [
+            ("{package}", self.package.as_deref()),
+            ("{bin}", self.bin.as_deref()),
+            ("{chip}", self.chip.as_deref()),
+        ]
+    }
+}
+
+/// Decide whether a scenario is offered, remembering it in `offered` if it counts towards the
+/// limit.
+///
+/// Scenarios for packages with firmware crates and for recently built binaries are always
+/// offered. Others are offered until the limit is reached, in the order Zed asks for them.
+fn admit(offered: &mut Vec<String>, id: &str, preferred: bool, limit: usize) -> bool {
+    if preferred || offered.iter().any(|offered| offered == id) {
+        return true;
+    }
+
+    if offered.len() >= limit {
+        return false;
+    }
+
+    offered.push(id.to_string());
+    true
+}
+
+/// The artifacts built last by the locator, most recent first.
+fn recent_builds(state: &State) -> Vec<String> {
+    state
+        .value(RECENT_BUILDS_KEY)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Remember that the artifact was built, so its scenario is preferred by the locator.
+fn record_build(state: &mut State, artifact: String) {
+    let mut builds = recent_builds(state);
+    builds.retain(|build| *build != artifact);
+    builds.insert(0, artifact);
+    builds.truncate(RECENT_BUILDS);
+
+    state
+        .values_mut()
+        .insert(RECENT_BUILDS_KEY.to_string(), builds.into());
+}
+
+/// Turn a `cargo build` or `cargo run` task into a task which only builds.
+fn cargo_build_task(mut task: TaskTemplate, settings: &Settings) -> Option<TaskTemplate> {
+    if task.command != "cargo" {
+        return None;
+    }
+
+    // A rustup toolchain like `+nightly` or an option may come before the subcommand. The
+    // capabilities of the extension only allow one.
+    let subcommand = task
+        .args
+        .iter()
+        .position(|arg| !arg.starts_with('-') && !arg.starts_with('+'))
+        .filter(|&subcommand| subcommand <= 1)?;
+
+    match task.args[subcommand].as_str() {
+        "build" | "b" => {}
+        "run" | "r" => task.args[subcommand] = "build".to_string(),
+        _ => return None,
+    }
+
+    // Arguments after `--` are for the program, which are not supported.
+    if let Some(separator) = task.args.iter().position(|arg| arg == "--") {
+        task.args.truncate(separator);
+    }
+
+    if let Some(level) = &settings.defmt_log
+        && !task.env.iter().any(|(key, _)| key == "DEFMT_LOG")
+    {
+        task.env.push(("DEFMT_LOG".to_string(), level.clone()));
+    }
+
+    Some(task)
+}
+
+/// Find the executable built by the cargo task.
+pub fn run(task: TaskTemplate) -> Result<DebugRequest, String> {
+    let mut args = task.args.clone();
+    args.push("--message-format=json".to_string());
+
+    if let Some(cwd) = &task.cwd
+        && !args.iter().any(|arg| arg.starts_with("--manifest-path"))
+    {
+        args.push(format!(
+            "--manifest-path={}",
+            crate::paths::join(Some(cwd), "Cargo.toml")
+        ));
+    }
+
+    let output = crate::process::run_with_env(&task.command, &args, &task.env)?;
+
+    if output.status != Some(0) {
+        return Err(format!(
+            "Building with cargo failed: {}",
+            output.stderr.trim()
+        ));
+    }
+
+    let program = find_executable(&output.stdout)?;
+    crate::host_binary::check(&program, None)?;
+
+    let mut state = State::load();
+    record_build(
+        &mut state,
+        ScenarioKey::from_args(&task.args, None).artifact(),
+    );
+    if let Err(err) = state.save() {
+        log!("{err}");
+    }
+
+    Ok(DebugRequest::Launch(LaunchRequest {
+        program,
+        cwd: task.cwd,
+        args: Vec::new(),
+        envs: Vec::new(),
+    }))
+}
+
+/// Find the executable in the JSON messages emitted by cargo.
+fn find_executable(messages: &str) -> Result<String, String> {
+    let executables: Vec<String> = messages
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|message| {
+            message.get("reason").and_then(Value::as_str) == Some("compiler-artifact")
+        })
+        .filter_map(|message| {
+            message
+                .get("executable")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        })
+        .collect();
+
+    match executables.as_slice() {
+        [executable] => Ok(executable.clone()),
+        [] => Err("The cargo build didn't produce an executable".to_string()),
+        _ => Err(format!(
+            "The cargo build produced multiple executables, select one using `--bin`: {}",
+            executables.join(", ")
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::TaskTemplate;
+
+    use crate::{settings::Settings, state::State};
+
+    fn task(args: &[&str]) -> TaskTemplate {
+        TaskTemplate {
+            label: "cargo run".to_string(),
+            command: "cargo".to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            env: Vec::new(),
+            cwd: Some("/project".to_string()),
+        }
+    }
+
+    #[test]
+    fn cargo_run_becomes_build() {
+        let settings = Settings::default();
+
+        let build = super::cargo_build_task(
+            task(&["run", "--release", "--bin", "app", "--", "arg"]),
+            &settings,
+        )
+        .unwrap();
+
+        assert_eq!(build.args, ["build", "--release", "--bin", "app"]);
+        assert!(build.env.is_empty());
+
+        let build = super::cargo_build_task(task(&["+nightly", "run"]), &settings).unwrap();
+        assert_eq!(build.args, ["+nightly", "build"]);
+    }
+
+    #[test]
+    fn other_tasks_are_ignored() {
+        let settings = Settings::default();
+
+        assert!(super::cargo_build_task(task(&["test"]), &settings).is_none());
+        assert!(super::cargo_build_task(task(&["+nightly", "-q", "run"]), &settings).is_none());
+
+        let mut make = task(&["all"]);
+        make.command = "make".to_string();
+        assert!(super::cargo_build_task(make, &settings).is_none());
+    }
+
+    #[test]
+    fn defmt_log_from_settings() {
+        let settings = Settings {
+            defmt_log: Some("trace".to_string()),
+            ..Default::default()
+        };
+
+        let build = super::cargo_build_task(task(&["build"]), &settings).unwrap();
+        assert_eq!(build.env, [("DEFMT_LOG".to_string(), "trace".to_string())]);
+
+        // An explicitly set level in the task is kept.
+        let mut explicit = task(&["build"]);
+        explicit
+            .env
+            .push(("DEFMT_LOG".to_string(), "warn".to_string()));
+        let build = super::cargo_build_task(explicit, &settings).unwrap();
+        assert_eq!(build.env, [("DEFMT_LOG".to_string(), "warn".to_string())]);
+    }
+
+    #[test]
+    fn scenario_key() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        let key = super::ScenarioKey::from_args(
+            &args(&["build", "-p", "radio", "--bin=firmware"]),
+            Some("nRF52840_xxAA".to_string()),
+        );
+        assert_eq!(key.package.as_deref(), Some("radio"));
+        assert_eq!(key.bin.as_deref(), Some("firmware"));
+        assert_eq!(key.id(), "probe-rs:radio:firmware:nRF52840_xxAA");
+
+        let example = super::ScenarioKey::from_args(&args(&["build", "--example", "blinky"]), None);
+        assert_eq!(example.id(), "probe-rs::blinky:");
+
+        // Keys sort by package first, then binary and chip.
+        let mut keys = [key, example];
+        keys.sort();
+        assert_eq!(keys[0].bin.as_deref(), Some("blinky"));
+    }
+
+    #[test]
+    fn admit() {
+        let mut offered = Vec::new();
+
+        assert!(super::admit(&mut offered, "a", false, 2));
+        assert!(super::admit(&mut offered, "b", false, 2));
+        assert!(!super::admit(&mut offered, "c", false, 2));
+
+        // Scenarios offered before, and preferred ones, are offered beyond the limit.
+        assert!(super::admit(&mut offered, "a", false, 2));
+        assert!(super::admit(&mut offered, "firmware", true, 2));
+        assert_eq!(offered, ["a", "b"]);
+    }
+
+    #[test]
+    fn record_build() {
+        let mut state = State::default();
+
+        for artifact in ["a:x", "b:y", "a:x"] {
+            super::record_build(&mut state, artifact.to_string());
+        }
+
+        assert_eq!(super::recent_builds(&state), ["a:x", "b:y"]);
+    }
+
+    #[test]
+    fn find_executable() {
+        let messages = r#"{"reason":"compiler-artifact","executable":null}
+{"reason":"compiler-artifact","executable":"/project/target/thumbv7em-none-eabihf/debug/app"}
+{"reason":"build-finished","success":true}"#;
+
+        assert_eq!(
+            super::find_executable(messages).unwrap(),
+            "/project/target/thumbv7em-none-eabihf/debug/app"
+        );
+
+        assert!(super::find_executable(r#"{"reason":"build-finished"}"#).is_err());
+    }
+}
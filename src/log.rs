@@ -0,0 +1,239 @@
+//! Parsing of probe-rs log files.
+//!
+//! probe-rs logs using `tracing`, either as plain text lines like
+//! `2024-05-01T10:00:00.000Z  WARN probe_rs::session: message`, or as JSON objects with
+//! `level`, `target` and `fields.message`. Both are understood here.
+//!
+//! In verbose mode, the messages of the extension while a session is started are written in
+//! the same JSON format, so they are parsed alongside the log of probe-rs.
+
+use std::{fs, sync::Mutex};
+
+use zed_extension_api::serde_json::{self, Value, json};
+
+/// The messages of the last session started in verbose mode, one JSON object per line, in the
+/// directory of the extension.
+pub const EXTENSION_LOG_PATH: &str = "session.log";
+
+/// The messages recorded for the session being started, if it's started in verbose mode.
+static RECORDED: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn parse(level: &str) -> Option<Self> {
+        match level.to_ascii_uppercase().as_str() {
+            "TRACE" => Some(Self::Trace),
+            "DEBUG" => Some(Self::Debug),
+            "INFO" => Some(Self::Info),
+            "WARN" | "WARNING" => Some(Self::Warn),
+            "ERROR" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    /// The name used by `tracing`.
+    fn tracing_name(self) -> &'static str {
+        match self {
+            Self::Trace => "TRACE",
+            Self::Debug => "DEBUG",
+            Self::Info => "INFO",
+            Self::Warn => "WARN",
+            Self::Error => "ERROR",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Trace => "trace",
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+/// An entry as a line of the JSON log, like `tracing` writes it.
+fn json_line(entry: &Entry) -> String {
+    json!({
+        "level": entry.level.tracing_name(),
+        "target": entry.target,
+        "fields": { "message": entry.message },
+    })
+    .to_string()
+}
+
+fn parse_json_line(line: &str) -> Option<Entry> {
+    let value: Value = serde_json::from_str(line).ok()?;
+
+    let level = Level::parse(value.get("level")?.as_str()?)?;
+    let target = value
+        .get("target")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let message = value
+        .pointer("/fields/message")
+        .or_else(|| value.get("message"))
+        .and_then(Value::as_str)?;
+
+    Some(Entry {
+        level,
+        target: target.to_string(),
+        message: message.to_string(),
+    })
+}
+
+fn parse_text_line(line: &str) -> Option<Entry> {
+    let mut words = line.split_whitespace();
+
+    // The timestamp is optional, so the level is either the first or second word.
+    let (level, rest) = loop {
+        let word = words.next()?;
+        if let Some(level) = Level::parse(word) {
+            break (level, words.collect::<Vec<_>>().join(" "));
+        }
+        if word.chars().next().is_some_and(|c| !c.is_ascii_digit()) {
+            return None;
+        }
+    };
+
+    let (target, message) = match rest.split_once(": ") {
+        Some((target, message)) if !target.contains(' ') => (target, message),
+        _ => ("", rest.as_str()),
+    };
+
+    Some(Entry {
+        level,
+        target: target.to_string(),
+        message: message.to_string(),
+    })
+}
+
+/// Start recording the messages of the extension for a session started in verbose mode.
+pub fn begin() {
+    *RECORDED.lock().unwrap_or_else(|err| err.into_inner()) = Some(Vec::new());
+}
+
+/// Record a message of the extension, if a session is started in verbose mode.
+pub fn record(level: Level, target: &str, message: &str) {
+    if let Some(lines) = RECORDED
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .as_mut()
+    {
+        lines.push(json_line(&Entry {
+            level,
+            target: target.to_string(),
+            message: message.to_string(),
+        }));
+    }
+}
+
+/// Stop recording, and write the messages together with the error the session failed with to
+/// [`EXTENSION_LOG_PATH`].
+pub fn finish(error: Option<&str>) {
+    let Some(mut lines) = RECORDED
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .take()
+    else {
+        return;
+    };
+
+    if let Some(error) = error {
+        lines.push(json_line(&Entry {
+            level: Level::Error,
+            target: module_path!().to_string(),
+            message: error.to_string(),
+        }));
+    }
+
+    let contents: String = lines.iter().map(|line| format!("{line}\n")).collect();
+    if let Err(err) = fs::write(EXTENSION_LOG_PATH, contents) {
+        log!("Failed to write the messages of the session to '{EXTENSION_LOG_PATH}': {err}");
+    }
+}
+
+/// Parse all log entries which could be recognized, skipping other lines.
+pub fn parse(contents: &str) -> Vec<Entry> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| parse_json_line(line).or_else(|| parse_text_line(line)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Entry, Level};
+
+    #[test]
+    fn parse_text() {
+        let contents = "2024-05-01T10:00:00.000Z  WARN probe_rs::session: Probe is slow\n\
+                        ERROR probe_rs::flashing: Flashing failed: timeout\n\
+                        some unrelated line\n";
+
+        assert_eq!(
+            super::parse(contents),
+            [
+                Entry {
+                    level: Level::Warn,
+                    target: "probe_rs::session".to_string(),
+                    message: "Probe is slow".to_string(),
+                },
+                Entry {
+                    level: Level::Error,
+                    target: "probe_rs::flashing".to_string(),
+                    message: "Flashing failed: timeout".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_json() {
+        let contents = r#"{"timestamp":"2024-05-01T10:00:00Z","level":"INFO","fields":{"message":"Attached"},"target":"probe_rs_tools::cmd::dap_server"}"#;
+
+        assert_eq!(
+            super::parse(contents),
+            [Entry {
+                level: Level::Info,
+                target: "probe_rs_tools::cmd::dap_server".to_string(),
+                message: "Attached".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn json_line() {
+        let entry = Entry {
+            level: Level::Warn,
+            target: "zed_probe_rs::validate".to_string(),
+            message: "'chip' is \"nRF52840\"".to_string(),
+        };
+
+        let line = super::json_line(&entry);
+
+        assert_eq!(
+            line,
+            r#"{"fields":{"message":"'chip' is \"nRF52840\""},"level":"WARN","target":"zed_probe_rs::validate"}"#
+        );
+        assert_eq!(super::parse(&line), [entry]);
+    }
+}
@@ -0,0 +1,126 @@
+//! Guidance for projects in which probe-rs is not set up yet.
+//!
+//! A new user usually misses several things at once. Instead of failing with the first
+//! missing piece, all remaining setup steps are listed in order.
+
+use std::fmt::Write;
+
+use zed_extension_api::{Worktree, serde_json::Value};
+
+const DEBUG_CONFIG_PATH: &str = ".zed/debug.json";
+
+/// What is already set up for the project.
+#[derive(Debug, Default)]
+pub struct Prerequisites {
+    pub probe_rs: bool,
+    pub chip: bool,
+    pub scenarios: bool,
+}
+
+/// A setup step, in the order in which they have to be done.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    InstallProbeRs,
+    SelectChip,
+    SaveScenario,
+}
+
+impl Step {
+    const ALL: [Step; 3] = [Step::InstallProbeRs, Step::SelectChip, Step::SaveScenario];
+
+    fn is_done(self, prerequisites: &Prerequisites) -> bool {
+        match self {
+            Step::InstallProbeRs => prerequisites.probe_rs,
+            Step::SelectChip => prerequisites.chip,
+            Step::SaveScenario => prerequisites.scenarios,
+        }
+    }
+
+    fn instructions(self) -> &'static str {
+        match self {
+            Step::InstallProbeRs => {
+                "Install probe-rs, see <https://probe.rs/docs/getting-started/installation/>, or set the path of the binary in the debugger settings of Zed."
+            }
+            Step::SelectChip => {
+                "Select the chip of the target with `chip` in the scenario, or for all scenarios in `.zed/probe-rs.json`. `probe-rs chip list` shows the supported chips."
+            }
+            Step::SaveScenario => {
+                "Save a probe-rs scenario in `.zed/debug.json`, so it can be adjusted. For projects using probe-run, `/probe-rs-migrate` suggests one."
+            }
+        }
+    }
+}
+
+impl Prerequisites {
+    /// The setup steps which are still missing.
+    pub fn pending_steps(&self) -> Vec<Step> {
+        Step::ALL
+            .into_iter()
+            .filter(|step| !step.is_done(self))
+            .collect()
+    }
+}
+
+/// Whether the worktree has probe-rs scenarios in `.zed/debug.json`.
+pub fn has_scenarios(worktree: &Worktree) -> bool {
+    crate::files::read_optional(
+        worktree,
+        DEBUG_CONFIG_PATH,
+        "saved scenarios are not checked",
+    )
+    .and_then(|contents| crate::jsonc::parse(&contents).ok())
+    .and_then(|scenarios| {
+        scenarios.as_array().map(|scenarios| {
+            scenarios.iter().any(|scenario| {
+                scenario.get("adapter").and_then(Value::as_str) == Some(crate::ADAPTER_NAME)
+            })
+        })
+    })
+    .unwrap_or(false)
+}
+
+/// Instructions for all pending setup steps.
+pub fn guidance(prerequisites: &Prerequisites) -> String {
+    let mut guidance = "probe-rs is not set up for this project yet. To get started:".to_string();
+
+    for (number, step) in prerequisites.pending_steps().into_iter().enumerate() {
+        let _ = write!(guidance, "\n{}. {}", number + 1, step.instructions());
+    }
+
+    guidance
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Prerequisites, Step};
+
+    #[test]
+    fn pending_steps() {
+        assert_eq!(
+            Prerequisites::default().pending_steps(),
+            [Step::InstallProbeRs, Step::SelectChip, Step::SaveScenario]
+        );
+
+        let prerequisites = Prerequisites {
+            chip: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            prerequisites.pending_steps(),
+            [Step::InstallProbeRs, Step::SaveScenario]
+        );
+    }
+
+    #[test]
+    fn guidance_is_numbered() {
+        let prerequisites = Prerequisites {
+            probe_rs: false,
+            chip: true,
+            scenarios: true,
+        };
+
+        let guidance = super::guidance(&prerequisites);
+        assert!(guidance.contains("\n1. Install probe-rs"), "{guidance}");
+        assert!(!guidance.contains("\n2."), "{guidance}");
+    }
+}
@@ -0,0 +1,118 @@
+//! Path handling for paths on the host.
+
+/// Join a relative path onto a root directory, leaving absolute paths unchanged.
+pub fn join(root: Option<&str>, path: &str) -> String {
+    match root {
+        Some(root) if !is_absolute(path) => {
+            format!("{}/{path}", root.trim_end_matches(['/', '\\']))
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// Check if a path is absolute, on any of the platforms Zed runs on.
+///
+/// `std::path::Path` can't be used for this, because the extension is compiled to
+/// WebAssembly and doesn't know about the host platform.
+pub fn is_absolute(path: &str) -> bool {
+    let bytes = path.as_bytes();
+
+    path.starts_with(['/', '\\'])
+        || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+}
+
+/// A path inside `root`, relative to it, or `None` if it's outside of `root`.
+pub fn strip_root<'a>(root: &str, path: &'a str) -> Option<&'a str> {
+    let root = root.trim_end_matches(['/', '\\']);
+
+    path.strip_prefix(root)?
+        .strip_prefix(['/', '\\'])
+        .filter(|relative| !relative.is_empty())
+}
+
+/// Remove quotes around a path, as added by "Copy as path" in the Windows Explorer.
+///
+/// Arguments are passed to tools as a list and never go through a shell, so paths with spaces
+/// don't need quotes, and quotes would become part of the path.
+pub fn unquote(path: &str) -> &str {
+    let path = path.trim();
+
+    ['"', '\'']
+        .iter()
+        .find_map(|quote| path.strip_prefix(*quote)?.strip_suffix(*quote))
+        .unwrap_or(path)
+}
+
+/// The absolute path of a file in the working directory of the extension.
+///
+/// Tools like probe-rs run outside of the extension sandbox, so they need the full path.
+pub fn in_work_dir(path: &str) -> Result<String, String> {
+    let work_dir = std::env::current_dir()
+        .map_err(|err| format!("Failed to get the extension directory: {err}"))?;
+
+    let path = work_dir.join(path);
+    path.to_str().map(str::to_string).ok_or_else(|| {
+        format!(
+            "The extension directory '{}' is not valid UTF-8",
+            path.display()
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn join() {
+        assert_eq!(
+            super::join(Some("/home/user/project"), "target/app"),
+            "/home/user/project/target/app"
+        );
+        assert_eq!(
+            super::join(Some("/home/user/project/"), "target/app"),
+            "/home/user/project/target/app"
+        );
+        assert_eq!(
+            super::join(Some("/home/user/project"), "/tmp/app"),
+            "/tmp/app"
+        );
+        assert_eq!(
+            super::join(Some("C:\\project"), "D:\\firmware\\app.elf"),
+            "D:\\firmware\\app.elf"
+        );
+        assert_eq!(super::join(None, "target/app"), "target/app");
+        assert_eq!(
+            super::join(Some("C:\\My Projects\\board"), "target/app one.elf"),
+            "C:\\My Projects\\board/target/app one.elf"
+        );
+    }
+
+    #[test]
+    fn strip_root() {
+        assert_eq!(
+            super::strip_root("/home/me/board", "/home/me/board/.zed/targets/h7.yaml"),
+            Some(".zed/targets/h7.yaml")
+        );
+        assert_eq!(
+            super::strip_root("C:\\board\\", "C:\\board\\h7.yaml"),
+            Some("h7.yaml")
+        );
+        assert_eq!(
+            super::strip_root("/home/me/board", "/home/me/board2/h7.yaml"),
+            None
+        );
+        assert_eq!(super::strip_root("/home/me/board", "/opt/h7.yaml"), None);
+    }
+
+    #[test]
+    fn unquote() {
+        assert_eq!(
+            super::unquote("\"C:\\My Projects\\app.elf\""),
+            "C:\\My Projects\\app.elf"
+        );
+        assert_eq!(super::unquote(" '/home/me/my fw' "), "/home/me/my fw");
+        assert_eq!(super::unquote("/home/me/my fw"), "/home/me/my fw");
+        // Quotes which belong to the name are kept.
+        assert_eq!(super::unquote("\"a'"), "\"a'");
+        assert_eq!(super::unquote("\""), "\"");
+    }
+}
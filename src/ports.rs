@@ -0,0 +1,260 @@
+//! Choosing the port of the dap-server, around servers of earlier sessions which didn't exit.
+//!
+//! A dap-server which hangs after its session, for example after the probe was unplugged,
+//! keeps its port bound. The new server then can't listen on it, and the session only fails
+//! with a timeout when Zed connects. Before a server is started, the listening ports are
+//! listed with a tool of the host, and a free port is used instead of a bound one. The
+//! extension can't stop the old process, so it's only reported in the log.
+
+use std::sync::Mutex;
+
+use zed_extension_api::{self as zed, Os};
+
+use crate::server::{DEFAULT_PORT, PORT_COUNT};
+
+/// Messages which were logged already, so a missing tool isn't reported for every session.
+static LOGGED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn log_once(message: String) {
+    let mut logged = LOGGED.lock().unwrap_or_else(|err| err.into_inner());
+    if !logged.contains(&message) {
+        log!("{message}");
+        logged.push(message);
+    }
+}
+
+/// A process listening on a TCP port.
+#[derive(Debug, PartialEq)]
+struct Listener {
+    port: u16,
+    /// The name of the process if known, and its id.
+    process: String,
+}
+
+/// The port at the end of an address like `127.0.0.1:50000`, `*:50000` or `[::1]:50000`.
+fn port_of(address: &str) -> Option<u16> {
+    address.rsplit_once(':')?.1.parse().ok()
+}
+
+/// Parse the output of `lsof -nP -iTCP -sTCP:LISTEN`.
+fn parse_lsof(output: &str) -> Vec<Listener> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (command, pid) = (fields.first()?, fields.get(1)?);
+            // The name is followed by `(LISTEN)`.
+            let address = fields.iter().rev().find(|field| field.contains(':'))?;
+
+            Some(Listener {
+                port: port_of(address)?,
+                process: format!("'{command}' (PID {pid})"),
+            })
+        })
+        .collect()
+}
+
+/// Parse the output of `ss -ltnp` on Linux, which only shows the processes of the user.
+fn parse_ss(output: &str) -> Vec<Listener> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            (*fields.first()? == "LISTEN").then_some(())?;
+
+            // Like `users:(("probe-rs",pid=4242,fd=9))`.
+            let process = fields
+                .iter()
+                .find_map(|field| {
+                    let users = field.strip_prefix("users:((\"")?;
+                    let (command, rest) = users.split_once('"')?;
+                    let pid = rest.split_once("pid=")?.1.split([',', ')']).next()?;
+                    Some(format!("'{command}' (PID {pid})"))
+                })
+                .unwrap_or_else(|| "a process of another user".to_string());
+
+            Some(Listener {
+                port: port_of(fields.get(3)?)?,
+                process,
+            })
+        })
+        .collect()
+}
+
+/// Parse the output of `netstat -ano -p TCP` on Windows, which only shows the process ids.
+fn parse_netstat(output: &str) -> Vec<Listener> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [protocol, local, _, state, pid] = fields.as_slice() else {
+                return None;
+            };
+
+            (*protocol == "TCP" && *state == "LISTENING").then_some(())?;
+
+            Some(Listener {
+                port: port_of(local)?,
+                process: format!("PID {pid}"),
+            })
+        })
+        .collect()
+}
+
+/// The listening ports on the host.
+fn listeners(os: Os) -> Result<Vec<Listener>, String> {
+    match os {
+        Os::Windows => crate::process::run("netstat", &["-ano", "-p", "TCP"].map(str::to_string))
+            .map(|output| parse_netstat(&output)),
+        Os::Mac | Os::Linux => {
+            let args = ["-nP", "-iTCP", "-sTCP:LISTEN"].map(str::to_string);
+
+            // lsof exits with an error if nothing listens at all.
+            match crate::process::run_with_status("lsof", &args) {
+                Ok(output) => Ok(parse_lsof(&output.stdout)),
+                // Minimal Linux installations often lack lsof, but have ss.
+                Err(err) if os == Os::Linux => {
+                    log_once(format!("Listing the ports with 'ss', since {err}"));
+                    crate::process::run("ss", &["-ltnp".to_string()])
+                        .map(|output| parse_ss(&output))
+                }
+                Err(err) => Err(err),
+            }
+        }
+    }
+}
+
+/// The first of the ports of the extension which is not bound by any of the listeners.
+fn free_port(listeners: &[Listener]) -> Option<u16> {
+    (DEFAULT_PORT..DEFAULT_PORT + PORT_COUNT)
+        .find(|port| !listeners.iter().any(|listener| listener.port == *port))
+}
+
+/// Choose the port for a new dap-server.
+///
+/// `recorded` is the port of the server started for the last session of the worktree, which
+/// is most likely the one which didn't exit. If the ports can't be listed, the default port is
+/// used.
+pub fn choose(recorded: Option<u16>) -> Result<u16, String> {
+    let (os, _) = zed::current_platform();
+
+    let listeners = match listeners(os) {
+        Ok(listeners) => listeners,
+        Err(err) => {
+            log_once(format!(
+                "Can't check whether port {DEFAULT_PORT} is free: {err}"
+            ));
+            return Ok(DEFAULT_PORT);
+        }
+    };
+
+    let port = free_port(&listeners).ok_or_else(|| {
+        format!(
+            "Ports {DEFAULT_PORT} to {} are all in use, stop the dap-servers of earlier \
+             sessions which are still running",
+            DEFAULT_PORT + PORT_COUNT - 1
+        )
+    })?;
+
+    for listener in listeners
+        .iter()
+        .filter(|listener| (DEFAULT_PORT..port).contains(&listener.port))
+    {
+        let origin = if recorded == Some(listener.port) {
+            "the dap-server of the last session in this worktree"
+        } else {
+            "another process"
+        };
+
+        log!(
+            "Port {} is still bound by {} ({origin}), which probably didn't exit after its \
+             session. Using port {port} instead, stop the old process if it hangs.",
+            listener.port,
+            listener.process,
+        );
+    }
+
+    Ok(port)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Listener;
+
+    #[test]
+    fn parse_lsof() {
+        let output = "COMMAND   PID USER   FD   TYPE DEVICE SIZE/OFF NODE NAME\n\
+                      probe-rs 4242 me     9u  IPv4 0x1234      0t0  TCP 127.0.0.1:50000 (LISTEN)\n\
+                      sshd      812 root   3u  IPv6  23456      0t0  TCP *:22 (LISTEN)\n";
+
+        assert_eq!(
+            super::parse_lsof(output),
+            [
+                Listener {
+                    port: 50_000,
+                    process: "'probe-rs' (PID 4242)".to_string()
+                },
+                Listener {
+                    port: 22,
+                    process: "'sshd' (PID 812)".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_ss() {
+        let output = "State  Recv-Q Send-Q Local Address:Port Peer Address:Port Process\n\
+                      LISTEN 0      128        127.0.0.1:50000      0.0.0.0:*     users:((\"probe-rs\",pid=4242,fd=9))\n\
+                      LISTEN 0      4096            [::]:22            [::]:*\n";
+
+        assert_eq!(
+            super::parse_ss(output),
+            [
+                Listener {
+                    port: 50_000,
+                    process: "'probe-rs' (PID 4242)".to_string()
+                },
+                Listener {
+                    port: 22,
+                    process: "a process of another user".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_netstat() {
+        let output = "\nActive Connections\n\n  Proto  Local Address          Foreign Address        State           PID\n  \
+                      TCP    0.0.0.0:135            0.0.0.0:0              LISTENING       1020\n  \
+                      TCP    127.0.0.1:50000        0.0.0.0:0              LISTENING       4242\n  \
+                      TCP    127.0.0.1:50000        127.0.0.1:50123        ESTABLISHED     4242\n";
+
+        assert_eq!(
+            super::parse_netstat(output)
+                .iter()
+                .map(|listener| listener.port)
+                .collect::<Vec<_>>(),
+            [135, 50_000]
+        );
+    }
+
+    #[test]
+    fn free_port() {
+        let listener = |port| Listener {
+            port,
+            process: String::new(),
+        };
+
+        assert_eq!(super::free_port(&[listener(22)]), Some(50_000));
+        assert_eq!(
+            super::free_port(&[listener(50_000), listener(50_001)]),
+            Some(50_002)
+        );
+        assert_eq!(
+            super::free_port(&(50_000..50_010).map(listener).collect::<Vec<_>>()),
+            None
+        );
+    }
+}
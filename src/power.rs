@@ -0,0 +1,288 @@
+//! Switching the power supply of the target by the probe, for `targetPower`.
+//!
+//! Some probes can power the target, which saves a separate supply on the desk. probe-rs
+//! can't switch it, so the extension uses the tool of the probe vendor before the session
+//! starts. Only J-Link probes are supported, through J-Link Commander, and they supply a fixed
+//! 5 V on pin 19 of their connector. Afterwards the extension checks the voltage the probe
+//! measures on the target, since flashing an unpowered target fails with connection errors
+//! which don't mention power at all.
+
+use std::fs;
+
+use zed_extension_api::{self as zed, Os, Worktree, serde_json::Value};
+
+use crate::{enumeration::ConnectedProbe, errors::Category, probes::ProbeKind};
+
+/// Below this voltage on VTref, the target is considered unpowered.
+const MINIMUM_VOLTAGE: f64 = 1.2;
+
+/// How far the measured voltage may be off the expected one, relative to it.
+const VOLTAGE_TOLERANCE: f64 = 0.1;
+
+/// Command file for J-Link Commander, in the session directory.
+const SCRIPT_FILE: &str = "target-power.jlink";
+
+/// The `targetPower` option of a scenario.
+#[derive(Debug, PartialEq)]
+pub struct TargetPower {
+    /// Whether the probe powers the target, or leaves it to its own supply.
+    pub enabled: bool,
+    /// Voltage in volts the target is expected to run at.
+    pub voltage: Option<f64>,
+}
+
+/// Read the `targetPower` option, which is a boolean, or an object with `enabled` and the
+/// expected `voltage`.
+pub fn parse(config: &Value) -> Result<Option<TargetPower>, String> {
+    let invalid = || {
+        "'targetPower' must be true, false, or an object with 'enabled' and 'voltage'".to_string()
+    };
+
+    match config.get("targetPower") {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Bool(enabled)) => Ok(Some(TargetPower {
+            enabled: *enabled,
+            voltage: None,
+        })),
+        Some(Value::Object(power)) => {
+            let enabled = match power.get("enabled") {
+                None => true,
+                Some(enabled) => enabled.as_bool().ok_or_else(invalid)?,
+            };
+            let voltage = match power.get("voltage") {
+                None => None,
+                Some(voltage) => Some(
+                    voltage
+                        .as_f64()
+                        .filter(|voltage| *voltage > 0.0)
+                        .ok_or_else(invalid)?,
+                ),
+            };
+
+            Ok(Some(TargetPower { enabled, voltage }))
+        }
+        Some(_) => Err(invalid()),
+    }
+}
+
+/// The probe which is used for the session, from the scenario or the only connected probe.
+fn probe(config: &Value, probes: Option<&[ConnectedProbe]>) -> Option<(ProbeKind, Option<String>)> {
+    if let Some(probe) = config.get("probe") {
+        let id = |field| {
+            probe
+                .get(field)
+                .and_then(Value::as_u64)
+                .and_then(|id| u16::try_from(id).ok())
+        };
+        let serial = probe
+            .get("serial_number")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        return Some((
+            ProbeKind::from_usb_id(id("vendor_id")?, id("product_id")?)?,
+            serial,
+        ));
+    }
+
+    match probes? {
+        [probe] => Some((
+            ProbeKind::from_usb_id(probe.vendor_id, probe.product_id)?,
+            probe.serial.clone(),
+        )),
+        _ => None,
+    }
+}
+
+/// The command file which switches the power and shows the status of the probe afterwards.
+///
+/// `perm` keeps the setting when J-Link Commander exits, so the target stays powered for the
+/// session. The short wait lets the supply of the target settle before VTref is measured.
+fn script(enabled: bool) -> String {
+    let state = if enabled { "on" } else { "off" };
+
+    format!("power {state} perm\nsleep 200\nst\nexit\n")
+}
+
+fn commander_args(script: &str, serial: Option<&str>) -> Vec<String> {
+    let mut args = ["-NoGui", "1", "-ExitOnError", "1", "-AutoConnect", "0"]
+        .map(str::to_string)
+        .to_vec();
+
+    if let Some(serial) = serial {
+        args.extend(["-SelectEmuBySN".to_string(), serial.to_string()]);
+    }
+
+    args.extend(["-CommandFile".to_string(), script.to_string()]);
+
+    args
+}
+
+/// The last voltage on VTref in the output of J-Link Commander, like `VTref=3.297V`.
+fn parse_vtref(output: &str) -> Option<f64> {
+    output.rmatch_indices("VTref=").find_map(|(start, _)| {
+        let value = &output[start + "VTref=".len()..];
+        let end = value.find('V')?;
+
+        value[..end].trim().parse().ok()
+    })
+}
+
+/// Check the measured voltage of the target.
+fn check_voltage(measured: f64, expected: Option<f64>) -> Result<(), String> {
+    if measured < MINIMUM_VOLTAGE {
+        return Err(format!(
+            "The target is not powered, the probe measured {measured:.2} V on VTref. Check the \
+             power supply of the target, or let the probe power it with \"targetPower\": true"
+        ));
+    }
+
+    if let Some(expected) = expected
+        && (measured - expected).abs() > expected * VOLTAGE_TOLERANCE
+    {
+        return Err(format!(
+            "The target runs at {measured:.2} V, but {expected:.2} V are expected by 'targetPower'"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Switch the power of the target as configured, and check that it is powered.
+pub fn apply(
+    worktree: &Worktree,
+    config: &Value,
+    probes: Option<&[ConnectedProbe]>,
+    session_directory: &str,
+) -> Result<(), String> {
+    let Some(power) = parse(config).map_err(|err| Category::Probe.error(err))? else {
+        return Ok(());
+    };
+
+    let serial = match probe(config, probes) {
+        Some((ProbeKind::JLink, serial)) => serial,
+        Some((kind, _)) => {
+            return Err(Category::Probe.error(format!(
+                "'targetPower' is only supported for J-Link probes, the {} can't power the target",
+                kind.name()
+            )));
+        }
+        None => {
+            return Err(Category::Probe
+                .error("'targetPower' needs to know the probe, select the J-Link with 'probe'"));
+        }
+    };
+
+    let (os, _) = zed::current_platform();
+    let name = if os == Os::Windows {
+        "JLink"
+    } else {
+        "JLinkExe"
+    };
+    let commander = worktree.which(name).ok_or_else(|| {
+        Category::Probe.error(format!(
+            "'targetPower' needs J-Link Commander ('{name}'), which was not found on the PATH. \
+             Install the J-Link Software and Documentation Pack from SEGGER"
+        ))
+    })?;
+
+    let script_file = format!("{session_directory}/{SCRIPT_FILE}");
+    fs::write(&script_file, script(power.enabled))
+        .map_err(|err| format!("Failed to write '{script_file}': {err}"))?;
+    let script = crate::paths::in_work_dir(&script_file)?;
+
+    let output = crate::process::run(&commander, &commander_args(&script, serial.as_deref()))
+        .map_err(|err| Category::Probe.error(err))?;
+
+    let state = if power.enabled { "on" } else { "off" };
+    log!("Switched the target power of the J-Link {state}");
+
+    let measured = parse_vtref(&output).ok_or_else(|| {
+        Category::Probe.error("J-Link Commander didn't report the target voltage")
+    })?;
+    check_voltage(measured, power.voltage).map_err(|err| Category::Target.error(err))
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    use super::TargetPower;
+    use crate::{enumeration::ConnectedProbe, probes::ProbeKind};
+
+    #[test]
+    fn parse() {
+        assert_eq!(super::parse(&json!({})), Ok(None));
+        assert_eq!(
+            super::parse(&json!({ "targetPower": false })),
+            Ok(Some(TargetPower {
+                enabled: false,
+                voltage: None
+            }))
+        );
+        assert_eq!(
+            super::parse(&json!({ "targetPower": { "voltage": 3.3 } })),
+            Ok(Some(TargetPower {
+                enabled: true,
+                voltage: Some(3.3)
+            }))
+        );
+        assert!(super::parse(&json!({ "targetPower": "on" })).is_err());
+        assert!(super::parse(&json!({ "targetPower": { "voltage": -1 } })).is_err());
+    }
+
+    #[test]
+    fn probe() {
+        let jlink = ConnectedProbe {
+            vendor_id: 0x1366,
+            product_id: 0x0105,
+            serial: Some("000123456789".to_string()),
+        };
+
+        assert_eq!(
+            super::probe(&json!({}), Some(&[jlink])),
+            Some((ProbeKind::JLink, Some("000123456789".to_string())))
+        );
+
+        let config = json!({ "probe": { "vendor_id": 0x0483, "product_id": 0x374e } });
+        assert_eq!(
+            super::probe(&config, None),
+            Some((ProbeKind::StLinkV3, None))
+        );
+
+        assert_eq!(super::probe(&json!({}), Some(&[])), None);
+    }
+
+    #[test]
+    fn commander_args() {
+        assert_eq!(
+            super::commander_args("/ext/target-power.jlink", Some("123"))[6..],
+            [
+                "-SelectEmuBySN",
+                "123",
+                "-CommandFile",
+                "/ext/target-power.jlink"
+            ]
+        );
+        assert_eq!(super::script(true), "power on perm\nsleep 200\nst\nexit\n");
+    }
+
+    #[test]
+    fn parse_vtref() {
+        let output = "S/N: 123\nVTref=0.000V\n\nVTref=3.297V\nITarget=0mA\n";
+        assert_eq!(super::parse_vtref(output), Some(3.297));
+        assert_eq!(super::parse_vtref("Connecting to J-Link"), None);
+    }
+
+    #[test]
+    fn check_voltage() {
+        assert!(super::check_voltage(3.29, None).is_ok());
+        assert!(super::check_voltage(3.29, Some(3.3)).is_ok());
+        assert!(
+            super::check_voltage(0.0, None)
+                .unwrap_err()
+                .contains("not powered")
+        );
+        assert!(super::check_voltage(1.8, Some(3.3)).is_err());
+    }
+}
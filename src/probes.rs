@@ -0,0 +1,129 @@
+//! Knowledge about debug probes, identified by their USB vendor and product id.
+
+/// Families of debug probes with known capabilities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeKind {
+    StLinkV2,
+    StLinkV3,
+    JLink,
+    EspUsbJtag,
+    Ftdi,
+}
+
+impl ProbeKind {
+    pub fn from_usb_id(vendor_id: u16, product_id: u16) -> Option<Self> {
+        match (vendor_id, product_id) {
+            (0x0483, 0x3748 | 0x374b | 0x374a | 0x3752) => Some(Self::StLinkV2),
+            (0x0483, 0x374e | 0x374f | 0x3753 | 0x3754) => Some(Self::StLinkV3),
+            (0x1366, _) => Some(Self::JLink),
+            (0x303a, 0x1001) => Some(Self::EspUsbJtag),
+            (0x0403, 0x6010 | 0x6011 | 0x6014) => Some(Self::Ftdi),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::StLinkV2 => "ST-Link V2",
+            Self::StLinkV3 => "ST-Link V3",
+            Self::JLink => "J-Link",
+            Self::EspUsbJtag => "ESP USB-JTAG",
+            Self::Ftdi => "FTDI",
+        }
+    }
+
+    /// Whether the probe can use the wire protocol at all.
+    pub fn supports_protocol(self, protocol: WireProtocol) -> bool {
+        match self {
+            Self::StLinkV2 | Self::StLinkV3 | Self::JLink => true,
+            Self::EspUsbJtag | Self::Ftdi => protocol == WireProtocol::Jtag,
+        }
+    }
+
+    /// Maximum protocol speed in kHz the probe supports for the given protocol.
+    ///
+    /// If the protocol is not known, the highest speed for any protocol is returned. `None` is
+    /// returned for a protocol the probe doesn't support.
+    pub fn max_speed_khz(self, protocol: Option<WireProtocol>) -> Option<u32> {
+        let (swd, jtag) = match self {
+            Self::StLinkV2 => (Some(4_000), 9_000),
+            Self::StLinkV3 => (Some(24_000), 21_000),
+            Self::JLink => (Some(50_000), 50_000),
+            Self::EspUsbJtag => (None, 40_000),
+            Self::Ftdi => (None, 30_000),
+        };
+
+        match protocol {
+            Some(WireProtocol::Swd) => swd,
+            Some(WireProtocol::Jtag) => Some(jtag),
+            None => Some(swd.map_or(jtag, |swd| swd.max(jtag))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireProtocol {
+    Swd,
+    Jtag,
+}
+
+impl WireProtocol {
+    pub fn parse(protocol: &str) -> Option<Self> {
+        match protocol {
+            "Swd" => Some(Self::Swd),
+            "Jtag" => Some(Self::Jtag),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Swd => "SWD",
+            Self::Jtag => "JTAG",
+        }
+    }
+
+    /// Maximum speed in kHz of the protocol itself, independent of the probe.
+    pub fn max_speed_khz(self) -> u32 {
+        match self {
+            Self::Swd => 50_000,
+            Self::Jtag => 50_000,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ProbeKind, WireProtocol};
+
+    #[test]
+    fn from_usb_id() {
+        assert_eq!(
+            ProbeKind::from_usb_id(0x0483, 0x3748),
+            Some(ProbeKind::StLinkV2)
+        );
+        assert_eq!(
+            ProbeKind::from_usb_id(0x1366, 0x0105),
+            Some(ProbeKind::JLink)
+        );
+        assert_eq!(ProbeKind::from_usb_id(0xc251, 0xf002), None);
+    }
+
+    #[test]
+    fn supports_protocol() {
+        assert!(ProbeKind::StLinkV2.supports_protocol(WireProtocol::Jtag));
+        assert!(!ProbeKind::Ftdi.supports_protocol(WireProtocol::Swd));
+        assert!(!ProbeKind::EspUsbJtag.supports_protocol(WireProtocol::Swd));
+    }
+
+    #[test]
+    fn max_speed() {
+        assert_eq!(
+            ProbeKind::StLinkV2.max_speed_khz(Some(WireProtocol::Swd)),
+            Some(4_000)
+        );
+        assert_eq!(ProbeKind::StLinkV2.max_speed_khz(None), Some(9_000));
+        assert_eq!(ProbeKind::Ftdi.max_speed_khz(Some(WireProtocol::Swd)), None);
+        assert_eq!(ProbeKind::Ftdi.max_speed_khz(None), Some(30_000));
+    }
+}
@@ -0,0 +1,186 @@
+//! Running external tools on the host.
+
+use std::sync::Mutex;
+
+use zed_extension_api::{self as zed, Os};
+
+/// The environment tools are run with.
+static ENVIRONMENT: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// Run tools with the environment of the worktree shell, which includes what direnv or
+/// `nix develop` set up for the project, like a `PATH` with the probe-rs of the project.
+pub fn set_environment(env: Vec<(String, String)>) {
+    if let Ok(mut environment) = ENVIRONMENT.lock() {
+        *environment = env;
+    }
+}
+
+fn command(binary: &str, args: &[String]) -> zed::process::Command {
+    let env = ENVIRONMENT
+        .lock()
+        .map(|env| env.clone())
+        .unwrap_or_default();
+
+    zed::process::Command::new(binary)
+        .args(args.iter().cloned())
+        .envs(env)
+}
+
+/// Run a tool like probe-rs with the given arguments, returning stdout if it exited successfully.
+pub fn run(binary: &str, args: &[String]) -> Result<String, String> {
+    let output = command(binary, args)
+        .output()
+        .map_err(|err| format!("Failed to run '{binary}': {err}"))?;
+
+    if output.status != Some(0) {
+        let stderr = decode(&output.stderr);
+        return Err(format!(
+            "'{binary} {}' failed: {}",
+            args.join(" "),
+            stderr.trim()
+        ));
+    }
+
+    Ok(decode(&output.stdout))
+}
+
+/// Decode the output of a tool.
+///
+/// Tools usually write UTF-8, but some Windows tools write UTF-16 or use the code page of the
+/// console. Output which isn't valid UTF-8 is decoded lossily instead of failing, so paths
+/// containing characters like in non-ASCII user names show up as [`char::REPLACEMENT_CHARACTER`]
+/// and can be recognized with [`is_mangled`].
+pub fn decode(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xff, 0xfe]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .collect();
+
+        return String::from_utf16_lossy(&units);
+    }
+
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Whether text from a tool, like a path, was not valid UTF-8 and can't be used as is.
+pub fn is_mangled(text: &str) -> bool {
+    text.contains(char::REPLACEMENT_CHARACTER)
+}
+
+/// The result of a tool which is expected to fail sometimes.
+#[derive(Debug)]
+pub struct Output {
+    /// The exit code, or `None` if the tool was killed by a signal.
+    pub status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Run a tool, returning its output regardless of the exit code.
+pub fn run_with_status(binary: &str, args: &[String]) -> Result<Output, String> {
+    run_with_env(binary, args, &[])
+}
+
+/// Like [`run_with_status`], with additional environment variables, which take precedence
+/// over the ones of the worktree shell.
+pub fn run_with_env(
+    binary: &str,
+    args: &[String],
+    env: &[(String, String)],
+) -> Result<Output, String> {
+    let output = command(binary, args)
+        .envs(env.iter().cloned())
+        .output()
+        .map_err(|err| format!("Failed to run '{binary}': {err}"))?;
+
+    Ok(Output {
+        status: output.status,
+        stdout: decode(&output.stdout),
+        stderr: decode(&output.stderr),
+    })
+}
+
+/// Exit code of the timeout tool on Linux if the time ran out.
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// List the names in a directory outside of the sandbox, or `None` if it doesn't exist.
+pub fn list_directory(os: Os, directory: &str) -> Option<String> {
+    let (command, args) = match os {
+        Os::Windows => ("cmd", vec!["/c", "dir", "/b", directory]),
+        Os::Linux | Os::Mac => ("ls", vec!["-1", directory]),
+    };
+    let args: Vec<String> = args.into_iter().map(str::to_string).collect();
+
+    run(command, &args).ok()
+}
+
+/// The command which runs a tool with a timeout, using a tool of the host, or `None` if the
+/// host has no suitable tool.
+///
+/// Extensions can't cancel processes they started, so this is the only way to avoid waiting
+/// forever for a tool which hangs.
+pub fn with_timeout(
+    os: Os,
+    seconds: u32,
+    binary: &str,
+    args: &[String],
+) -> Option<(String, Vec<String>)> {
+    let mut timeout_args = match os {
+        Os::Linux => vec![seconds.to_string()],
+        // macOS has no `timeout`, but always comes with perl.
+        Os::Mac => vec![
+            "-e".to_string(),
+            "alarm shift; exec @ARGV or die".to_string(),
+            seconds.to_string(),
+        ],
+        Os::Windows => return None,
+    };
+
+    timeout_args.push(binary.to_string());
+    timeout_args.extend(args.iter().cloned());
+
+    let tool = if os == Os::Linux { "timeout" } else { "perl" };
+
+    Some((tool.to_string(), timeout_args))
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::Os;
+
+    #[test]
+    fn decode() {
+        assert_eq!(
+            super::decode("C:\\Users\\Jörg".as_bytes()),
+            "C:\\Users\\Jörg"
+        );
+
+        // UTF-16 with byte order mark, as written by some Windows tools.
+        let utf16: Vec<u8> = [0xfeff_u16, 'J' as u16, 0xf6, 'r' as u16]
+            .iter()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect();
+        assert_eq!(super::decode(&utf16), "Jör");
+
+        // "Jörg" in the Windows-1252 code page.
+        let path = super::decode(b"C:\\Users\\J\xf6rg");
+        assert!(super::is_mangled(&path));
+        assert!(!super::is_mangled("C:\\Users\\Jörg"));
+    }
+
+    #[test]
+    fn with_timeout() {
+        let args = ["list".to_string()];
+
+        let (command, args_linux) = super::with_timeout(Os::Linux, 5, "probe-rs", &args).unwrap();
+        assert_eq!(command, "timeout");
+        assert_eq!(args_linux, ["5", "probe-rs", "list"]);
+
+        let (command, args_mac) = super::with_timeout(Os::Mac, 5, "probe-rs", &args).unwrap();
+        assert_eq!(command, "perl");
+        assert_eq!(args_mac[2..], ["5", "probe-rs", "list"]);
+
+        assert!(super::with_timeout(Os::Windows, 5, "probe-rs", &args).is_none());
+    }
+}
@@ -0,0 +1,570 @@
+//! Detection of the embedded framework used by the project.
+//!
+//! Defaults which work for one framework are wrong for another, so the `Cargo.toml` of the
+//! worktree is checked for Embassy, RTIC and esp-hal, and generated scenarios are adjusted to
+//! them. The cargo runner in `.cargo/config.toml` is checked as well, since older projects
+//! often select the chip through `probe-run`. Like the settings, the result is kept in the
+//! extension state for hooks without a worktree.
+
+use zed_extension_api::{
+    Worktree,
+    serde_json::{Map, Value, json},
+};
+
+use crate::state::State;
+
+const STATE_KEY: &str = "project";
+
+/// Cargo configuration files, in the order cargo uses them.
+pub const CARGO_CONFIG_PATHS: &[&str] = &[".cargo/config.toml", ".cargo/config"];
+
+/// Chips supported by esp-hal, which are selected using a feature of the same name.
+const ESP_HAL_CHIPS: &[&str] = &[
+    "esp32", "esp32c2", "esp32c3", "esp32c6", "esp32h2", "esp32p4", "esp32s2", "esp32s3",
+];
+
+/// Crates which are only used by firmware, and mark a package as embedded.
+const EMBEDDED_CRATES: &[&str] = &[
+    "cortex-m-rt",
+    "riscv-rt",
+    "embassy-executor",
+    "rtic",
+    "cortex-m-rtic",
+    "esp-hal",
+    "defmt",
+    "panic-probe",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framework {
+    Embassy,
+    Rtic,
+    EspHal,
+}
+
+impl Framework {
+    /// Crates which identify the framework.
+    fn crates(self) -> &'static [&'static str] {
+        match self {
+            Framework::Embassy => &["embassy-executor"],
+            Framework::Rtic => &["rtic", "cortex-m-rtic"],
+            Framework::EspHal => &["esp-hal"],
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Framework::Embassy => "Embassy",
+            Framework::Rtic => "RTIC",
+            Framework::EspHal => "esp-hal",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [Framework::Embassy, Framework::Rtic, Framework::EspHal]
+            .into_iter()
+            .find(|framework| framework.name() == name)
+    }
+}
+
+/// A `probe-run` or `probe-rs run` cargo runner, with the options relevant for debugging.
+#[derive(Debug, Default, PartialEq)]
+pub struct Runner {
+    pub command: String,
+    pub chip: Option<String>,
+    pub probe: Option<String>,
+    pub speed: Option<u64>,
+    pub connect_under_reset: bool,
+}
+
+impl Runner {
+    /// Find the runner in the contents of a cargo configuration file.
+    pub fn parse(cargo_config: &str) -> Option<Self> {
+        let runner = cargo_config.lines().find_map(|line| {
+            let value = line.trim().strip_prefix("runner")?.trim_start();
+            let value = value.strip_prefix('=')?.trim();
+
+            Some(value.trim_matches(['"', '\'']))
+        })?;
+
+        let mut args = runner.split_whitespace();
+        let program = args.next()?;
+        let program = program.rsplit(['/', '\\']).next().unwrap_or(program);
+        let program = program.strip_suffix(".exe").unwrap_or(program);
+
+        let command = match program {
+            "probe-run" => "probe-run",
+            "probe-rs" if args.next() == Some("run") => "probe-rs run",
+            _ => return None,
+        };
+
+        let mut result = Runner {
+            command: command.to_string(),
+            ..Default::default()
+        };
+
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag, Some(value.to_string())),
+                None => (arg, None),
+            };
+
+            let mut value = || inline.clone().or_else(|| args.next().map(str::to_string));
+
+            match flag {
+                "--chip" => result.chip = value(),
+                "--probe" => result.probe = value(),
+                "--speed" => result.speed = value().and_then(|speed| speed.parse().ok()),
+                "--connect-under-reset" => result.connect_under_reset = true,
+                _ => {}
+            }
+        }
+
+        Some(result)
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Project {
+    pub frameworks: Vec<Framework>,
+    /// Chip selected by the project, if it can be determined from the dependencies.
+    pub chip: Option<String>,
+    /// Whether the project logs using RTT, e.g. through defmt.
+    pub uses_rtt: bool,
+    /// Packages of the worktree which depend on firmware crates.
+    pub embedded_packages: Vec<String>,
+}
+
+impl Project {
+    /// Detect the project of the worktree, and remember it for hooks without a worktree.
+    pub fn load(worktree: &Worktree) -> Self {
+        const CONSEQUENCE: &str = "configure the chip and RTT in the scenario instead";
+
+        let manifest =
+            crate::files::read_optional(worktree, "Cargo.toml", CONSEQUENCE).unwrap_or_default();
+        let runner = CARGO_CONFIG_PATHS
+            .iter()
+            .find_map(|path| crate::files::read_optional(worktree, path, CONSEQUENCE))
+            .and_then(|cargo_config| Runner::parse(&cargo_config));
+
+        let mut project = Self::detect(&manifest, runner.as_ref());
+
+        // Only explicitly listed members are checked, expanding globs would need a directory
+        // listing, which extensions can't get.
+        let member_manifests = workspace_members(&manifest)
+            .into_iter()
+            .filter(|member| !member.contains('*'))
+            .filter_map(|member| {
+                crate::files::read_optional(
+                    worktree,
+                    &format!("{member}/Cargo.toml"),
+                    "its scenarios are not preferred by the locator",
+                )
+            });
+
+        project.embedded_packages = std::iter::once(manifest.clone())
+            .chain(member_manifests)
+            .filter(|manifest| is_embedded(manifest))
+            .filter_map(|manifest| package_name(&manifest).map(str::to_string))
+            .collect();
+
+        for framework in &project.frameworks {
+            log!("Detected {} project", framework.name());
+        }
+
+        if let Some(runner) = &runner {
+            log!(
+                "The project uses {} as cargo runner, use /probe-rs-migrate to get a matching debug scenario",
+                runner.command
+            );
+        }
+
+        let mut state = State::load();
+        *state.section_mut(STATE_KEY) = project.to_values();
+        if let Err(err) = state.save() {
+            log!("{err}");
+        }
+
+        project
+    }
+
+    /// The project which was detected last, for hooks which are called without a worktree.
+    pub fn last_loaded() -> Self {
+        State::load()
+            .section(STATE_KEY)
+            .map(Self::from_values)
+            .unwrap_or_default()
+    }
+
+    /// Detect the project from the contents of its `Cargo.toml` and its cargo runner.
+    fn detect(manifest: &str, runner: Option<&Runner>) -> Self {
+        let frameworks: Vec<_> = [Framework::Embassy, Framework::Rtic, Framework::EspHal]
+            .into_iter()
+            .filter(|framework| {
+                framework
+                    .crates()
+                    .iter()
+                    .any(|name| dependency(manifest, name).is_some())
+            })
+            .collect();
+
+        // The chip of the runner was selected explicitly, so it is preferred.
+        let runner_chip = runner.and_then(|runner| runner.chip.clone());
+        let chip = runner_chip.or_else(|| {
+            let esp_hal = dependency(manifest, "esp-hal")?;
+
+            ESP_HAL_CHIPS
+                .iter()
+                .find(|chip| esp_hal.contains(&format!("\"{chip}\"")))
+                .map(|chip| chip.to_string())
+        });
+
+        let uses_rtt = frameworks.contains(&Framework::Embassy)
+            || ["defmt-rtt", "rtt-target"]
+                .iter()
+                .any(|name| dependency(manifest, name).is_some());
+
+        Self {
+            frameworks,
+            chip,
+            uses_rtt,
+            embedded_packages: Vec::new(),
+        }
+    }
+
+    fn to_values(&self) -> Map<String, Value> {
+        let frameworks: Vec<_> = self.frameworks.iter().map(|f| f.name()).collect();
+
+        let values = json!({
+            "frameworks": frameworks,
+            "chip": self.chip,
+            "usesRtt": self.uses_rtt,
+            "embeddedPackages": self.embedded_packages,
+        });
+
+        values.as_object().cloned().unwrap_or_default()
+    }
+
+    fn from_values(values: &Map<String, Value>) -> Self {
+        let frameworks = values.get("frameworks").and_then(Value::as_array);
+
+        Self {
+            frameworks: frameworks
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .filter_map(Framework::from_name)
+                .collect(),
+            chip: values
+                .get("chip")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            uses_rtt: values
+                .get("usesRtt")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            embedded_packages: values
+                .get("embeddedPackages")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    /// Adjust the core configuration of a generated scenario to the project.
+    pub fn apply_defaults(&self, core_config: &mut Map<String, Value>) {
+        if self.uses_rtt && !core_config.contains_key("rttEnabled") {
+            core_config.insert("rttEnabled".to_string(), true.into());
+        }
+    }
+}
+
+/// The name of the package in a manifest.
+pub fn package_name(manifest: &str) -> Option<&str> {
+    string_value(table(manifest, "package")?, "name")
+}
+
+/// The members of a workspace, as listed in the manifest.
+pub fn workspace_members(manifest: &str) -> Vec<&str> {
+    let Some(workspace) = table(manifest, "workspace") else {
+        return Vec::new();
+    };
+
+    let Some(start) = workspace.find("members") else {
+        return Vec::new();
+    };
+
+    let list = &workspace[start..];
+    let list = match (list.find('['), list.find(']')) {
+        (Some(open), Some(close)) if open < close => &list[open + 1..close],
+        _ => return Vec::new(),
+    };
+
+    list.lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| line.split(','))
+        .map(|member| member.trim().trim_matches(['"', '\'']))
+        .filter(|member| !member.is_empty())
+        .collect()
+}
+
+/// Whether a manifest depends on crates which are only used by firmware.
+fn is_embedded(manifest: &str) -> bool {
+    EMBEDDED_CRATES
+        .iter()
+        .any(|name| dependency(manifest, name).is_some())
+}
+
+/// The build target from the cargo configuration of the worktree.
+pub fn build_target_of(worktree: &Worktree) -> Option<String> {
+    CARGO_CONFIG_PATHS
+        .iter()
+        .find_map(|path| {
+            crate::files::read_optional(worktree, path, "the build target is not known")
+        })
+        .and_then(|cargo_config| build_target(&cargo_config).map(str::to_string))
+}
+
+/// The build target from a cargo configuration file.
+pub fn build_target(cargo_config: &str) -> Option<&str> {
+    string_value(table(cargo_config, "build")?, "target")
+}
+
+/// The contents of the `[name]` table, without the header.
+fn table<'a>(contents: &'a str, name: &str) -> Option<&'a str> {
+    let header = format!("[{name}]");
+    let mut offset = 0;
+
+    for line in contents.split_inclusive('\n') {
+        offset += line.len();
+
+        if line.trim() == header {
+            let table = &contents[offset..];
+            let end = table.find("\n[").unwrap_or(table.len());
+
+            return Some(&table[..end]);
+        }
+    }
+
+    None
+}
+
+/// A string value of a key in a table.
+fn string_value<'a>(table: &'a str, key: &str) -> Option<&'a str> {
+    table.lines().find_map(|line| {
+        let value = line.trim().strip_prefix(key)?.trim_start();
+        let value = value.strip_prefix('=')?.trim();
+
+        value.strip_prefix('"')?.split('"').next()
+    })
+}
+
+/// The declaration of a dependency in a manifest.
+///
+/// Both `name = ...` lines and `[dependencies.name]` tables are found. This is not a full TOML
+/// parser, but good enough to recognize the dependencies and their features.
+fn dependency<'a>(manifest: &'a str, name: &str) -> Option<&'a str> {
+    let mut offset = 0;
+
+    for line in manifest.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+
+        let trimmed = line.trim();
+
+        if let Some(rest) = trimmed.strip_prefix(name)
+            && rest.trim_start().starts_with('=')
+        {
+            return Some(trimmed);
+        }
+
+        let is_table = trimmed.starts_with('[')
+            && trimmed
+                .trim_matches(['[', ']'])
+                .rsplit('.')
+                .next()
+                .is_some_and(|table| table.trim() == name);
+
+        if is_table {
+            let table = &manifest[start..];
+            let end = table[line.len()..]
+                .find("\n[")
+                .map_or(table.len(), |end| end + line.len());
+
+            return Some(&table[..end]);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::{Map, json};
+
+    use super::{Framework, Project, Runner};
+
+    #[test]
+    fn detect_embassy() {
+        let project = Project::detect(
+            r#"
+[package]
+name = "blinky"
+
+[dependencies]
+embassy-executor = { version = "0.6", features = ["arch-cortex-m"] }
+embassy-nrf = { version = "0.2", features = ["nrf52840"] }
+"#,
+            None,
+        );
+
+        assert_eq!(project.frameworks, [Framework::Embassy]);
+        assert_eq!(project.chip, None);
+        assert!(project.uses_rtt);
+    }
+
+    #[test]
+    fn detect_esp_hal_chip() {
+        let project = Project::detect(
+            r#"
+[dependencies]
+esp-backtrace = { version = "0.14", features = ["esp32c3"] }
+
+[dependencies.esp-hal]
+version = "0.21"
+features = ["esp32c3"]
+"#,
+            None,
+        );
+
+        assert_eq!(project.frameworks, [Framework::EspHal]);
+        assert_eq!(project.chip.as_deref(), Some("esp32c3"));
+        assert!(!project.uses_rtt);
+    }
+
+    #[test]
+    fn detect_rtic_with_defmt() {
+        let project = Project::detect(
+            "[dependencies]\nrtic = \"2\"\ndefmt-rtt = \"0.4\"\nrtic-monotonics = \"2\"\n",
+            None,
+        );
+
+        assert_eq!(project.frameworks, [Framework::Rtic]);
+        assert!(project.uses_rtt);
+    }
+
+    #[test]
+    fn parse_runner() {
+        let runner = Runner::parse(
+            r#"
+[target.'cfg(all(target_arch = "arm", target_os = "none"))']
+runner = "probe-run --chip STM32F401RETx --speed=1000 --connect-under-reset"
+
+[build]
+target = "thumbv7em-none-eabihf"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            runner,
+            Runner {
+                command: "probe-run".to_string(),
+                chip: Some("STM32F401RETx".to_string()),
+                probe: None,
+                speed: Some(1000),
+                connect_under_reset: true,
+            }
+        );
+
+        let runner = Runner::parse("runner = 'probe-rs run --chip nRF52840_xxAA'").unwrap();
+        assert_eq!(runner.command, "probe-rs run");
+        assert_eq!(runner.chip.as_deref(), Some("nRF52840_xxAA"));
+
+        assert!(Runner::parse("runner = \"espflash flash --monitor\"").is_none());
+        assert!(Runner::parse("[build]\ntarget = \"riscv32imc-unknown-none-elf\"").is_none());
+    }
+
+    #[test]
+    fn runner_chip_is_preferred() {
+        let runner = Runner {
+            chip: Some("esp32c6".to_string()),
+            ..Default::default()
+        };
+
+        let project = Project::detect(
+            "[dependencies]\nesp-hal = { version = \"0.21\", features = [\"esp32c3\"] }\n",
+            Some(&runner),
+        );
+
+        assert_eq!(project.chip.as_deref(), Some("esp32c6"));
+    }
+
+    #[test]
+    fn package_and_target() {
+        assert_eq!(
+            super::package_name("[package]\nname = \"blinky\"\nversion = \"0.1.0\"\n"),
+            Some("blinky")
+        );
+        assert_eq!(
+            super::build_target("[build]\ntarget = \"thumbv6m-none-eabi\" # RP2040\n"),
+            Some("thumbv6m-none-eabi")
+        );
+        assert_eq!(super::package_name("[workspace]\nmembers = []\n"), None);
+    }
+
+    #[test]
+    fn workspace_members() {
+        let manifest = r#"
+[workspace]
+resolver = "2"
+members = [
+    "firmware",
+    "tools/*", # host tools
+    'xtask',
+]
+"#;
+
+        assert_eq!(
+            super::workspace_members(manifest),
+            ["firmware", "tools/*", "xtask"]
+        );
+        assert!(super::workspace_members("[package]\nname = \"app\"\n").is_empty());
+
+        assert!(super::is_embedded(
+            "[dependencies]\ncortex-m-rt = \"0.7\"\n"
+        ));
+        assert!(!super::is_embedded("[dependencies]\nclap = \"4\"\n"));
+    }
+
+    #[test]
+    fn values_roundtrip() {
+        let project = Project {
+            frameworks: vec![Framework::Embassy, Framework::EspHal],
+            chip: Some("esp32s3".to_string()),
+            uses_rtt: true,
+            embedded_packages: vec!["firmware".to_string()],
+        };
+
+        assert_eq!(Project::from_values(&project.to_values()), project);
+    }
+
+    #[test]
+    fn apply_defaults() {
+        let project = Project {
+            uses_rtt: true,
+            ..Default::default()
+        };
+
+        let mut core_config = Map::new();
+        project.apply_defaults(&mut core_config);
+        assert_eq!(core_config["rttEnabled"], true);
+
+        let mut core_config = json!({ "rttEnabled": false }).as_object().cloned().unwrap();
+        project.apply_defaults(&mut core_config);
+        assert_eq!(core_config["rttEnabled"], false);
+    }
+}
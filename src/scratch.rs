@@ -0,0 +1,105 @@
+//! Temporary directories for the files a session needs while it runs.
+//!
+//! Target descriptions with flash algorithms and command files for vendor tools are only
+//! needed by one session. Each session gets its own directory in `sessions/` in the working
+//! directory of the extension, which is tracked in the extension state. The extension isn't
+//! told when a session ends, so a directory is removed when the next session of the same
+//! scenario starts, or after a day. Directories which aren't tracked, for example because the
+//! state was lost, are removed as well, so nothing accumulates.
+
+use std::{
+    fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use zed_extension_api::serde_json::{Map, Value, json};
+
+use crate::state::State;
+
+/// Directory in the working directory of the extension containing the session directories.
+pub const DIRECTORY: &str = "sessions";
+
+const STATE_KEY: &str = "sessionDirectories";
+
+/// How long a directory is kept if no later session of its scenario starts, in seconds.
+const MAX_AGE: u64 = 24 * 60 * 60;
+
+/// The tracked directories which can be removed when a session of `label` starts.
+fn expired(directories: &Map<String, Value>, label: &str, now: u64) -> Vec<String> {
+    directories
+        .iter()
+        .filter(|(_, directory)| {
+            let created_at = directory.get("createdAt").and_then(Value::as_u64);
+
+            directory.get("label").and_then(Value::as_str) == Some(label)
+                || created_at.is_none_or(|created_at| created_at + MAX_AGE < now)
+        })
+        .map(|(path, _)| path.clone())
+        .collect()
+}
+
+/// Create the directory for a session of the scenario with the given label, and remove the
+/// ones which are no longer needed.
+///
+/// Returns the path of the directory, relative to the working directory of the extension.
+pub fn create(label: &str) -> Result<String, String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+
+    let mut state = State::load();
+    let directories = state.section_mut(STATE_KEY);
+
+    for path in expired(directories, label, now) {
+        fs::remove_dir_all(&path).ok();
+        directories.remove(&path);
+    }
+
+    // Left over from sessions the state doesn't know about.
+    if let Ok(entries) = fs::read_dir(DIRECTORY) {
+        for entry in entries.filter_map(Result::ok) {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let path = format!("{DIRECTORY}/{name}");
+
+            if !directories.contains_key(&path) {
+                fs::remove_dir_all(&path).ok();
+            }
+        }
+    }
+
+    let path = format!("{DIRECTORY}/{}-{now}", crate::config::file_name(label));
+    fs::create_dir_all(&path)
+        .map_err(|err| format!("Failed to create the session directory '{path}': {err}"))?;
+
+    directories.insert(path.clone(), json!({ "label": label, "createdAt": now }));
+    if let Err(err) = state.save() {
+        log!("{err}");
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    #[test]
+    fn expired() {
+        let directories = json!({
+            "sessions/flash-nrf52-100": { "label": "Flash nRF52", "createdAt": 100 },
+            "sessions/flash-stm32-200": { "label": "Flash STM32", "createdAt": 200 },
+            "sessions/attach-300": { "label": "Attach", "createdAt": 300 },
+        });
+        let directories = directories.as_object().unwrap();
+
+        // The previous session of the scenario, and directories older than a day.
+        let now = 24 * 60 * 60 + 250;
+        assert_eq!(
+            super::expired(directories, "Flash STM32", now),
+            ["sessions/flash-nrf52-100", "sessions/flash-stm32-200"]
+        );
+        assert!(super::expired(directories, "Debug", 400).is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+//! Command line for the probe-rs debug adapter server.
+
+/// Port of the dap-server, if the extension starts it.
+pub const DEFAULT_PORT: u16 = 50_000;
+
+/// Number of ports from [`DEFAULT_PORT`] on which the extension starts dap-servers, if the
+/// default port is still bound, see [`crate::ports`].
+pub const PORT_COUNT: u16 = 10;
+
+/// Whether a port is one the extension starts dap-servers on.
+pub fn is_extension_port(port: u16) -> bool {
+    (DEFAULT_PORT..DEFAULT_PORT + PORT_COUNT).contains(&port)
+}
+
+/// Name of the binary of probe-rs releases before 0.14, which had the debugger in a separate
+/// binary, started with `probe-rs-debugger debug --dap`.
+pub const LEGACY_BINARY_NAME: &str = "probe-rs-debugger";
+
+/// The command line interface of the debug adapter.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Cli {
+    /// `probe-rs dap-server`.
+    #[default]
+    DapServer,
+    /// `probe-rs-debugger debug --dap`, which has no other options.
+    Legacy,
+}
+
+impl Cli {
+    /// The interface of the binary at the given path, by its file name.
+    pub fn from_binary(binary: &str) -> Self {
+        let name = binary.rsplit(['/', '\\']).next().unwrap_or(binary);
+        let name = name.strip_suffix(".exe").unwrap_or(name);
+
+        if name == LEGACY_BINARY_NAME {
+            Cli::Legacy
+        } else {
+            Cli::DapServer
+        }
+    }
+
+    /// The interface of a binary by its `--help` output, which lists the subcommands.
+    ///
+    /// Only a binary which has a `debug` subcommand but no `dap-server` is considered legacy, so
+    /// anything unexpected is started as usual.
+    pub fn from_help(help: &str) -> Self {
+        let subcommands: Vec<&str> = help
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .collect();
+
+        if !subcommands.contains(&"dap-server") && subcommands.contains(&"debug") {
+            Cli::Legacy
+        } else {
+            Cli::DapServer
+        }
+    }
+}
+
+/// Arguments for `probe-rs dap-server`.
+///
+/// Options are collected first and turned into arguments at the end, so that they can be
+/// added in any order without depending on each other.
+#[derive(Debug, Default)]
+pub struct DapServer {
+    cli: Cli,
+    port: u16,
+    single_session: bool,
+    log_file: Option<String>,
+    extra_args: Vec<String>,
+}
+
+impl DapServer {
+    pub fn new(port: u16) -> Self {
+        Self {
+            port,
+            ..Default::default()
+        }
+    }
+
+    pub fn cli(mut self, cli: Cli) -> Self {
+        self.cli = cli;
+        self
+    }
+
+    /// Exit the server after the first debug session.
+    pub fn single_session(mut self, single_session: bool) -> Self {
+        self.single_session = single_session;
+        self
+    }
+
+    /// Write the log of the server to the given file.
+    pub fn log_file(mut self, log_file: Option<String>) -> Self {
+        self.log_file = log_file;
+        self
+    }
+
+    /// Additional arguments, passed after all other arguments.
+    pub fn extra_args(mut self, args: impl IntoIterator<Item = String>) -> Self {
+        self.extra_args.extend(args);
+        self
+    }
+
+    /// The arguments, without the options the legacy CLI doesn't have.
+    pub fn arguments(&self) -> Vec<String> {
+        if self.cli == Cli::Legacy {
+            let mut arguments = ["debug", "--dap", "--port"].map(str::to_string).to_vec();
+            arguments.push(self.port.to_string());
+            arguments.extend(self.extra_args.iter().cloned());
+
+            return arguments;
+        }
+
+        let mut arguments = vec![
+            "dap-server".to_string(),
+            "--port".to_string(),
+            self.port.to_string(),
+        ];
+
+        if self.single_session {
+            arguments.push("--single-session".to_string());
+        }
+
+        if let Some(log_file) = &self.log_file {
+            arguments.extend(["--log-file".to_string(), log_file.clone()]);
+        }
+
+        arguments.extend(self.extra_args.iter().cloned());
+
+        arguments
+    }
+}
+
+/// The command and arguments which start the debug adapter through the `wrapper`, like
+/// `["sudo"]` for USB access which needs elevation, or `["flatpak-spawn", "--host"]` to leave
+/// the sandbox of Zed.
+pub fn wrap(wrapper: &[String], command: String, arguments: Vec<String>) -> (String, Vec<String>) {
+    let Some((program, wrapper_args)) = wrapper.split_first() else {
+        return (command, arguments);
+    };
+
+    let mut wrapped = wrapper_args.to_vec();
+    wrapped.push(command);
+    wrapped.extend(arguments);
+
+    (program.clone(), wrapped)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cli, DapServer};
+
+    #[test]
+    fn default_arguments() {
+        assert_eq!(
+            DapServer::new(50_000).arguments(),
+            ["dap-server", "--port", "50000"]
+        );
+    }
+
+    #[test]
+    fn all_arguments() {
+        let server = DapServer::new(50_001)
+            .extra_args(["--extra".to_string()])
+            .log_file(Some("/work/.zed/probe-rs.log".to_string()))
+            .single_session(true);
+
+        assert_eq!(
+            server.arguments(),
+            [
+                "dap-server",
+                "--port",
+                "50001",
+                "--single-session",
+                "--log-file",
+                "/work/.zed/probe-rs.log",
+                "--extra"
+            ]
+        );
+    }
+
+    #[test]
+    fn legacy_arguments() {
+        let server = DapServer::new(50_000)
+            .cli(Cli::from_binary("C:\\Tools\\probe-rs-debugger.exe"))
+            .log_file(Some("/work/.zed/probe-rs.log".to_string()))
+            .single_session(true);
+
+        assert_eq!(server.arguments(), ["debug", "--dap", "--port", "50000"]);
+        assert_eq!(Cli::from_binary("/usr/bin/probe-rs"), Cli::DapServer);
+    }
+
+    #[test]
+    fn wrap() {
+        let wrapper = ["distrobox", "enter", "dev", "--"].map(str::to_string);
+        let arguments = vec!["dap-server".to_string(), "--port".to_string()];
+
+        assert_eq!(
+            super::wrap(&wrapper, "probe-rs".to_string(), arguments.clone()),
+            (
+                "distrobox".to_string(),
+                ["enter", "dev", "--", "probe-rs", "dap-server", "--port"]
+                    .map(str::to_string)
+                    .to_vec()
+            )
+        );
+        assert_eq!(
+            super::wrap(&[], "probe-rs".to_string(), arguments.clone()),
+            ("probe-rs".to_string(), arguments)
+        );
+    }
+
+    #[test]
+    fn from_help() {
+        let legacy = "probe-rs-debugger 0.13.0\n\n\
+                      USAGE:\n    probe-rs-debugger <SUBCOMMAND>\n\n\
+                      SUBCOMMANDS:\n    \
+                      debug    Open target in debug mode and accept debug commands\n    \
+                      help     Prints this message\n    \
+                      list     List all connected debug probes\n";
+        assert_eq!(Cli::from_help(legacy), Cli::Legacy);
+
+        let current = "Usage: probe-rs <COMMAND>\n\nCommands:\n  \
+                       dap-server  Debug Adapter Protocol (DAP) server\n  \
+                       debug       Debug a target\n  \
+                       list        List all connected debug probes\n";
+        assert_eq!(Cli::from_help(current), Cli::DapServer);
+        assert_eq!(Cli::from_help(""), Cli::DapServer);
+    }
+}
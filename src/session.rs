@@ -0,0 +1,43 @@
+//! The scenario for which a session is being started.
+//!
+//! Several sessions may be started one after the other, and their lines in the Zed log would
+//! be hard to tell apart. While a session is started, log lines are tagged with the label of
+//! its scenario, and the dap-server started for it gets the label in its environment, so it
+//! can be recognized in the process list.
+
+use std::sync::Mutex;
+
+/// Environment variable of the dap-server, containing the label of the scenario.
+pub const ENV_VAR: &str = "PROBE_RS_ZED_SCENARIO";
+
+static LABEL: Mutex<Option<String>> = Mutex::new(None);
+
+/// Tag the following log lines with the label of a scenario.
+pub fn begin(label: &str) {
+    *LABEL.lock().unwrap_or_else(|err| err.into_inner()) = Some(label.to_string());
+}
+
+/// Stop tagging log lines.
+pub fn end() {
+    *LABEL.lock().unwrap_or_else(|err| err.into_inner()) = None;
+}
+
+/// The prefix for log lines, which is empty if no session is being started.
+pub fn prefix() -> String {
+    let label = LABEL.lock().unwrap_or_else(|err| err.into_inner());
+
+    format_prefix(label.as_deref())
+}
+
+fn format_prefix(label: Option<&str>) -> String {
+    label.map_or_else(String::new, |label| format!("[{label}] "))
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn format_prefix() {
+        assert_eq!(super::format_prefix(Some("Debug nRF52")), "[Debug nRF52] ");
+        assert_eq!(super::format_prefix(None), "");
+    }
+}
@@ -0,0 +1,233 @@
+//! Extension settings, which apply to all probe-rs scenarios of a worktree.
+//!
+//! The settings are read from `.zed/probe-rs.json` in the worktree. Some extension hooks, like
+//! the locator, are called without a worktree. These use the settings which were loaded last,
+//! which are kept in the extension state for that purpose.
+
+use zed_extension_api::{
+    Worktree,
+    serde_json::{Map, Value},
+};
+
+use crate::{install::Channel, state::State, targets::ResetConnection};
+
+pub const SETTINGS_PATH: &str = ".zed/probe-rs.json";
+
+const STATE_KEY: &str = "settings";
+
+/// State key for the git branch of the worktree, which is not a setting but used like one.
+const GIT_BRANCH_KEY: &str = "gitBranch";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Settings {
+    /// probe-rs binary for the project, see [`crate::config::adapter_path`].
+    pub adapter_path: Option<String>,
+    /// Name of the probe-rs binary looked up on the `PATH`, see [`crate::server::Cli`].
+    pub adapter_name: Option<String>,
+    /// Version of probe-rs to use, see [`crate::install`].
+    pub adapter_version: Option<String>,
+    /// Command the debug adapter is started with, see [`crate::server::wrap`].
+    pub adapter_wrapper: Vec<String>,
+    /// Install releases without a checksum, see [`crate::install`].
+    pub allow_unverified_downloads: bool,
+    /// Which probe-rs releases are downloaded, see [`crate::install`].
+    pub channel: Channel,
+    /// Build id which can be used in the label template.
+    pub build_id: Option<String>,
+    /// Build probe-rs with cargo if there is no release binary for the host.
+    pub cargo_install: bool,
+    /// Chip for scenarios which don't configure one.
+    pub chip: Option<String>,
+    /// Chip name prefixes which must or must not be connected to under reset.
+    pub connect_under_reset: Vec<(String, ResetConnection)>,
+    /// Value for the `DEFMT_LOG` environment variable of generated build tasks.
+    pub defmt_log: Option<String>,
+    /// URL template for downloading probe-rs from a mirror, see [`crate::install`].
+    pub download_mirror: Option<String>,
+    /// Command run after the extension flashed the programs, see [`crate::flashing`].
+    pub flashed_command: Vec<String>,
+    /// Whether generated scenarios halt the target after flashing, regardless of stop on entry.
+    pub halt_after_reset: Option<bool>,
+    /// Name of the board from the lab mapping file to use, see [`crate::lab`].
+    pub lab_board: Option<String>,
+    /// Template for the labels of generated scenarios, see [`crate::label`].
+    pub label_template: Option<String>,
+    /// Maximum number of scenarios the locator offers for packages without firmware crates.
+    pub locator_limit: Option<usize>,
+    /// Never access the network, only use probe-rs and SVD files found locally.
+    pub offline: bool,
+    /// Probe selector in the `VID:PID[:SERIAL]` format, for attach scenarios created in Zed.
+    pub probe: Option<String>,
+    /// Additional directory which is searched for SVD files.
+    pub svd_directory: Option<String>,
+    /// URL template for downloading SVD files, with a `{chip}` placeholder.
+    pub svd_url: Option<String>,
+    /// Start the dap-server in single-session mode, so it exits after the session ends.
+    pub single_session: bool,
+    /// Print the timings of the phases of starting a session, see [`crate::timing`].
+    pub timings: bool,
+    /// Count sessions, flashes and errors locally, see [`crate::statistics`].
+    pub usage_statistics: bool,
+    /// Check for newer probe-rs releases when a session starts, see [`crate::update`].
+    pub update_check: bool,
+    /// Let probe-rs write a detailed log to `.zed/probe-rs.log`.
+    pub verbose: bool,
+    /// Current git branch of the worktree, detected when the settings are loaded.
+    pub git_branch: Option<String>,
+}
+
+impl Settings {
+    /// Load the settings of the worktree, and remember them for hooks without a worktree.
+    pub fn load(worktree: &Worktree) -> Self {
+        let contents =
+            crate::files::read_optional(worktree, SETTINGS_PATH, "the default settings are used");
+
+        let values = match contents.map(|contents| crate::jsonc::parse(&contents)) {
+            Some(Ok(Value::Object(values))) => values,
+            Some(Ok(_)) => {
+                log!("Ignoring '{SETTINGS_PATH}', expected a JSON object");
+                Map::new()
+            }
+            Some(Err(err)) => {
+                log!("Ignoring '{SETTINGS_PATH}', failed to parse it: {err}");
+                Map::new()
+            }
+            // The settings file is optional.
+            None => Map::new(),
+        };
+
+        let git_branch = crate::files::read_optional(
+            worktree,
+            ".git/HEAD",
+            "{branch} can't be used in the label template",
+        )
+        .and_then(|head| crate::label::parse_git_head(&head));
+
+        let mut state = State::load();
+        *state.section_mut(STATE_KEY) = values.clone();
+        state.values_mut().insert(
+            GIT_BRANCH_KEY.to_string(),
+            git_branch.clone().map_or(Value::Null, Value::String),
+        );
+        if let Err(err) = state.save() {
+            log!("{err}");
+        }
+
+        Self {
+            git_branch,
+            ..Self::from_values(&values)
+        }
+    }
+
+    /// The settings which were loaded last, for hooks which are called without a worktree.
+    pub fn last_loaded() -> Self {
+        let state = State::load();
+
+        Self {
+            git_branch: state
+                .value(GIT_BRANCH_KEY)
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            ..state
+                .section(STATE_KEY)
+                .map(Self::from_values)
+                .unwrap_or_default()
+        }
+    }
+
+    fn from_values(values: &Map<String, Value>) -> Self {
+        let string = |key: &str| values.get(key).and_then(Value::as_str).map(str::to_string);
+        let bool = |key: &str| values.get(key).and_then(Value::as_bool);
+        let strings = |key: &str| {
+            values
+                .get(key)
+                .and_then(Value::as_array)
+                .map(|strings| {
+                    strings
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        Self {
+            adapter_path: string("adapterPath"),
+            adapter_name: string("adapterName"),
+            adapter_version: string("adapterVersion"),
+            adapter_wrapper: strings("adapterWrapper"),
+            allow_unverified_downloads: bool("allowUnverifiedDownloads").unwrap_or(false),
+            build_id: string("buildId"),
+            cargo_install: bool("cargoInstall").unwrap_or(false),
+            channel: string("channel")
+                .and_then(|channel| Channel::parse(&channel))
+                .unwrap_or_default(),
+            chip: string("chip"),
+            connect_under_reset: values
+                .get("connectUnderReset")
+                .and_then(Value::as_object)
+                .map(|flags| {
+                    flags
+                        .iter()
+                        .filter_map(|(chip, flag)| {
+                            Some((chip.clone(), ResetConnection::parse(flag.as_str()?)?))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            defmt_log: string("defmtLog"),
+            download_mirror: string("downloadMirror"),
+            flashed_command: strings("flashedCommand"),
+            halt_after_reset: bool("haltAfterReset"),
+            lab_board: string("labBoard"),
+            label_template: string("labelTemplate"),
+            locator_limit: values
+                .get("locatorLimit")
+                .and_then(Value::as_u64)
+                .map(|limit| limit as usize),
+            offline: bool("offline").unwrap_or(false),
+            probe: string("probe"),
+            svd_directory: string("svdDirectory"),
+            svd_url: string("svdUrl"),
+            single_session: bool("singleSession").unwrap_or(false),
+            timings: bool("timings").unwrap_or(false),
+            update_check: bool("updateCheck").unwrap_or(true),
+            usage_statistics: bool("usageStatistics").unwrap_or(false),
+            verbose: bool("verbose").unwrap_or(false),
+            git_branch: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    use super::Settings;
+
+    #[test]
+    fn from_values() {
+        let values = json!({
+            "channel": "prerelease",
+            "chip": "nRF52840_xxAA",
+            "defmtLog": "debug",
+            "haltAfterReset": false,
+            "singleSession": true,
+            "unknown": true
+        });
+
+        assert_eq!(
+            Settings::from_values(values.as_object().unwrap()),
+            Settings {
+                channel: crate::install::Channel::Prerelease,
+                chip: Some("nRF52840_xxAA".to_string()),
+                defmt_log: Some("debug".to_string()),
+                halt_after_reset: Some(false),
+                single_session: true,
+                update_check: true,
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! Retrying the connection at a lower speed, for `speedFallback`.
+//!
+//! Long or unshielded cables, breadboards and level shifters often can't handle the speed the
+//! probe uses, and the resulting errors don't point at the wiring. With `speedFallback`, the
+//! extension connects to the target once before the session starts. If that fails, it tries
+//! again at a conservative speed, and if that works, the session uses the lower speed and the
+//! log tells the user that the speed is the problem.
+
+use zed_extension_api::serde_json::Value;
+
+use crate::{errors::Category, targets::Architecture};
+
+/// Speed in kHz used with `"speedFallback": true`, which practically all wiring can handle.
+pub const DEFAULT_FALLBACK_KHZ: u64 = 100;
+
+/// Read the `speedFallback` option, which is `true` or a speed in kHz.
+///
+/// Returns `None` if there's nothing to fall back to, because the configured speed is already
+/// as low as the fallback speed.
+pub fn fallback_speed(config: &Value) -> Result<Option<u64>, String> {
+    let fallback = match config.get("speedFallback") {
+        None | Some(Value::Null) | Some(Value::Bool(false)) => return Ok(None),
+        Some(Value::Bool(true)) => DEFAULT_FALLBACK_KHZ,
+        Some(speed) => speed
+            .as_u64()
+            .filter(|speed| *speed > 0)
+            .ok_or_else(|| "'speedFallback' must be true or a speed in kHz".to_string())?,
+    };
+
+    let speed = config.get("speed").and_then(Value::as_u64);
+
+    Ok(speed
+        .is_none_or(|speed| speed > fallback)
+        .then_some(fallback))
+}
+
+/// The address of a register which can be read to check the connection to a chip.
+///
+/// All Cortex-M cores have the CPUID register at the same address. For other architectures
+/// there is no such register, so the check is skipped.
+fn check_address(chip: &str) -> Option<u64> {
+    match crate::targets::architecture(chip)? {
+        Architecture::Arm => Some(0xE000_ED00),
+        Architecture::RiscV | Architecture::Xtensa => None,
+    }
+}
+
+/// Connect to the target, and lower the `speed` of the configuration if the connection only
+/// works at the fallback speed.
+///
+/// Failures are only reported, the session then fails with the error of probe-rs itself.
+pub fn check(probe_rs: &str, config: &mut Value) {
+    let fallback = match fallback_speed(config) {
+        Ok(Some(fallback)) => fallback,
+        Ok(None) => return,
+        Err(err) => {
+            log!("{err}");
+            return;
+        }
+    };
+
+    let Some(chip) = config.get("chip").and_then(Value::as_str) else {
+        return;
+    };
+
+    let Some(address) = check_address(chip) else {
+        log!("Can't check the connection speed for '{chip}', 'speedFallback' is ignored");
+        return;
+    };
+
+    let connect = |config: &Value| {
+        let mut args = vec!["read".to_string()];
+        args.extend(crate::config::session_args(config)?);
+        args.extend(["b32".to_string(), format!("{address:#x}"), "1".to_string()]);
+
+        crate::process::run(probe_rs, &args)
+    };
+
+    let err = match connect(config) {
+        Ok(_) => return,
+        Err(err) => err,
+    };
+
+    // A lower speed doesn't help if the probe itself can't be opened.
+    if Category::of_probe_rs_error(&err) == Category::Probe {
+        return;
+    }
+
+    let mut slow_config = config.clone();
+    slow_config["speed"] = fallback.into();
+
+    let speed = config.get("speed").and_then(Value::as_u64);
+
+    match connect(&slow_config) {
+        Ok(_) => {
+            log!("{}", notice(speed, fallback));
+            *config = slow_config;
+        }
+        Err(slow_err) => {
+            log!("Connecting to the target failed at {fallback} kHz as well: {slow_err}");
+        }
+    }
+}
+
+/// The message when the connection only worked at the fallback speed.
+fn notice(speed: Option<u64>, fallback: u64) -> String {
+    let speed = speed.map_or_else(
+        || "the default speed of the probe".to_string(),
+        |speed| format!("{speed} kHz"),
+    );
+
+    format!(
+        "Connecting to the target failed at {speed}, but worked at {fallback} kHz. The wiring \
+         between probe and target likely can't handle the higher speed, for example because of \
+         long or unshielded cables. This session uses {fallback} kHz, set \"speed\" to a lower \
+         value or improve the wiring to avoid the retry."
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    #[test]
+    fn fallback_speed() {
+        assert_eq!(super::fallback_speed(&json!({})), Ok(None));
+        assert_eq!(
+            super::fallback_speed(&json!({ "speedFallback": true })),
+            Ok(Some(super::DEFAULT_FALLBACK_KHZ))
+        );
+        assert_eq!(
+            super::fallback_speed(&json!({ "speedFallback": 500, "speed": 4000 })),
+            Ok(Some(500))
+        );
+        // Nothing to fall back to.
+        assert_eq!(
+            super::fallback_speed(&json!({ "speedFallback": true, "speed": 100 })),
+            Ok(None)
+        );
+        assert!(super::fallback_speed(&json!({ "speedFallback": "slow" })).is_err());
+        assert!(super::fallback_speed(&json!({ "speedFallback": 0 })).is_err());
+    }
+
+    #[test]
+    fn check_address() {
+        assert_eq!(super::check_address("STM32F401RETx"), Some(0xE000_ED00));
+        assert_eq!(super::check_address("esp32c3"), None);
+        assert_eq!(super::check_address("unknown"), None);
+    }
+
+    #[test]
+    fn notice() {
+        assert!(
+            super::notice(Some(4000), 100)
+                .starts_with("Connecting to the target failed at 4000 kHz, but worked at 100 kHz.")
+        );
+        assert!(super::notice(None, 100).contains("at the default speed of the probe,"));
+    }
+}
@@ -0,0 +1,264 @@
+//! Detection of program binaries which don't match the firmware on the target.
+//!
+//! When a session flashes the target, a fingerprint of each program binary is recorded in the
+//! extension state. When attaching later, the fingerprints are compared to warn about binaries
+//! which were rebuilt since, because the debugger would then show wrong symbols and variables.
+//! With `flashOnlyChanged`, they are compared before flashing, to skip flashing programs
+//! which are already on the target.
+
+use std::fs;
+
+use zed_extension_api::{
+    self as zed, Os,
+    serde_json::{Map, Value},
+};
+
+use crate::state::State;
+
+const STATE_KEY: &str = "flashedPrograms";
+
+/// Paths of all program binaries in the configuration, resolved against `cwd`.
+pub fn program_binaries(config: &Value, cwd: &str) -> Vec<String> {
+    config
+        .get("coreConfigs")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|core| core.get("programBinary").and_then(Value::as_str))
+        .map(|program| crate::paths::join(Some(cwd), program))
+        .collect()
+}
+
+/// Compute a fingerprint of the file contents, its SHA-256 digest in lowercase hex.
+///
+/// The extension sandbox can only read files in the working directory of the extension, and
+/// programs in the worktree usually aren't readable, so those are hashed with the SHA-256 tool
+/// of the host instead. Both give the same digest, so fingerprints can always be compared.
+fn fingerprint(path: &str) -> Result<String, String> {
+    match fs::read(path) {
+        Ok(data) => Ok(crate::sha256::hex_digest(&data)),
+        Err(_) => host_fingerprint(path),
+    }
+}
+
+/// Compute the SHA-256 digest of the file contents, using the tool of the host.
+fn host_fingerprint(path: &str) -> Result<String, String> {
+    let (os, _) = zed::current_platform();
+
+    let (tool, args) = match os {
+        Os::Windows => ("certutil", vec!["-hashfile", path, "SHA256"]),
+        Os::Mac => ("shasum", vec!["-a", "256", path]),
+        Os::Linux => ("sha256sum", vec![path]),
+    };
+    let args: Vec<String> = args.into_iter().map(str::to_string).collect();
+
+    let output = crate::process::run(tool, &args)?;
+
+    parse_digest(&output).ok_or_else(|| format!("'{tool}' printed no SHA-256 digest"))
+}
+
+/// The SHA-256 digest in the output of the tool, which also prints the path, and in the case
+/// of older versions of certutil, the digest with spaces between the bytes.
+fn parse_digest(output: &str) -> Option<String> {
+    let is_digest = |text: &str| text.len() == 64 && text.chars().all(|c| c.is_ascii_hexdigit());
+
+    output.lines().find_map(|line| {
+        let first = line.split_whitespace().next()?;
+        let joined: String = line.split_whitespace().collect();
+
+        [first, joined.as_str()]
+            .into_iter()
+            .find(|text| is_digest(text))
+            .map(str::to_ascii_lowercase)
+    })
+}
+
+/// Record the fingerprints of the programs which are about to be flashed, by the extension or
+/// by probe-rs when the session starts.
+pub fn record_flashed(programs: &[String]) {
+    let mut state = State::load();
+    record(state.section_mut(STATE_KEY), programs, fingerprint);
+
+    if let Err(err) = state.save() {
+        log!("{err}");
+    }
+}
+
+fn record(
+    flashed: &mut Map<String, Value>,
+    programs: &[String],
+    fingerprint: impl Fn(&str) -> Result<String, String>,
+) {
+    for program in programs {
+        match fingerprint(program) {
+            Ok(fingerprint) => {
+                flashed.insert(program.clone(), fingerprint.into());
+            }
+            Err(err) => {
+                log!("Unable to fingerprint '{program}': {err}");
+                flashed.remove(program);
+            }
+        }
+    }
+}
+
+/// Compare the programs against the recorded fingerprints, returning a warning for each
+/// program which was changed since it was last flashed.
+pub fn check_attach(programs: &[String]) -> Vec<String> {
+    let state = State::load();
+
+    warnings(state.section(STATE_KEY), programs, fingerprint)
+}
+
+fn warnings(
+    flashed: Option<&Map<String, Value>>,
+    programs: &[String],
+    fingerprint: impl Fn(&str) -> Result<String, String>,
+) -> Vec<String> {
+    programs
+        .iter()
+        .filter_map(|program| {
+            let recorded = flashed
+                .and_then(|flashed| flashed.get(program))
+                .and_then(Value::as_str);
+
+            staleness_warning(program, recorded, fingerprint(program).ok().as_deref())
+        })
+        .collect()
+}
+
+/// Whether all programs match the fingerprints recorded when they were last flashed.
+pub fn unchanged(programs: &[String]) -> bool {
+    let state = State::load();
+
+    is_unchanged(state.section(STATE_KEY), programs, fingerprint)
+}
+
+/// A program which can't be fingerprinted counts as changed, so it's flashed.
+fn is_unchanged(
+    flashed: Option<&Map<String, Value>>,
+    programs: &[String],
+    fingerprint: impl Fn(&str) -> Result<String, String>,
+) -> bool {
+    !programs.is_empty()
+        && programs.iter().all(|program| {
+            let recorded = flashed
+                .and_then(|flashed| flashed.get(program))
+                .and_then(Value::as_str);
+
+            recorded.is_some() && fingerprint(program).ok().as_deref() == recorded
+        })
+}
+
+fn staleness_warning(
+    program: &str,
+    recorded: Option<&str>,
+    current: Option<&str>,
+) -> Option<String> {
+    match (recorded, current) {
+        (Some(recorded), Some(current)) if recorded != current => Some(format!(
+            "Warning: '{program}' changed since it was last flashed, the symbols likely don't match the firmware on the target"
+        )),
+        (None, _) => Some(format!(
+            "Warning: '{program}' was not flashed by this extension, make sure it matches the firmware on the target"
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::{Map, json};
+
+    #[test]
+    fn program_binaries() {
+        let config = json!({
+            "coreConfigs": [
+                { "programBinary": "target/thumbv7em-none-eabihf/debug/app" },
+                { "coreIndex": 1 },
+                { "programBinary": "/abs/net" },
+            ]
+        });
+
+        assert_eq!(
+            super::program_binaries(&config, "/project"),
+            [
+                "/project/target/thumbv7em-none-eabihf/debug/app",
+                "/abs/net"
+            ]
+        );
+    }
+
+    const DIGEST: &str = "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae";
+
+    #[test]
+    fn parse_digest() {
+        assert_eq!(
+            super::parse_digest(&format!("{DIGEST}  /project/app\n")).as_deref(),
+            Some(DIGEST)
+        );
+
+        let certutil = format!(
+            "SHA256 hash of C:\\project\\app:\r\n{}\r\n\
+             CertUtil: -hashfile command completed successfully.\r\n",
+            DIGEST.to_uppercase()
+        );
+        assert_eq!(super::parse_digest(&certutil).as_deref(), Some(DIGEST));
+
+        let spaced = DIGEST
+            .as_bytes()
+            .chunks(2)
+            .map(|byte| std::str::from_utf8(byte).unwrap())
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(super::parse_digest(&spaced).as_deref(), Some(DIGEST));
+
+        assert_eq!(super::parse_digest("sha256sum: app: No such file"), None);
+    }
+
+    #[test]
+    fn flashed_by_probe_rs_then_attach() {
+        let programs = ["/project/app".to_string()];
+        let fingerprint = |_: &str| Ok(DIGEST.to_string());
+
+        // Without additional images to flash first, probe-rs flashes the programs.
+        let outcome = crate::flashing::Outcome::Flashed;
+        let mut flashed = Map::new();
+        if outcome.programs_flashed() {
+            super::record(&mut flashed, &programs, fingerprint);
+        }
+
+        assert!(super::warnings(Some(&flashed), &programs, fingerprint).is_empty());
+        assert_eq!(
+            super::warnings(Some(&flashed), &programs, |_| Ok("0".repeat(64))).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn is_unchanged() {
+        let programs = ["/project/app".to_string()];
+        let fingerprint = |_: &str| Ok(DIGEST.to_string());
+        let mut flashed = Map::new();
+
+        assert!(!super::is_unchanged(None, &programs, fingerprint));
+
+        super::record(&mut flashed, &programs, fingerprint);
+        assert!(super::is_unchanged(Some(&flashed), &programs, fingerprint));
+        assert!(!super::is_unchanged(Some(&flashed), &[], fingerprint));
+        assert!(!super::is_unchanged(Some(&flashed), &programs, |_| Ok(
+            "0".repeat(64)
+        )));
+        assert!(!super::is_unchanged(Some(&flashed), &programs, |_| Err(
+            "no such file".to_string()
+        )));
+    }
+
+    #[test]
+    fn staleness_warning() {
+        assert!(super::staleness_warning("app", Some("1 2"), Some("1 2")).is_none());
+        assert!(super::staleness_warning("app", Some("1 2"), None).is_none());
+        assert!(super::staleness_warning("app", Some("1 2"), Some("3 4")).is_some());
+        assert!(super::staleness_warning("app", None, Some("3 4")).is_some());
+    }
+}
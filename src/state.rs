@@ -0,0 +1,102 @@
+//! State which is persisted across sessions in the working directory of the extension.
+
+use std::fs;
+
+use zed_extension_api::serde_json::{self, Map, Value};
+
+pub const STATE_FILE: &str = "state.json";
+
+/// Persistent extension state, stored as a JSON object.
+///
+/// Each feature uses its own top-level key, so unknown keys written by other versions of the
+/// extension are preserved.
+#[derive(Debug, Default)]
+pub struct State {
+    values: Map<String, Value>,
+}
+
+impl State {
+    /// Load the state, starting with an empty state if none was stored yet or it can't be read.
+    pub fn load() -> Self {
+        fs::read_to_string(STATE_FILE)
+            .ok()
+            .map(|contents| Self::parse(&contents))
+            .unwrap_or_default()
+    }
+
+    fn parse(contents: &str) -> Self {
+        match serde_json::from_str(contents) {
+            Ok(Value::Object(values)) => Self { values },
+            _ => {
+                log!("Ignoring invalid extension state in '{STATE_FILE}'");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(&self.values)
+            .map_err(|err| format!("Failed to serialize extension state: {err}"))?;
+
+        fs::write(STATE_FILE, contents)
+            .map_err(|err| format!("Failed to write extension state to '{STATE_FILE}': {err}"))
+    }
+
+    /// Get the object stored under `key`, creating it if necessary.
+    pub fn section_mut(&mut self, key: &str) -> &mut Map<String, Value> {
+        let value = self
+            .values
+            .entry(key)
+            .or_insert_with(|| Value::Object(Map::new()));
+
+        if !value.is_object() {
+            *value = Value::Object(Map::new());
+        }
+
+        value
+            .as_object_mut()
+            .expect("value was just set to an object")
+    }
+
+    pub fn section(&self, key: &str) -> Option<&Map<String, Value>> {
+        self.values.get(key).and_then(Value::as_object)
+    }
+
+    pub fn value(&self, key: &str) -> Option<&Value> {
+        self.values.get(key)
+    }
+
+    pub fn values_mut(&mut self) -> &mut Map<String, Value> {
+        &mut self.values
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::State;
+
+    #[test]
+    fn parse_keeps_unknown_keys() {
+        let mut state = State::parse(r#"{"unknown": 1, "flashed": {"a": "b"}}"#);
+
+        state
+            .section_mut("flashed")
+            .insert("c".to_string(), "d".into());
+
+        assert_eq!(state.values["unknown"], 1);
+        assert_eq!(state.section("flashed").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(State::parse("[1, 2]").values.is_empty());
+        assert!(State::parse("{").values.is_empty());
+    }
+
+    #[test]
+    fn section_replaces_non_objects() {
+        let mut state = State::parse(r#"{"flashed": 5}"#);
+        assert!(state.section("flashed").is_none());
+        assert!(state.section_mut("flashed").is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+//! Local usage statistics, for the `usageStatistics` setting.
+//!
+//! Counting sessions, flashes and the kinds of errors over time shows whether a problem recurs,
+//! for example a probe which fails every few sessions. The counters are kept in a file in the
+//! working directory of the extension and are never sent anywhere.
+
+use std::{collections::BTreeMap, fmt::Write, fs};
+
+use zed_extension_api::serde_json::{self, Map, Value, json};
+
+use crate::{errors::Category, settings::Settings};
+
+const STATISTICS_FILE: &str = "statistics.json";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Statistics {
+    pub sessions_started: u64,
+    pub sessions_failed: u64,
+    pub flash_succeeded: u64,
+    pub flash_failed: u64,
+    /// Number of failed sessions per kind of error.
+    pub errors: BTreeMap<String, u64>,
+}
+
+impl Statistics {
+    pub fn load() -> Self {
+        fs::read_to_string(STATISTICS_FILE)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Value>(&contents).ok())
+            .as_ref()
+            .and_then(Value::as_object)
+            .map(Self::from_values)
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(&self.to_values())
+            .map_err(|err| format!("Failed to serialize the usage statistics: {err}"))?;
+
+        fs::write(STATISTICS_FILE, contents).map_err(|err| {
+            format!("Failed to write the usage statistics to '{STATISTICS_FILE}': {err}")
+        })
+    }
+
+    fn from_values(values: &Map<String, Value>) -> Self {
+        let count = |key: &str| values.get(key).and_then(Value::as_u64).unwrap_or(0);
+
+        Self {
+            sessions_started: count("sessionsStarted"),
+            sessions_failed: count("sessionsFailed"),
+            flash_succeeded: count("flashSucceeded"),
+            flash_failed: count("flashFailed"),
+            errors: values
+                .get("errors")
+                .and_then(Value::as_object)
+                .into_iter()
+                .flatten()
+                .filter_map(|(code, count)| Some((code.clone(), count.as_u64()?)))
+                .collect(),
+        }
+    }
+
+    fn to_values(&self) -> Value {
+        json!({
+            "sessionsStarted": self.sessions_started,
+            "sessionsFailed": self.sessions_failed,
+            "flashSucceeded": self.flash_succeeded,
+            "flashFailed": self.flash_failed,
+            "errors": self.errors,
+        })
+    }
+
+    /// A summary for showing to the user, with the most common errors first.
+    pub fn summary(&self) -> String {
+        let mut text = String::new();
+
+        let _ = writeln!(
+            text,
+            "- Sessions: {} started, {} failed to start",
+            self.sessions_started, self.sessions_failed
+        );
+        let _ = writeln!(
+            text,
+            "- Flashing by the extension: {} succeeded, {} failed",
+            self.flash_succeeded, self.flash_failed
+        );
+
+        let mut errors: Vec<_> = self.errors.iter().collect();
+        errors.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        if !errors.is_empty() {
+            let errors: Vec<String> = errors
+                .iter()
+                .map(|(code, count)| format!("{code} ({count})"))
+                .collect();
+            let _ = writeln!(text, "- Errors: {}", errors.join(", "));
+        }
+
+        text
+    }
+}
+
+/// The kind of error a session failed with.
+fn error_code(err: &str) -> &'static str {
+    Category::from_error(err).map_or("other", Category::code)
+}
+
+/// Update the statistics, if they are enabled in the settings.
+fn update(change: impl FnOnce(&mut Statistics)) {
+    if !Settings::last_loaded().usage_statistics {
+        return;
+    }
+
+    let mut statistics = Statistics::load();
+    change(&mut statistics);
+
+    if let Err(err) = statistics.save() {
+        log!("{err}");
+    }
+}
+
+/// Count the outcome of starting a session.
+pub fn record_session(result: Result<(), &str>) {
+    update(|statistics| match result {
+        Ok(()) => statistics.sessions_started += 1,
+        Err(err) => {
+            statistics.sessions_failed += 1;
+            *statistics
+                .errors
+                .entry(error_code(err).to_string())
+                .or_default() += 1;
+        }
+    });
+}
+
+/// Count a flash done by the extension.
+pub fn record_flash(succeeded: bool) {
+    update(|statistics| {
+        if succeeded {
+            statistics.flash_succeeded += 1;
+        } else {
+            statistics.flash_failed += 1;
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    use super::Statistics;
+
+    #[test]
+    fn values_roundtrip() {
+        let statistics = Statistics {
+            sessions_started: 12,
+            sessions_failed: 3,
+            flash_succeeded: 10,
+            flash_failed: 1,
+            errors: [("probe".to_string(), 2), ("other".to_string(), 1)].into(),
+        };
+
+        let values = statistics.to_values();
+        assert_eq!(values["errors"], json!({ "other": 1, "probe": 2 }));
+        assert_eq!(
+            Statistics::from_values(values.as_object().unwrap()),
+            statistics
+        );
+    }
+
+    #[test]
+    fn summary() {
+        let statistics = Statistics {
+            sessions_started: 5,
+            sessions_failed: 3,
+            errors: [("other".to_string(), 1), ("target".to_string(), 2)].into(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            statistics.summary(),
+            "- Sessions: 5 started, 3 failed to start\n\
+             - Flashing by the extension: 0 succeeded, 0 failed\n\
+             - Errors: target (2), other (1)\n"
+        );
+    }
+
+    #[test]
+    fn error_code() {
+        let err = crate::errors::Category::Probe.error("No connected probes were found");
+
+        assert_eq!(super::error_code(&err), "probe");
+        assert_eq!(super::error_code("Failed to parse JSON config"), "other");
+    }
+}
@@ -0,0 +1,370 @@
+//! Discovery of SVD files for the peripherals view.
+//!
+//! Vendor projects usually contain the SVD file of their chip somewhere in the tree. If no
+//! `svdFile` is configured, the SVD files in the worktree are searched for the one which
+//! matches the chip best. If there is none, and the `svdUrl` setting is set, the SVD file is
+//! downloaded into the working directory of the extension.
+
+use std::{
+    fs,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use zed_extension_api::{self as zed, DownloadedFileType, Os, serde_json::Value};
+
+/// Directories which are not searched, because they are large and don't contain sources.
+///
+/// These are part of the `find` capability in `extension.toml`, as is [`MAX_DEPTH`].
+const SKIPPED_DIRECTORIES: &[&str] = &["target", ".git", "node_modules"];
+
+/// Shortest file name which is accepted as a partial match, to avoid matching e.g. `stm32.svd`.
+const MIN_MATCH_LENGTH: usize = 6;
+
+/// Directory for downloaded SVD files, in the working directory of the extension.
+pub const DOWNLOAD_DIRECTORY: &str = "cache/svd";
+
+/// Deepest directory level which is searched, so a huge monorepo can't stall the session.
+const MAX_DEPTH: usize = 8;
+
+/// How long search results are reused, so repeated sessions don't search again.
+const CACHE_DURATION: Duration = Duration::from_secs(300);
+
+/// Search results per directory, with the time of the search.
+static CACHE: Mutex<Vec<(String, Instant, Vec<String>)>> = Mutex::new(Vec::new());
+
+/// Forget the search results, so the next session searches again.
+pub fn clear_cache() {
+    if let Ok(mut cache) = CACHE.lock() {
+        cache.clear();
+    }
+}
+
+/// Directory names from a `.gitignore`, which are skipped in addition to [`SKIPPED_DIRECTORIES`].
+///
+/// Only plain names like `build/` or `/out` are used, patterns with wildcards are ignored.
+pub fn ignored_directories(gitignore: &str) -> Vec<String> {
+    gitignore
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with(['#', '!']))
+        .map(|line| line.trim_start_matches('/').trim_end_matches('/'))
+        .filter(|name| !name.is_empty() && !name.contains(['*', '?', '[', '/', '\\']))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The search tool and its arguments for finding SVD files in a directory.
+///
+/// `find` only skips the [`SKIPPED_DIRECTORIES`], so its arguments have the fixed shape allowed
+/// by the capabilities of the extension. Ignored directories are filtered from the results.
+fn search_command(os: Os, directory: &str) -> (&'static str, Vec<String>) {
+    if os == Os::Windows {
+        return (
+            "where",
+            vec!["/r".to_string(), directory.to_string(), "*.svd".to_string()],
+        );
+    }
+
+    let mut args = vec![
+        directory.to_string(),
+        "-maxdepth".to_string(),
+        MAX_DEPTH.to_string(),
+        "(".to_string(),
+    ];
+    for (index, skipped) in SKIPPED_DIRECTORIES.iter().enumerate() {
+        if index > 0 {
+            args.push("-o".to_string());
+        }
+        args.extend(["-name".to_string(), skipped.to_string()]);
+    }
+    args.extend(
+        [
+            ")", "-prune", "-o", "-type", "f", "-iname", "*.svd", "-print",
+        ]
+        .map(str::to_string),
+    );
+
+    ("find", args)
+}
+
+/// Whether a found file is inside one of the skipped directories.
+///
+/// `where` can't skip directories, so its results are filtered instead.
+fn is_skipped(directory: &str, file: &str, skipped: &[String]) -> bool {
+    let relative = file.strip_prefix(directory).unwrap_or(file);
+
+    relative
+        .split(['/', '\\'])
+        .rev()
+        .skip(1)
+        .any(|component| skipped.iter().any(|skipped| skipped == component))
+}
+
+/// Find all SVD files in the given directories, using the search tool of the host.
+fn find_svd_files(directories: &[String], ignored: &[String]) -> Vec<String> {
+    let (os, _) = zed::current_platform();
+
+    let skipped: Vec<String> = SKIPPED_DIRECTORIES
+        .iter()
+        .map(|name| name.to_string())
+        .chain(ignored.iter().cloned())
+        .collect();
+
+    let mut cache = CACHE.lock().unwrap_or_else(|err| err.into_inner());
+    cache.retain(|(_, searched, _)| searched.elapsed() < CACHE_DURATION);
+
+    let mut files = Vec::new();
+
+    for directory in directories {
+        if let Some((_, _, cached)) = cache.iter().find(|(cached, _, _)| cached == directory) {
+            files.extend(cached.iter().cloned());
+            continue;
+        }
+
+        let (tool, args) = search_command(os, directory);
+
+        let found: Vec<String> = match crate::process::run(tool, &args) {
+            Ok(output) => output
+                .lines()
+                .map(str::trim)
+                .filter(|file| !file.is_empty() && !is_skipped(directory, file, &skipped))
+                .filter(|file| {
+                    let mangled = crate::process::is_mangled(file);
+                    if mangled {
+                        log!("Ignoring SVD file '{file}', its path is not valid UTF-8");
+                    }
+                    !mangled
+                })
+                .map(str::to_string)
+                .collect(),
+            Err(err) => {
+                log!("Failed to search for SVD files in '{directory}': {err}");
+                continue;
+            }
+        };
+
+        cache.push((directory.clone(), Instant::now(), found.clone()));
+        files.extend(found);
+    }
+
+    files
+}
+
+/// How well the file name matches the chip, or `None` if it doesn't match at all.
+///
+/// The file name has to be a prefix of the chip name, where `x` in the file name matches any
+/// character, like in `STM32F40x.svd`. Longer matches are better.
+fn match_length(chip: &str, file: &str) -> Option<usize> {
+    let name = file.rsplit(['/', '\\']).next().unwrap_or(file);
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+
+    let chip = chip.to_ascii_lowercase();
+    let stem = stem.to_ascii_lowercase();
+
+    if stem.len() > chip.len() || (stem.len() < MIN_MATCH_LENGTH && stem != chip) {
+        return None;
+    }
+
+    let matches = stem
+        .chars()
+        .zip(chip.chars())
+        .all(|(s, c)| s == c || s == 'x');
+
+    matches.then_some(stem.len())
+}
+
+/// The SVD file which matches the chip best.
+fn best_match<'a>(chip: &str, files: &'a [String]) -> Option<&'a str> {
+    files
+        .iter()
+        .filter_map(|file| Some((match_length(chip, file)?, file)))
+        .max_by_key(|(length, _)| *length)
+        .map(|(_, file)| file.as_str())
+}
+
+/// Use a matching SVD file if the configuration doesn't specify one.
+///
+/// The worktree is searched first, skipping the directories in `ignored`. If it doesn't contain a matching file, and `url_template`
+/// is set, the file is downloaded. This is only done for configurations with a single core,
+/// since the cores of multi-core chips usually have different SVD files.
+pub fn apply_discovered(
+    config: &mut Value,
+    worktree_root: &str,
+    ignored: &[String],
+    extra_directory: Option<&str>,
+    url_template: Option<&str>,
+) {
+    let Some(chip) = config
+        .get("chip")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+    else {
+        return;
+    };
+
+    let Some([core]) = config
+        .get_mut("coreConfigs")
+        .and_then(Value::as_array_mut)
+        .map(Vec::as_mut_slice)
+    else {
+        return;
+    };
+
+    if core.get("svdFile").is_some_and(|svd| !svd.is_null()) || !core.is_object() {
+        return;
+    }
+
+    let mut directories = vec![worktree_root.to_string()];
+    directories.extend(
+        extra_directory.map(|directory| crate::paths::join(Some(worktree_root), directory)),
+    );
+
+    let files = find_svd_files(&directories, ignored);
+
+    if let Some(file) = best_match(&chip, &files) {
+        log!("Using SVD file '{file}' for '{chip}'");
+        core["svdFile"] = file.into();
+        return;
+    }
+
+    let Some(url_template) = url_template else {
+        return;
+    };
+
+    match download(&chip, url_template) {
+        Ok(file) => {
+            log!("Using downloaded SVD file '{file}' for '{chip}'");
+            core["svdFile"] = file.into();
+        }
+        Err(err) => log!("No SVD file for '{chip}': {err}"),
+    }
+}
+
+/// The download URL for the chip, from a template containing `{chip}`.
+fn download_url(url_template: &str, chip: &str) -> Result<String, String> {
+    if !url_template.contains("{chip}") {
+        return Err(format!(
+            "The SVD URL '{url_template}' doesn't contain the {{chip}} placeholder"
+        ));
+    }
+
+    Ok(url_template.replace("{chip}", chip))
+}
+
+/// Download the SVD file for the chip, or use the previously downloaded file.
+fn download(chip: &str, url_template: &str) -> Result<String, String> {
+    let path = format!(
+        "{DOWNLOAD_DIRECTORY}/{}.svd",
+        crate::config::file_name(chip)
+    );
+
+    if fs::metadata(&path).is_err() {
+        let url = download_url(url_template, chip)?;
+
+        fs::create_dir_all(DOWNLOAD_DIRECTORY)
+            .map_err(|err| format!("Failed to create '{DOWNLOAD_DIRECTORY}': {err}"))?;
+
+        log!("Downloading SVD file for '{chip}' from {url}");
+        zed::download_file(&url, &path, DownloadedFileType::Uncompressed)
+            .map_err(|err| format!("Failed to download '{url}': {err}"))?;
+    }
+
+    crate::paths::in_work_dir(&path)
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::Os;
+
+    #[test]
+    fn match_length() {
+        assert_eq!(
+            super::match_length("nRF52840_xxAA", "/p/svd/nrf52840.svd"),
+            Some(8)
+        );
+        assert_eq!(
+            super::match_length("STM32F401RETx", "C:\\p\\STM32F40x.svd"),
+            Some(9)
+        );
+        assert_eq!(super::match_length("STM32F401RETx", "STM32F411.svd"), None);
+        assert_eq!(super::match_length("STM32F401RETx", "stm32.svd"), None);
+        assert_eq!(super::match_length("esp32", "esp32.svd"), Some(5));
+    }
+
+    #[test]
+    fn ignored_directories() {
+        assert_eq!(
+            super::ignored_directories(
+                "# build output\n/build/\nout\n*.o\n!keep\nvendor/generated\n"
+            ),
+            ["build", "out"]
+        );
+    }
+
+    #[test]
+    fn search_command() {
+        let (tool, args) = super::search_command(Os::Linux, "/p");
+        assert_eq!(tool, "find");
+        assert_eq!(
+            args[..13],
+            [
+                "/p",
+                "-maxdepth",
+                "8",
+                "(",
+                "-name",
+                "target",
+                "-o",
+                "-name",
+                ".git",
+                "-o",
+                "-name",
+                "node_modules",
+                ")"
+            ]
+        );
+
+        let (tool, args) = super::search_command(Os::Windows, "C:\\My Projects\\p");
+        assert_eq!(tool, "where");
+        assert_eq!(args, ["/r", "C:\\My Projects\\p", "*.svd"]);
+    }
+
+    #[test]
+    fn is_skipped() {
+        let skipped = ["target".to_string()];
+
+        assert!(super::is_skipped(
+            "C:\\p",
+            "C:\\p\\tools\\target\\a.svd",
+            &skipped
+        ));
+        assert!(!super::is_skipped("/p", "/p/svd/target.svd", &skipped));
+        // The searched directory itself may be inside a skipped directory.
+        assert!(!super::is_skipped("/target/p", "/target/p/a.svd", &skipped));
+    }
+
+    #[test]
+    fn download_url() {
+        assert_eq!(
+            super::download_url("https://example.com/svd/{chip}.svd", "nRF52840_xxAA").unwrap(),
+            "https://example.com/svd/nRF52840_xxAA.svd"
+        );
+        assert!(super::download_url("https://example.com/svd", "nRF52840_xxAA").is_err());
+    }
+
+    #[test]
+    fn best_match() {
+        let files = [
+            "/p/vendor/STM32F40x.svd".to_string(),
+            "/p/vendor/STM32F401.svd".to_string(),
+            "/p/vendor/nrf52840.svd".to_string(),
+        ];
+
+        assert_eq!(
+            super::best_match("STM32F401RETx", &files),
+            Some("/p/vendor/STM32F401.svd")
+        );
+        assert_eq!(super::best_match("rp2040", &files), None);
+    }
+}
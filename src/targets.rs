@@ -0,0 +1,189 @@
+//! Knowledge about target chips, based on the probe-rs target names.
+
+/// Architecture of the cores of a chip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Architecture {
+    Arm,
+    RiscV,
+    Xtensa,
+}
+
+/// Chip name prefixes of known families, mapped to their architecture.
+///
+/// More specific prefixes have to come first.
+const FAMILIES: &[(&str, Architecture)] = &[
+    ("esp32c", Architecture::RiscV),
+    ("esp32h", Architecture::RiscV),
+    ("esp32p", Architecture::RiscV),
+    ("esp32", Architecture::Xtensa),
+    ("gd32vf", Architecture::RiscV),
+    ("fe310", Architecture::RiscV),
+    ("ch32v", Architecture::RiscV),
+    ("stm32", Architecture::Arm),
+    ("nrf", Architecture::Arm),
+    ("rp2040", Architecture::Arm),
+    ("atsam", Architecture::Arm),
+    ("lpc", Architecture::Arm),
+    ("mimxrt", Architecture::Arm),
+    ("efr32", Architecture::Arm),
+];
+
+/// Board and marketing names, mapped to the probe-rs target name of their chip.
+///
+/// Names are compared ignoring case, spaces and punctuation, so `nRF52840-DK` matches as well.
+const ALIASES: &[(&str, &str)] = &[
+    ("nRF52840 DK", "nRF52840_xxAA"),
+    ("nRF52840 Dongle", "nRF52840_xxAA"),
+    ("Feather nRF52840", "nRF52840_xxAA"),
+    ("nRF52833 DK", "nRF52833_xxAA"),
+    ("nRF52 DK", "nRF52832_xxAA"),
+    ("nRF52832 DK", "nRF52832_xxAA"),
+    ("nRF5340 DK", "nRF5340_xxAA"),
+    ("nRF9160 DK", "nRF9160_xxAA"),
+    ("micro:bit", "nRF51822_xxAA"),
+    ("micro:bit v2", "nRF52833_xxAA"),
+    ("Blue Pill", "STM32F103C8"),
+    ("Black Pill", "STM32F411CEUx"),
+    ("Nucleo-F401RE", "STM32F401RETx"),
+    ("Nucleo-F411RE", "STM32F411RETx"),
+    ("Nucleo-F446RE", "STM32F446RETx"),
+    ("Nucleo-L476RG", "STM32L476RGTx"),
+    ("STM32F3DISCOVERY", "STM32F303VCTx"),
+    ("STM32F4DISCOVERY", "STM32F407VGTx"),
+    ("Pico", "RP2040"),
+    ("Pico W", "RP2040"),
+    ("Raspberry Pi Pico", "RP2040"),
+    ("Pico 2", "RP235x"),
+    ("ESP32-C3-DevKitM-1", "esp32c3"),
+    ("ESP32-C6-DevKitC-1", "esp32c6"),
+    ("ESP32-S3-DevKitC-1", "esp32s3"),
+];
+
+/// Whether connecting under reset is needed for a chip, or can't work with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetConnection {
+    Required,
+    Forbidden,
+}
+
+impl ResetConnection {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "required" => Some(Self::Required),
+            "forbidden" => Some(Self::Forbidden),
+            _ => None,
+        }
+    }
+}
+
+/// Chip name prefixes which must or must not be connected to under reset, with the reason.
+const RESET_CONNECTIONS: &[(&str, ResetConnection, &str)] = &[
+    (
+        "rp2",
+        ResetConnection::Forbidden,
+        "holding the RUN pin low resets the whole chip including its debug port, so the probe \
+         can't connect while it's held in reset",
+    ),
+    (
+        "esp32",
+        ResetConnection::Forbidden,
+        "the built-in USB-JTAG interface is reset together with the chip, so the probe \
+         disconnects",
+    ),
+];
+
+/// Whether the chip must or must not be connected to under reset, and why.
+///
+/// `flags` are chip name prefixes from the settings, which take precedence over the built-in
+/// knowledge, for boards where the firmware disables the debug port or the reset line is not
+/// wired.
+pub fn reset_connection(
+    chip: &str,
+    flags: &[(String, ResetConnection)],
+) -> Option<(ResetConnection, String)> {
+    let chip = chip.to_ascii_lowercase();
+    let matches = |prefix: &str| chip.starts_with(&prefix.to_ascii_lowercase());
+
+    if let Some((prefix, flag)) = flags.iter().find(|(prefix, _)| matches(prefix)) {
+        return Some((
+            *flag,
+            format!("'{prefix}' is flagged in 'connectUnderReset' of the settings"),
+        ));
+    }
+
+    RESET_CONNECTIONS
+        .iter()
+        .find(|(prefix, _, _)| matches(prefix))
+        .map(|(_, flag, reason)| (*flag, reason.to_string()))
+}
+
+/// The probe-rs target name for a board or marketing name, if the chip is one.
+pub fn resolve_alias(chip: &str) -> Option<&'static str> {
+    let normalize = |name: &str| -> String {
+        name.chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    };
+
+    let chip = normalize(chip);
+
+    ALIASES
+        .iter()
+        .find(|(alias, _)| normalize(alias) == chip)
+        .map(|(_, target)| *target)
+}
+
+/// Guess the architecture of a chip from its name.
+pub fn architecture(chip: &str) -> Option<Architecture> {
+    let chip = chip.to_ascii_lowercase();
+
+    FAMILIES
+        .iter()
+        .find(|(prefix, _)| chip.starts_with(prefix))
+        .map(|(_, architecture)| *architecture)
+}
+
+/// Whether the chip is one of the Espressif chips, which are debugged over their USB-JTAG interface.
+pub fn is_espressif(chip: &str) -> bool {
+    chip.to_ascii_lowercase().starts_with("esp32")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Architecture, ResetConnection};
+
+    #[test]
+    fn architecture() {
+        assert_eq!(
+            super::architecture("nRF52840_xxAA"),
+            Some(Architecture::Arm)
+        );
+        assert_eq!(super::architecture("esp32c3"), Some(Architecture::RiscV));
+        assert_eq!(super::architecture("esp32s3"), Some(Architecture::Xtensa));
+        assert_eq!(super::architecture("unknown"), None);
+    }
+
+    #[test]
+    fn resolve_alias() {
+        assert_eq!(super::resolve_alias("nRF52840 DK"), Some("nRF52840_xxAA"));
+        assert_eq!(super::resolve_alias("blue-pill"), Some("STM32F103C8"));
+        assert_eq!(super::resolve_alias("Pico"), Some("RP2040"));
+        assert_eq!(super::resolve_alias("Micro:bit V2"), Some("nRF52833_xxAA"));
+        assert_eq!(super::resolve_alias("nRF52840_xxAA"), None);
+    }
+
+    #[test]
+    fn reset_connection() {
+        assert_eq!(
+            super::reset_connection("RP2040", &[]).map(|(flag, _)| flag),
+            Some(ResetConnection::Forbidden)
+        );
+        assert_eq!(super::reset_connection("nRF52840_xxAA", &[]), None);
+
+        let flags = [("STM32L0".to_string(), ResetConnection::Required)];
+        let (flag, reason) = super::reset_connection("STM32L072CZYx", &flags).unwrap();
+        assert_eq!(flag, ResetConnection::Required);
+        assert!(reason.contains("settings"));
+    }
+}
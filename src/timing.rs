@@ -0,0 +1,99 @@
+//! Timing of the phases of starting a session.
+//!
+//! When starting a session feels slow, the timings show which phase takes the time. Only the
+//! phases done by the extension can be measured; starting the dap-server and connecting to it
+//! is done by Zed afterwards. The timings of the last session are kept in the extension state
+//! and nowhere else.
+
+use std::time::{Duration, Instant};
+
+use zed_extension_api::serde_json::Value;
+
+use crate::state::State;
+
+const STATE_KEY: &str = "lastTimings";
+
+/// The phases measured while a session is started.
+pub struct Timings {
+    started: Instant,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            phases: Vec::new(),
+        }
+    }
+
+    /// Run a phase, and remember how long it took.
+    pub fn measure<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.phases.push((phase, start.elapsed()));
+
+        result
+    }
+
+    /// Remember the timings as the ones of the last session, and print them if `print` is set.
+    pub fn finish(self, print: bool) {
+        let summary = format_summary(&self.phases, self.started.elapsed());
+
+        if print {
+            log!("Timings: {summary}");
+        }
+
+        let mut state = State::load();
+        state
+            .values_mut()
+            .insert(STATE_KEY.to_string(), summary.into());
+        if let Err(err) = state.save() {
+            log!("{err}");
+        }
+    }
+}
+
+/// The timings of the last session which was started.
+pub fn last() -> Option<String> {
+    State::load()
+        .value(STATE_KEY)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+fn format_summary(phases: &[(&str, Duration)], total: Duration) -> String {
+    phases
+        .iter()
+        .map(|(phase, duration)| (*phase, *duration))
+        .chain([("total", total)])
+        .map(|(phase, duration)| format!("{phase} {}", format_duration(duration)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{} ms", duration.as_millis())
+    } else {
+        format!("{:.2} s", duration.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    #[test]
+    fn format_summary() {
+        let phases = [
+            ("binary", Duration::from_millis(12)),
+            ("flash", Duration::from_millis(4_204)),
+        ];
+
+        assert_eq!(
+            super::format_summary(&phases, Duration::from_millis(4_530)),
+            "binary 12 ms, flash 4.20 s, total 4.53 s"
+        );
+    }
+}
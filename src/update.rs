@@ -0,0 +1,106 @@
+//! Check for newer probe-rs releases.
+//!
+//! New probe-rs releases often add chips and fix probe support, so a notice is printed when a
+//! session starts with an older version. The latest release is looked up at most once a day.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zed_extension_api::serde_json::{Value, json};
+
+use crate::{install::Channel, state::State, version::Version};
+
+const STATE_KEY: &str = "latestRelease";
+
+/// How long the latest release is remembered, in seconds.
+const CHECK_INTERVAL: u64 = 24 * 60 * 60;
+
+/// Print a notice if the version is older than the latest probe-rs release.
+///
+/// `token` is a GitHub token for looking up the release, see [`crate::github`].
+pub fn check(current: Version, channel: Channel, token: Option<&str>) {
+    let Some(latest) = latest_version(channel, token) else {
+        return;
+    };
+
+    if let Some(notice) = notice(current, latest) {
+        log!("{notice}");
+    }
+}
+
+fn notice(current: Version, latest: Version) -> Option<String> {
+    (latest > current).then(|| {
+        format!(
+            "probe-rs {latest} is available, this session uses {current}. See <https://probe.rs/docs/getting-started/installation/> for updating it."
+        )
+    })
+}
+
+/// The version of the latest release, from the state if it was checked recently.
+fn latest_version(channel: Channel, token: Option<&str>) -> Option<Version> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+
+    let mut state = State::load();
+
+    if let Some(latest) = state.section(STATE_KEY)
+        && let Some(checked_at) = latest.get("checkedAt").and_then(Value::as_u64)
+        && is_fresh(checked_at, now)
+        && latest.get("channel").and_then(Value::as_str) == Some(channel_name(channel))
+    {
+        return latest
+            .get("version")
+            .and_then(Value::as_str)
+            .and_then(Version::parse);
+    }
+
+    let release = crate::install::latest_release(channel, token);
+
+    // A failed check is remembered as well, so it's not retried for every session.
+    let version = match release {
+        Ok(release) => Some(release.version),
+        Err(err) => {
+            log!("Failed to check for probe-rs updates: {err}");
+            None
+        }
+    };
+
+    state.values_mut().insert(
+        STATE_KEY.to_string(),
+        json!({ "version": version, "checkedAt": now, "channel": channel_name(channel) }),
+    );
+    if let Err(err) = state.save() {
+        log!("{err}");
+    }
+
+    version.as_deref().and_then(Version::parse)
+}
+
+fn channel_name(channel: Channel) -> &'static str {
+    match channel {
+        Channel::Stable => "stable",
+        Channel::Prerelease => "prerelease",
+    }
+}
+
+fn is_fresh(checked_at: u64, now: u64) -> bool {
+    now.saturating_sub(checked_at) < CHECK_INTERVAL
+}
+
+#[cfg(test)]
+mod test {
+    use crate::version::Version;
+
+    #[test]
+    fn notice() {
+        let version = |version| Version::parse(version).unwrap();
+
+        assert!(super::notice(version("0.24.0"), version("0.25.1")).is_some());
+        assert!(super::notice(version("0.25.1"), version("0.25.1")).is_none());
+        assert!(super::notice(version("0.26.0"), version("0.25.1")).is_none());
+    }
+
+    #[test]
+    fn is_fresh() {
+        assert!(super::is_fresh(1_000, 1_000 + 60));
+        assert!(!super::is_fresh(1_000, 1_000 + super::CHECK_INTERVAL));
+    }
+}
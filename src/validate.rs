@@ -0,0 +1,755 @@
+//! Validation of the debug configuration before a session is started.
+//!
+//! The checks here catch configurations which probe-rs would reject, or which can't work with
+//! the hardware, and report them with an explanation instead of an opaque failure later.
+
+use std::fmt;
+
+use zed_extension_api::serde_json::{Value, json};
+
+use crate::{
+    enumeration::ConnectedProbe,
+    probes::{ProbeKind, WireProtocol},
+    targets::{self, Architecture, ResetConnection},
+};
+
+/// A single problem found in the configuration.
+#[derive(Debug, PartialEq)]
+pub struct Finding {
+    /// Stable identifier for the kind of problem.
+    pub code: &'static str,
+    /// The configuration field the problem was found in.
+    pub path: String,
+    pub message: String,
+    /// How the problem can be fixed, if there is an obvious fix.
+    pub suggestion: Option<String>,
+}
+
+impl Finding {
+    fn warning(code: &'static str, path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            path: path.into(),
+            message: message.into(),
+            suggestion: None,
+        }
+    }
+
+    fn with_suggestion(mut self, suggestion: impl Into<String>) -> Self {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "severity": "warning",
+            "code": self.code,
+            "path": self.path,
+            "message": self.message,
+            "suggestion": self.suggestion,
+        })
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Warning ({}): {}: {}",
+            self.code, self.path, self.message
+        )
+    }
+}
+
+/// Clamp the configured speed to the maximum supported by the probe and protocol.
+///
+/// Too high speeds make the connection fail in confusing ways, so instead of passing them
+/// along, the speed is reduced. The returned warning is also part of [`validate`]. A protocol
+/// the probe doesn't support is reported by [`validate`] instead, so the speed is left as is.
+pub fn clamp_speed(config: &mut Value) -> Option<Finding> {
+    let speed = config.get("speed").and_then(Value::as_u64)?;
+
+    let protocol = config
+        .get("wireProtocol")
+        .and_then(Value::as_str)
+        .and_then(WireProtocol::parse);
+    let probe = probe_kind(config);
+
+    let (max_speed, limited_by) = match probe {
+        Some(probe) => (probe.max_speed_khz(protocol)?, probe.name()),
+        None => (
+            protocol.map_or(u32::MAX, WireProtocol::max_speed_khz),
+            protocol.map_or("", WireProtocol::name),
+        ),
+    };
+
+    if speed <= u64::from(max_speed) {
+        return None;
+    }
+
+    config["speed"] = max_speed.into();
+
+    Some(Finding::warning(
+        "speed-clamped",
+        "speed",
+        format!(
+            "{speed} kHz exceeds the maximum of {max_speed} kHz supported by {limited_by}, using {max_speed} kHz instead"
+        ),
+    )
+    .with_suggestion(format!("Set \"speed\" to {max_speed} or less")))
+}
+
+/// Replace a board or marketing name in `chip` with the probe-rs target name.
+///
+/// The returned warning tells the target name, so it can be used in the configuration. It is
+/// also part of [`validate`].
+pub fn resolve_chip_alias(config: &mut Value) -> Option<Finding> {
+    let chip = config.get("chip").and_then(Value::as_str)?;
+    let target = targets::resolve_alias(chip)?;
+
+    let finding = Finding::warning(
+        "chip-alias",
+        "chip",
+        format!("'{chip}' is not a probe-rs target name, using '{target}' instead"),
+    )
+    .with_suggestion(format!("Set \"chip\" to \"{target}\""));
+
+    config["chip"] = target.into();
+
+    Some(finding)
+}
+
+/// Run all checks on the configuration.
+pub fn validate(config: &Value) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    check_probe_pair(config, &mut findings);
+    check_speed(config, &mut findings);
+    check_flashing(config, &mut findings);
+
+    findings.extend(resolve_chip_alias(&mut config.clone()));
+    findings.extend(clamp_speed(&mut config.clone()));
+
+    findings
+}
+
+/// Check `connectUnderReset` against chips which need it or can't work with it, see
+/// [`targets::reset_connection`].
+///
+/// This is separate from [`validate`], since it also depends on the flags in the settings.
+pub fn check_connect_under_reset(
+    config: &Value,
+    flags: &[(String, ResetConnection)],
+) -> Option<Finding> {
+    let chip = config.get("chip").and_then(Value::as_str)?;
+    let (flag, reason) = targets::reset_connection(chip, flags)?;
+
+    let enabled = config
+        .get("connectUnderReset")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+
+    match flag {
+        ResetConnection::Forbidden if enabled => Some(
+            Finding::warning(
+                "connect-under-reset-forbidden",
+                "connectUnderReset",
+                format!("'{chip}' can't be connected to under reset: {reason}"),
+            )
+            .with_suggestion("Remove \"connectUnderReset\""),
+        ),
+        ResetConnection::Required if !enabled => Some(
+            Finding::warning(
+                "connect-under-reset-required",
+                "connectUnderReset",
+                format!("'{chip}' has to be connected to under reset: {reason}"),
+            )
+            .with_suggestion("Set \"connectUnderReset\": true"),
+        ),
+        _ => None,
+    }
+}
+
+/// Check that probe-rs knows the chip, with the chips it listed.
+///
+/// Chips of a `chipDescriptionPath` are not listed, so they are not checked.
+pub fn check_chip(config: &Value, chips: &[String]) -> Option<Finding> {
+    let chip = config.get("chip").and_then(Value::as_str)?;
+    if config.get("chipDescriptionPath").is_some() || crate::chips::is_known(chips, chip) {
+        return None;
+    }
+
+    Some(
+        Finding::warning(
+            "unknown-chip",
+            "chip",
+            format!("probe-rs doesn't know the chip '{chip}'"),
+        )
+        .with_suggestion(
+            "Check the name with `probe-rs chip list`, or run /probe-rs-refresh-chips if \
+             probe-rs was just updated",
+        ),
+    )
+}
+
+/// Check that the configured probe is connected.
+///
+/// This is separate from [`validate`], since listing the probes needs the hardware.
+pub fn check_connected(config: &Value, probes: &[ConnectedProbe]) -> Option<Finding> {
+    if probes.is_empty() {
+        return Some(
+            Finding::warning("no-probes", "probe", "No debug probes are connected")
+                .with_suggestion("Connect a debug probe, and check the USB permissions"),
+        );
+    }
+
+    let probe = config.get("probe")?;
+    let id = |field| {
+        probe
+            .get(field)
+            .and_then(Value::as_u64)
+            .and_then(|id| u16::try_from(id).ok())
+    };
+    let (vendor_id, product_id) = (id("vendor_id")?, id("product_id")?);
+    let serial = probe.get("serial_number").and_then(Value::as_str);
+
+    let connected = probes.iter().any(|connected| {
+        connected.vendor_id == vendor_id
+            && connected.product_id == product_id
+            && serial.is_none_or(|serial| connected.serial.as_deref() == Some(serial))
+    });
+
+    (!connected).then(|| {
+        Finding::warning(
+            "probe-not-connected",
+            "probe",
+            format!(
+                "The probe {vendor_id:04x}:{product_id:04x}{} is not connected",
+                serial
+                    .map(|serial| format!(":{serial}"))
+                    .unwrap_or_default()
+            ),
+        )
+        .with_suggestion("Connect the probe, or select one of the connected probes")
+    })
+}
+
+fn probe_kind(config: &Value) -> Option<ProbeKind> {
+    let probe = config.get("probe")?;
+
+    let id = |field| {
+        probe
+            .get(field)
+            .and_then(Value::as_u64)
+            .and_then(|id| u16::try_from(id).ok())
+    };
+
+    ProbeKind::from_usb_id(id("vendor_id")?, id("product_id")?)
+}
+
+/// Check for combinations of probe, chip and wire protocol which can't work.
+fn check_probe_pair(config: &Value, findings: &mut Vec<Finding>) {
+    let chip = config.get("chip").and_then(Value::as_str);
+    let architecture = chip.and_then(targets::architecture);
+    let protocol = config
+        .get("wireProtocol")
+        .and_then(Value::as_str)
+        .and_then(WireProtocol::parse);
+
+    if let (Some(architecture), Some(WireProtocol::Swd)) = (architecture, protocol)
+        && architecture != Architecture::Arm
+    {
+        let chip = chip.unwrap_or_default();
+
+        findings.push(Finding::warning(
+            "swd-non-arm",
+            "wireProtocol",
+            format!("SWD is only supported by ARM chips, but '{chip}' is not an ARM chip. Use JTAG instead."),
+        ).with_suggestion("Set \"wireProtocol\" to \"Jtag\""));
+    }
+
+    let Some(probe) = probe_kind(config) else {
+        return;
+    };
+
+    if let Some(protocol) = protocol
+        && !probe.supports_protocol(protocol)
+    {
+        findings.push(
+            Finding::warning(
+                "probe-protocol",
+                "wireProtocol",
+                format!(
+                    "The {} probe doesn't support {}",
+                    probe.name(),
+                    protocol.name()
+                ),
+            )
+            .with_suggestion("Use a different probe, or change \"wireProtocol\""),
+        );
+    }
+
+    let Some(chip) = chip else {
+        return;
+    };
+
+    match probe {
+        ProbeKind::StLinkV2 | ProbeKind::StLinkV3
+            if architecture.is_some_and(|a| a != Architecture::Arm) =>
+        {
+            findings.push(
+                Finding::warning(
+                    "probe-chip",
+                    "probe",
+                    format!(
+                        "{} probes can only debug ARM chips, but '{chip}' is not an ARM chip",
+                        probe.name()
+                    ),
+                )
+                .with_suggestion("Use a probe which supports the chip"),
+            );
+        }
+        ProbeKind::EspUsbJtag if !targets::is_espressif(chip) => {
+            findings.push(
+                Finding::warning(
+                    "probe-chip",
+                    "probe",
+                    format!(
+                        "The {} interface can only debug Espressif chips, not '{chip}'",
+                        probe.name()
+                    ),
+                )
+                .with_suggestion("Use a probe which supports the chip"),
+            );
+        }
+        _ => {}
+    }
+}
+
+fn check_speed(config: &Value, findings: &mut Vec<Finding>) {
+    if config.get("speed").and_then(Value::as_u64) == Some(0) {
+        findings.push(
+            Finding::warning(
+                "speed-zero",
+                "speed",
+                "A speed of 0 kHz is not valid, remove the field to use the default speed",
+            )
+            .with_suggestion("Remove the \"speed\" field"),
+        );
+    }
+}
+
+/// Check for flashing settings which contradict each other.
+fn check_flashing(config: &Value, findings: &mut Vec<Finding>) {
+    let flashing = |field| {
+        config
+            .pointer(&format!("/flashingConfig/{field}"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+    };
+
+    check_core_halt(config, findings);
+
+    let core_halt = crate::config::core_halt_after_reset(config)
+        .iter()
+        .any(|(_, halt)| *halt);
+    let halt_path = if flashing("haltAfterReset") {
+        "flashingConfig.haltAfterReset"
+    } else {
+        "coreConfigs.haltAfterReset"
+    };
+
+    if (flashing("haltAfterReset") || core_halt) && !flashing("flashingEnabled") {
+        findings.push(
+            Finding::warning(
+                "halt-without-flashing",
+                halt_path,
+                "haltAfterReset only applies after flashing, but flashing is disabled",
+            )
+            .with_suggestion("Set \"flashingEnabled\" to true, or remove \"haltAfterReset\""),
+        );
+    }
+
+    // Every program binary is flashed by probe-rs, and every additional image by the
+    // extension, so the same file listed twice is flashed twice.
+    let mut flashed: Vec<(String, &str)> = Vec::new();
+
+    let cores = config.get("coreConfigs").and_then(Value::as_array);
+    for (index, core) in cores.into_iter().flatten().enumerate() {
+        if let Some(program) = core.get("programBinary").and_then(Value::as_str) {
+            flashed.push((format!("coreConfigs[{index}].programBinary"), program));
+        }
+    }
+
+    if let Err(err) = crate::images::additional_images(config) {
+        findings.push(Finding::warning("invalid-image", "additionalImages", err));
+        return;
+    }
+
+    let images = config.get("additionalImages").and_then(Value::as_array);
+    let images = images.map(Vec::as_slice).unwrap_or_default();
+
+    for (index, image) in images.iter().enumerate() {
+        if let Some(path) = image.get("path").and_then(Value::as_str) {
+            flashed.push((format!("additionalImages[{index}].path"), path));
+        }
+    }
+
+    for (index, (path, file)) in flashed.iter().enumerate() {
+        if let Some((other, _)) = flashed[..index].iter().find(|(_, other)| other == file) {
+            findings.push(
+                Finding::warning(
+                    "duplicate-image",
+                    path.clone(),
+                    format!("'{file}' is already flashed by {other}"),
+                )
+                .with_suggestion(format!("Remove one of {other} and {path}")),
+            );
+        }
+    }
+
+    // The extent of binary images isn't known without reading them, but images at the same
+    // base address definitely overwrite each other.
+    let base_address = |image: &Value| image.get("baseAddress").and_then(Value::as_u64);
+
+    for (index, image) in images.iter().enumerate() {
+        let Some(address) = base_address(image) else {
+            continue;
+        };
+
+        if let Some(other) = images[..index]
+            .iter()
+            .position(|other| base_address(other) == Some(address))
+        {
+            findings.push(
+                Finding::warning(
+                    "image-overlap",
+                    format!("additionalImages[{index}].baseAddress"),
+                    format!(
+                        "Image is flashed to {address:#010x} ({address}), like additionalImages[{other}], and overwrites it"
+                    ),
+                )
+                .with_suggestion("Use a different \"baseAddress\" for one of the images"),
+            );
+        }
+    }
+}
+
+/// Turn numbers given as strings into integers like the session does, see
+/// [`crate::config::normalize_numbers`], for validating a scenario which wasn't started.
+pub fn normalize_numbers(config: &mut Value) -> Option<Finding> {
+    crate::config::normalize_numbers(config).err().map(|err| {
+        Finding::warning("invalid-number", err.path.clone(), err.to_string())
+            .with_suggestion("Use an integer, or a string with a decimal or 0x-prefixed hex number")
+    })
+}
+
+/// Check `haltAfterReset` of the cores, which probe-rs can only apply to all cores at once.
+fn check_core_halt(config: &Value, findings: &mut Vec<Finding>) {
+    let cores = crate::config::core_halt_after_reset(config);
+
+    if cores.is_empty() {
+        return;
+    }
+
+    let global = config
+        .pointer("/flashingConfig/haltAfterReset")
+        .and_then(Value::as_bool);
+
+    if let Some(global) = global {
+        for (index, halt) in &cores {
+            if *halt != global {
+                findings.push(
+                    Finding::warning(
+                        "core-halt-conflict",
+                        format!("coreConfigs[{index}].haltAfterReset"),
+                        format!(
+                            "flashingConfig.haltAfterReset is {global}, which takes precedence for all cores"
+                        ),
+                    )
+                    .with_suggestion("Remove \"haltAfterReset\" from \"flashingConfig\""),
+                );
+            }
+        }
+        return;
+    }
+
+    let halted = cores.iter().filter(|(_, halt)| *halt).count();
+    let cores_count = config
+        .get("coreConfigs")
+        .and_then(Value::as_array)
+        .map_or(0, Vec::len);
+
+    if halted > 0 && halted < cores_count {
+        findings.push(
+            Finding::warning(
+                "core-halt-mixed",
+                "coreConfigs.haltAfterReset",
+                "probe-rs can only halt all cores or none after reset, so the session can't start with only some cores halted",
+            )
+            .with_suggestion("Set \"haltAfterReset\" to the same value for all cores"),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    use crate::{enumeration::ConnectedProbe, targets::ResetConnection};
+
+    fn codes(config: zed_extension_api::serde_json::Value) -> Vec<&'static str> {
+        super::validate(&config)
+            .into_iter()
+            .map(|finding| finding.code)
+            .collect()
+    }
+
+    #[test]
+    fn clamp_speed_to_probe() {
+        let mut config = json!({
+            "wireProtocol": "Swd",
+            "speed": 50_000,
+            "probe": { "vendor_id": 0x0483, "product_id": 0x3748 }
+        });
+
+        let finding = super::clamp_speed(&mut config).unwrap();
+        assert_eq!(finding.code, "speed-clamped");
+        assert_eq!(config["speed"], 4_000);
+
+        assert!(super::clamp_speed(&mut config).is_none());
+    }
+
+    #[test]
+    fn clamp_speed_unsupported_protocol() {
+        let mut config = json!({
+            "wireProtocol": "Swd",
+            "speed": 4_000,
+            "probe": { "vendor_id": 0x0403, "product_id": 0x6010 }
+        });
+
+        assert!(super::clamp_speed(&mut config).is_none());
+        assert_eq!(config["speed"], 4_000);
+    }
+
+    #[test]
+    fn clamp_speed_to_protocol() {
+        let mut config = json!({ "wireProtocol": "Jtag", "speed": 100_000 });
+        assert!(super::clamp_speed(&mut config).is_some());
+        assert_eq!(config["speed"], 50_000);
+
+        let mut config = json!({ "speed": 100_000 });
+        assert!(super::clamp_speed(&mut config).is_none());
+        assert_eq!(config["speed"], 100_000);
+    }
+
+    #[test]
+    fn resolve_chip_alias() {
+        let mut config = json!({ "chip": "Blue Pill" });
+
+        let finding = super::resolve_chip_alias(&mut config).unwrap();
+        assert_eq!(finding.code, "chip-alias");
+        assert_eq!(config["chip"], "STM32F103C8");
+
+        assert!(super::resolve_chip_alias(&mut config).is_none());
+    }
+
+    #[test]
+    fn speed_zero() {
+        assert_eq!(codes(json!({ "speed": 0 })), ["speed-zero"]);
+    }
+
+    #[test]
+    fn valid_probe_pair() {
+        assert!(
+            codes(json!({
+                "chip": "STM32F401RETx",
+                "wireProtocol": "Swd",
+                "probe": { "vendor_id": 0x0483, "product_id": 0x374b }
+            }))
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn stlink_with_riscv_chip() {
+        assert_eq!(
+            codes(json!({
+                "chip": "esp32c3",
+                "wireProtocol": "Jtag",
+                "probe": { "vendor_id": 0x0483, "product_id": 0x3748 }
+            })),
+            ["probe-chip"]
+        );
+    }
+
+    #[test]
+    fn swd_on_jtag_only_probe() {
+        assert_eq!(
+            codes(json!({
+                "chip": "esp32c3",
+                "wireProtocol": "Swd",
+                "probe": { "vendor_id": 0x303a, "product_id": 0x1001 }
+            })),
+            ["swd-non-arm", "probe-protocol"]
+        );
+    }
+
+    #[test]
+    fn esp_usb_jtag_with_other_chip() {
+        assert_eq!(
+            codes(json!({
+                "chip": "nRF52840_xxAA",
+                "probe": { "vendor_id": 0x303a, "product_id": 0x1001 }
+            })),
+            ["probe-chip"]
+        );
+    }
+
+    #[test]
+    fn halt_without_flashing() {
+        assert_eq!(
+            codes(json!({ "flashingConfig": { "haltAfterReset": true } })),
+            ["halt-without-flashing"]
+        );
+        assert!(
+            codes(json!({
+                "flashingConfig": { "flashingEnabled": true, "haltAfterReset": true }
+            }))
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn duplicate_images() {
+        let config = json!({
+            "coreConfigs": [
+                { "programBinary": "target/app" },
+                { "programBinary": "target/app" }
+            ],
+            "additionalImages": [
+                { "path": "boot.bin", "format": "Bin", "baseAddress": 0 },
+                { "path": "slot0.bin", "format": "Bin", "baseAddress": 0 },
+                { "path": "target/app" }
+            ]
+        });
+
+        let findings = super::validate(&config);
+        let findings: Vec<_> = findings
+            .iter()
+            .map(|finding| (finding.code, finding.path.as_str()))
+            .collect();
+
+        assert_eq!(
+            findings,
+            [
+                ("duplicate-image", "coreConfigs[1].programBinary"),
+                ("duplicate-image", "additionalImages[2].path"),
+                ("image-overlap", "additionalImages[1].baseAddress"),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_image() {
+        assert_eq!(
+            codes(json!({ "additionalImages": [{ "format": "Bin" }] })),
+            ["invalid-image"]
+        );
+    }
+
+    #[test]
+    fn check_chip() {
+        let chips = ["nRF52840_xxAA".to_string()];
+
+        assert_eq!(
+            super::check_chip(&json!({ "chip": "nRF54L15" }), &chips)
+                .unwrap()
+                .code,
+            "unknown-chip"
+        );
+        assert_eq!(
+            super::check_chip(&json!({ "chip": "nrf52840_xxaa" }), &chips),
+            None
+        );
+        assert_eq!(
+            super::check_chip(
+                &json!({ "chip": "MyBoard", "chipDescriptionPath": "board.yaml" }),
+                &chips
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn check_connected() {
+        let probes = [ConnectedProbe {
+            vendor_id: 0x0483,
+            product_id: 0x374b,
+            serial: Some("0671FF".to_string()),
+        }];
+
+        let check = |config| super::check_connected(&config, &probes).map(|f| f.code);
+
+        assert_eq!(check(json!({})), None);
+        assert_eq!(
+            check(json!({ "probe": { "vendor_id": 0x0483, "product_id": 0x374b } })),
+            None
+        );
+        assert_eq!(
+            check(json!({
+                "probe": { "vendor_id": 0x0483, "product_id": 0x374b, "serial_number": "1234" }
+            })),
+            Some("probe-not-connected")
+        );
+        assert_eq!(
+            super::check_connected(&json!({}), &[]).map(|f| f.code),
+            Some("no-probes")
+        );
+    }
+
+    #[test]
+    fn core_halt() {
+        let flashing = json!({ "flashingEnabled": true });
+
+        assert_eq!(
+            codes(json!({
+                "flashingConfig": flashing,
+                "coreConfigs": [{ "haltAfterReset": true }, { "haltAfterReset": false }]
+            })),
+            ["core-halt-mixed"]
+        );
+        assert!(
+            codes(json!({
+                "flashingConfig": flashing,
+                "coreConfigs": [{ "haltAfterReset": true }, { "haltAfterReset": true }]
+            }))
+            .is_empty()
+        );
+        assert_eq!(
+            codes(json!({
+                "flashingConfig": { "flashingEnabled": true, "haltAfterReset": false },
+                "coreConfigs": [{ "haltAfterReset": true }]
+            })),
+            ["core-halt-conflict"]
+        );
+        assert_eq!(
+            codes(json!({ "coreConfigs": [{ "haltAfterReset": true }] })),
+            ["halt-without-flashing"]
+        );
+    }
+
+    #[test]
+    fn check_connect_under_reset() {
+        let config = json!({ "chip": "RP2040", "connectUnderReset": true });
+        let finding = super::check_connect_under_reset(&config, &[]).unwrap();
+        assert_eq!(finding.code, "connect-under-reset-forbidden");
+
+        assert!(super::check_connect_under_reset(&json!({ "chip": "RP2040" }), &[]).is_none());
+
+        let flags = [("STM32L0".to_string(), ResetConnection::Required)];
+        let config = json!({ "chip": "STM32L072CZYx" });
+        let finding = super::check_connect_under_reset(&config, &flags).unwrap();
+        assert_eq!(finding.code, "connect-under-reset-required");
+    }
+}
@@ -0,0 +1,146 @@
+//! Zed task variables, like `$ZED_WORKTREE_ROOT` or `$ZED_FILE`, in configuration values.
+//!
+//! Zed replaces the task variables it knows before the configuration reaches the extension.
+//! Variables which are left, e.g. `$ZED_FILE` without an open file, would end up verbatim in
+//! paths passed to probe-rs, so they are reported instead. The worktree root is known to the
+//! extension, and replaced here as well.
+
+use zed_extension_api::serde_json::Value;
+
+/// Replace the task variables in all strings of the configuration.
+///
+/// Both `$ZED_NAME` and `${ZED_NAME}` are supported, and `${ZED_NAME:default}` uses the default
+/// if the variable is not available.
+pub fn substitute(config: &mut Value, worktree_root: &str) -> Result<(), String> {
+    let known = [("ZED_WORKTREE_ROOT", worktree_root)];
+
+    substitute_value(config, "", &known)
+}
+
+fn substitute_value(value: &mut Value, path: &str, known: &[(&str, &str)]) -> Result<(), String> {
+    match value {
+        Value::String(string) => {
+            *string = substitute_str(string, known).map_err(|name| {
+                format!(
+                    "The task variable ${name} used in '{path}' is not available. Zed only sets some variables in certain situations, e.g. $ZED_FILE only when a file is open. Use ${{{name}:default}} to provide a default."
+                )
+            })?;
+        }
+        Value::Array(values) => {
+            for (index, value) in values.iter_mut().enumerate() {
+                substitute_value(value, &format!("{path}[{index}]"), known)?;
+            }
+        }
+        Value::Object(values) => {
+            for (key, value) in values.iter_mut() {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+
+                substitute_value(value, &path, known)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Replace the task variables in a string, returning the name of an unknown variable.
+fn substitute_str(value: &str, known: &[(&str, &str)]) -> Result<String, String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        let (variable, default, length) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => {
+                    let (name, default) = match braced[..end].split_once(':') {
+                        Some((name, default)) => (name, Some(default)),
+                        None => (&braced[..end], None),
+                    };
+                    (name, default, end + 2)
+                }
+                None => ("", None, 0),
+            },
+            None => {
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                (&rest[..end], None, end)
+            }
+        };
+
+        if !variable.starts_with("ZED_") {
+            // Not a task variable, e.g. an environment variable for a shell.
+            result.push('$');
+            continue;
+        }
+
+        let value = known
+            .iter()
+            .find(|(name, _)| *name == variable)
+            .map(|(_, value)| *value)
+            .or(default)
+            .ok_or_else(|| variable.to_string())?;
+
+        result.push_str(value);
+        rest = &rest[length..];
+    }
+
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::serde_json::json;
+
+    const KNOWN: &[(&str, &str)] = &[("ZED_WORKTREE_ROOT", "/project")];
+
+    #[test]
+    fn substitute_str() {
+        let substitute = |value| super::substitute_str(value, KNOWN);
+
+        assert_eq!(
+            substitute("$ZED_WORKTREE_ROOT/target").unwrap(),
+            "/project/target"
+        );
+        assert_eq!(
+            substitute("${ZED_WORKTREE_ROOT}_build").unwrap(),
+            "/project_build"
+        );
+        assert_eq!(
+            substitute("${ZED_FILE:src/main.rs}").unwrap(),
+            "src/main.rs"
+        );
+        assert_eq!(substitute("$HOME/$ 5").unwrap(), "$HOME/$ 5");
+        assert_eq!(substitute("$ZED_FILE").unwrap_err(), "ZED_FILE");
+    }
+
+    #[test]
+    fn substitute_config() {
+        let mut config = json!({
+            "cwd": "$ZED_WORKTREE_ROOT",
+            "coreConfigs": [{ "programBinary": "${ZED_WORKTREE_ROOT}/target/app", "coreIndex": 0 }]
+        });
+
+        super::substitute(&mut config, "/project").unwrap();
+        assert_eq!(config["cwd"], "/project");
+        assert_eq!(
+            config["coreConfigs"][0]["programBinary"],
+            "/project/target/app"
+        );
+
+        let mut config = json!({ "coreConfigs": [{ "programBinary": "$ZED_DIRNAME/app" }] });
+        let err = super::substitute(&mut config, "/project").unwrap_err();
+        assert!(err.contains("$ZED_DIRNAME"), "{err}");
+        assert!(err.contains("'coreConfigs[0].programBinary'"), "{err}");
+    }
+}
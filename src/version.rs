@@ -0,0 +1,161 @@
+//! Versions of probe-rs.
+
+use std::fmt;
+
+use zed_extension_api::Worktree;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+/// The oldest probe-rs which supports everything the extension uses, like the `dap-server`
+/// options and the `--binary-format` option of `probe-rs download`.
+pub const MINIMUM: Version = Version {
+    major: 0,
+    minor: 24,
+    patch: 0,
+};
+
+impl Version {
+    /// Parse a version like `0.24.0`, optionally prefixed with `v`.
+    pub fn parse(version: &str) -> Option<Self> {
+        let version = version.trim().trim_start_matches('v');
+        // Ignore pre-release and build metadata, like `0.25.0-alpha.1`.
+        let version = version.split(['-', '+']).next()?;
+
+        let mut parts = version.split('.').map(|part| part.parse().ok());
+
+        let version = Self {
+            major: parts.next()??,
+            minor: parts.next()??,
+            patch: parts.next().unwrap_or(Some(0))?,
+        };
+
+        parts.next().is_none().then_some(version)
+    }
+
+    /// Find the version in the output of `probe-rs --version`, e.g.
+    /// `probe-rs 0.24.0 (git commit: 1a2b3c4)`.
+    pub fn from_version_output(output: &str) -> Option<Self> {
+        output.split_whitespace().find_map(Self::parse)
+    }
+}
+
+/// Refuse versions older than [`MINIMUM`], which would only fail with confusing errors later.
+pub fn check_minimum(version: Version) -> Result<(), String> {
+    if version >= MINIMUM {
+        return Ok(());
+    }
+
+    Err(format!(
+        "probe-rs {version} is too old, the extension needs probe-rs {MINIMUM} or newer. Update it, \
+         see <https://probe.rs/docs/getting-started/installation/>."
+    ))
+}
+
+/// File in the worktree root which pins the version of probe-rs, like `rust-toolchain` does
+/// for rustc.
+pub const VERSION_FILE: &str = ".probe-rs-version";
+
+/// Read the version from a version file, which contains just the version, like `0.24.0`.
+/// Lines starting with `#` are comments.
+fn parse_version_file(contents: &str) -> Result<Option<String>, String> {
+    let mut lines = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+    let Some(version) = lines.next() else {
+        return Ok(None);
+    };
+
+    if Version::parse(version).is_none() || lines.next().is_some() {
+        return Err(format!(
+            "'{VERSION_FILE}' must only contain a probe-rs version like 0.24.0"
+        ));
+    }
+
+    Ok(Some(version.to_string()))
+}
+
+/// The version pinned by the version file of the worktree, if it has one.
+pub fn pinned_by_file(worktree: &Worktree) -> Option<String> {
+    let contents = crate::files::read_optional(
+        worktree,
+        VERSION_FILE,
+        "the version of probe-rs is not pinned by it",
+    )?;
+
+    match parse_version_file(&contents) {
+        Ok(version) => version,
+        Err(err) => {
+            log!("Ignoring '{VERSION_FILE}': {err}");
+            None
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Version;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Version::parse("0.24.0"),
+            Some(Version {
+                major: 0,
+                minor: 24,
+                patch: 0
+            })
+        );
+        assert_eq!(Version::parse("v0.25.1-alpha.1").unwrap().patch, 1);
+        assert_eq!(Version::parse("0.24").unwrap().to_string(), "0.24.0");
+        assert_eq!(Version::parse("0.24.0.1"), None);
+        assert_eq!(Version::parse("probe-rs"), None);
+    }
+
+    #[test]
+    fn from_version_output() {
+        assert_eq!(
+            Version::from_version_output("probe-rs 0.24.0 (git commit: 1a2b3c4)\n")
+                .unwrap()
+                .to_string(),
+            "0.24.0"
+        );
+        assert!(Version::parse("1.2.3") > Version::parse("1.2"));
+    }
+
+    #[test]
+    fn check_minimum() {
+        assert!(super::check_minimum(super::MINIMUM).is_ok());
+        assert!(super::check_minimum(Version::parse("0.26.1").unwrap()).is_ok());
+
+        let err = super::check_minimum(Version::parse("0.21.1").unwrap()).unwrap_err();
+        assert!(err.starts_with("probe-rs 0.21.1 is too old, the extension needs probe-rs 0.24.0"));
+    }
+
+    #[test]
+    fn parse_version_file() {
+        assert_eq!(
+            super::parse_version_file("0.24.0\n"),
+            Ok(Some("0.24.0".to_string()))
+        );
+        assert_eq!(
+            super::parse_version_file("# Pinned for the lab boards\nv0.25.0\n"),
+            Ok(Some("v0.25.0".to_string()))
+        );
+        assert_eq!(super::parse_version_file("\n"), Ok(None));
+        assert!(super::parse_version_file("latest").is_err());
+        assert!(super::parse_version_file("0.24.0\n0.25.0").is_err());
+    }
+}
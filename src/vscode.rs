@@ -0,0 +1,114 @@
+//! Reusing the probe-rs binary installed for the probe-rs extension of VS Code.
+//!
+//! Users coming from VS Code often have a probe-rs which the probe-rs extension of VS Code
+//! installed next to itself, without having it on the `PATH`. That binary is used before
+//! downloading another copy. The extension directories are outside of the extension sandbox,
+//! so they are listed with a tool of the host, and the binaries are checked by running them.
+
+use zed_extension_api::{self as zed, Os, Worktree};
+
+use crate::version::Version;
+
+/// Prefix of the directories of the probe-rs extension, which end with its version.
+const EXTENSION_PREFIX: &str = "probe-rs.probe-rs-debugger-";
+
+/// Directories with VS Code extensions relative to the home directory, for VS Code, its
+/// Insiders builds, the open source builds, remote development and Cursor.
+const EXTENSION_DIRECTORIES: &[&str] = &[
+    ".vscode/extensions",
+    ".vscode-insiders/extensions",
+    ".vscode-oss/extensions",
+    ".vscode-server/extensions",
+    ".cursor/extensions",
+];
+
+/// The installed versions of the probe-rs extension in a directory listing, newest first.
+fn extension_versions(listing: &str) -> Vec<&str> {
+    let mut names: Vec<(Version, &str)> = listing
+        .lines()
+        .map(str::trim)
+        .filter_map(|name| {
+            let version = name.strip_prefix(EXTENSION_PREFIX)?;
+            // Platform specific builds are named like `…-0.24.0-linux-x64`.
+            let version = version
+                .split_once('-')
+                .map_or(version, |(version, _)| version);
+
+            Some((Version::parse(version)?, name))
+        })
+        .collect();
+
+    names.sort_by_key(|(version, _)| std::cmp::Reverse(*version));
+    names.into_iter().map(|(_, name)| name).collect()
+}
+
+/// The places of the binary inside of the directory of the extension.
+fn binary_candidates(os: Os, directory: &str) -> [String; 2] {
+    let binary = if os == Os::Windows {
+        "probe-rs.exe"
+    } else {
+        "probe-rs"
+    };
+
+    [
+        format!("{directory}/bin/{binary}"),
+        format!("{directory}/{binary}"),
+    ]
+}
+
+/// The probe-rs binary of the VS Code extension, with exactly the `pinned` version if given.
+pub fn find(worktree: &Worktree, pinned: Option<&str>) -> Option<String> {
+    let (os, _) = zed::current_platform();
+    let home_variable = if os == Os::Windows {
+        "USERPROFILE"
+    } else {
+        "HOME"
+    };
+    let home = worktree
+        .shell_env()
+        .into_iter()
+        .find_map(|(name, value)| (name == home_variable).then_some(value))?;
+
+    EXTENSION_DIRECTORIES.iter().find_map(|extensions| {
+        let extensions = crate::paths::join(Some(&home), extensions);
+        let listing = crate::process::list_directory(os, &extensions)?;
+
+        extension_versions(&listing).into_iter().find_map(|name| {
+            binary_candidates(os, &format!("{extensions}/{name}"))
+                .into_iter()
+                .find(|binary| match pinned {
+                    Some(version) => crate::install::has_version(binary, version),
+                    None => crate::install::version(binary).is_some(),
+                })
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use zed_extension_api::Os;
+
+    #[test]
+    fn extension_versions() {
+        let listing = "ms-vscode.cpptools-1.20.5-linux-x64\n\
+                       probe-rs.probe-rs-debugger-0.23.1\n\
+                       probe-rs.probe-rs-debugger-0.24.2-linux-x64\n\
+                       rust-lang.rust-analyzer-0.3.1950-linux-x64\n";
+
+        assert_eq!(
+            super::extension_versions(listing),
+            [
+                "probe-rs.probe-rs-debugger-0.24.2-linux-x64",
+                "probe-rs.probe-rs-debugger-0.23.1"
+            ]
+        );
+    }
+
+    #[test]
+    fn binary_candidates() {
+        assert_eq!(
+            super::binary_candidates(Os::Windows, "C:/Users/me/.vscode/extensions/x")[0],
+            "C:/Users/me/.vscode/extensions/x/bin/probe-rs.exe"
+        );
+    }
+}